@@ -0,0 +1,90 @@
+/* Fcmp file compare utility. Generated by cbindgen; do not edit. */
+
+#ifndef FCMP_H
+#define FCMP_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * The status codes returned by the C interface.
+ */
+typedef enum FcmpStatus {
+  /**
+   * The operation succeeded.
+   */
+  FCMP_STATUS_OK = 0,
+  /**
+   * A required pointer was null.
+   */
+  FCMP_STATUS_NULL_POINTER = 1,
+  /**
+   * A path was not valid for the platform.
+   */
+  FCMP_STATUS_INVALID_PATH = 2,
+  /**
+   * The files can't be ordered.
+   */
+  FCMP_STATUS_INCOMPARABLE = 3,
+  /**
+   * A file is missing.
+   */
+  FCMP_STATUS_MISSING = 4,
+  /**
+   * A file couldn't be read.
+   */
+  FCMP_STATUS_IO = 5,
+  /**
+   * The comparison failed for another reason.
+   */
+  FCMP_STATUS_ERROR = 6,
+  /**
+   * The comparison panicked.
+   */
+  FCMP_STATUS_PANIC = 7,
+} FcmpStatus;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Compares the modification times of the files at the given paths.
+ *
+ * Stores -1, 0, or 1 in `ordering` if the first file is older than, the same
+ * age as, or newer than the second. A missing file is older than any other
+ * file.
+ *
+ * Returns `FCMP_STATUS_INCOMPARABLE` without storing an ordering if the
+ * files can't be ordered.
+ *
+ * ### Safety
+ *
+ * `a` and `b` must be null or point to NUL-terminated strings, and
+ * `ordering` must be null or point to a writable `int32_t`.
+ */
+enum FcmpStatus fcmp_compare_paths(const char *a, const char *b, int32_t *ordering);
+
+/**
+ * Finds the most recently modified of the files at the given paths, storing
+ * its index in `index`. If several files are the most recently modified, the
+ * first of them is selected.
+ *
+ * ### Safety
+ *
+ * `paths` must be null or point to `len` pointers which are null or point to
+ * NUL-terminated strings, and `index` must be null or point to a writable
+ * `size_t`.
+ */
+enum FcmpStatus fcmp_newest_index(const char *const *paths, size_t len, size_t *index);
+
+/**
+ * Returns a static, NUL-terminated description of the given status code.
+ */
+const char *fcmp_status_message(enum FcmpStatus status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FCMP_H */
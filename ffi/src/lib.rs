@@ -0,0 +1,307 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! C interface to fcmp.
+//!
+//! The functions are declared in `include/fcmp.h`, which is generated with
+//! `cbindgen` and must be regenerated when they change.
+////////////////////////////////////////////////////////////////////////////////
+#![forbid(non_ascii_idents)]
+#![deny(keyword_idents)]
+#![deny(macro_use_extern_crate)]
+#![deny(missing_abi)]
+#![deny(unsafe_op_in_unsafe_fn)]
+#![warn(absolute_paths_not_starting_with_crate)]
+#![warn(anonymous_parameters)]
+#![warn(bad_style)]
+#![warn(bare_trait_objects)]
+#![warn(dead_code)]
+#![warn(elided_lifetimes_in_paths)]
+#![warn(improper_ctypes)]
+#![warn(missing_copy_implementations)]
+#![warn(missing_debug_implementations)]
+#![warn(missing_doc_code_examples)]
+#![warn(missing_docs)]
+// #![warn(must_not_suspend)] // Unstable
+#![warn(no_mangle_generic_items)]
+// #![warn(non_exhaustive_omitted_patterns)] // Unstable.
+#![warn(non_shorthand_field_patterns)]
+#![warn(nonstandard_style)]
+#![warn(noop_method_call)]
+#![warn(overflowing_literals)]
+#![warn(path_statements)]
+#![warn(patterns_in_fns_without_body)]
+#![warn(rust_2018_idioms)]
+// #![warn(single_use_lifetimes)] // False positives.
+#![warn(trivial_casts)]
+#![warn(trivial_numeric_casts)]
+#![warn(unconditional_recursion)]
+#![warn(unreachable_pub)]
+#![warn(unused)]
+#![warn(unused_allocation)]
+#![warn(unused_comparisons)]
+#![warn(unused_crate_dependencies)]
+#![warn(unused_extern_crates)]
+#![warn(unused_import_braces)]
+#![warn(unused_lifetimes)]
+#![warn(unused_parens)]
+#![warn(unused_qualifications)]
+#![warn(unused_results)]
+#![warn(variant_size_differences)]
+#![warn(while_true)]
+
+// Clippy groups.
+#![warn(clippy::cargo)]
+#![warn(clippy::nursery)]
+#![warn(clippy::pedantic)]
+
+// Clippy restriction lints.
+#![warn(clippy::clone_on_ref_ptr)]
+#![warn(clippy::create_dir)]
+#![warn(clippy::dbg_macro)]
+#![warn(clippy::decimal_literal_representation)]
+#![warn(clippy::exit)]
+#![warn(clippy::filetype_is_file)]
+#![warn(clippy::float_cmp_const)]
+#![warn(clippy::if_then_some_else_none)]
+#![warn(clippy::lossy_float_literal)]
+#![warn(clippy::map_err_ignore)]
+#![warn(clippy::mem_forget)]
+#![warn(clippy::missing_docs_in_private_items)]
+#![warn(clippy::missing_enforced_import_renames)]
+#![warn(clippy::mod_module_files)]
+#![warn(clippy::multiple_inherent_impl)]
+#![warn(clippy::print_stderr)]
+#![warn(clippy::print_stdout)]
+#![warn(clippy::rc_buffer)]
+#![warn(clippy::rest_pat_in_fully_bound_structs)]
+#![warn(clippy::string_add)]
+#![warn(clippy::unneeded_field_pattern)]
+#![warn(clippy::verbose_file_reads)]
+
+// Non-improvement lints.
+#![allow(clippy::cast_precision_loss)]
+#![allow(clippy::default_trait_access)]
+#![allow(clippy::enum_glob_use)]
+#![allow(clippy::match_bool)]
+#![allow(clippy::multiple_crate_versions)]
+#![allow(clippy::single_match_else)]
+#![allow(clippy::unseparated_literal_suffix)]
+
+// Unreliable lints. May be enabled for spot checking.
+#![allow(clippy::inline_always)]
+#![allow(clippy::module_name_repetitions)]
+#![allow(clippy::wildcard_imports)]
+#![allow(clippy::missing_const_for_fn)]
+#![allow(clippy::redundant_pub_crate)] // Conflicts with unreachable_pub.
+#![allow(clippy::shadow_unrelated)] // Does not work correctly.
+
+// TODO: Remove these when error handling is more mature:
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+
+// External library imports.
+use fcmp::compare_all;
+use fcmp::Comparator;
+use fcmp::partial_cmp_paths;
+use fcmp::FcmpError;
+
+// Standard library imports.
+use std::ffi::c_char;
+use std::ffi::CStr;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FcmpStatus
+////////////////////////////////////////////////////////////////////////////////
+/// The status codes returned by the C interface.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FcmpStatus {
+    /// The operation succeeded.
+    Ok = 0,
+    /// A required pointer was null.
+    NullPointer = 1,
+    /// A path was not valid for the platform.
+    InvalidPath = 2,
+    /// The files can't be ordered.
+    Incomparable = 3,
+    /// A file is missing.
+    Missing = 4,
+    /// A file couldn't be read.
+    Io = 5,
+    /// The comparison failed for another reason.
+    Error = 6,
+    /// The comparison panicked.
+    Panic = 7,
+}
+
+impl From<&FcmpError> for FcmpStatus {
+    fn from(error: &FcmpError) -> Self {
+        match error {
+            FcmpError::Missing { .. }
+                | FcmpError::BrokenSymlink { .. } => Self::Missing,
+            FcmpError::Io { .. }
+                | FcmpError::Unreadable { .. }
+                | FcmpError::Diff { .. } => Self::Io,
+            _ => Self::Error,
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// fcmp_compare_paths
+////////////////////////////////////////////////////////////////////////////////
+/// Compares the modification times of the files at the given paths.
+///
+/// Stores -1, 0, or 1 in `ordering` if the first file is older than, the same
+/// age as, or newer than the second. A missing file is older than any other
+/// file.
+///
+/// Returns `FCMP_STATUS_INCOMPARABLE` without storing an ordering if the
+/// files can't be ordered.
+///
+/// ### Safety
+///
+/// `a` and `b` must be null or point to NUL-terminated strings, and
+/// `ordering` must be null or point to a writable `int32_t`.
+#[no_mangle]
+pub unsafe extern "C" fn fcmp_compare_paths(
+    a: *const c_char,
+    b: *const c_char,
+    ordering: *mut i32)
+    -> FcmpStatus
+{
+    if ordering.is_null() { return FcmpStatus::NullPointer; }
+    // SAFETY: The caller guarantees the paths are valid if non-null.
+    let (a, b) = match unsafe { (path(a), path(b)) } {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(status), _) | (_, Err(status)) => return status,
+    };
+
+    guard(|| {
+        match partial_cmp_paths(&a, &b, &Comparator::default()) {
+            Ok(outcome) => outcome.ordering().map_or(
+                FcmpStatus::Incomparable,
+                |ord| {
+                    // SAFETY: The caller guarantees `ordering` is writable.
+                    unsafe { ordering.write(ord as i32) };
+                    FcmpStatus::Ok
+                }),
+            Err(e) => FcmpStatus::from(&e),
+        }
+    })
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// fcmp_newest_index
+////////////////////////////////////////////////////////////////////////////////
+/// Finds the most recently modified of the files at the given paths, storing
+/// its index in `index`. If several files are the most recently modified, the
+/// first of them is selected.
+///
+/// ### Safety
+///
+/// `paths` must be null or point to `len` pointers which are null or point to
+/// NUL-terminated strings, and `index` must be null or point to a writable
+/// `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn fcmp_newest_index(
+    paths: *const *const c_char,
+    len: usize,
+    index: *mut usize)
+    -> FcmpStatus
+{
+    if paths.is_null() || index.is_null() { return FcmpStatus::NullPointer; }
+    // SAFETY: The caller guarantees `paths` points to `len` pointers.
+    let ptrs = unsafe { std::slice::from_raw_parts(paths, len) };
+    let mut owned = Vec::with_capacity(len);
+    for &ptr in ptrs {
+        // SAFETY: The caller guarantees each path is valid if non-null.
+        match unsafe { path(ptr) } {
+            Ok(path) => owned.push(path),
+            Err(status) => return status,
+        }
+    }
+
+    guard(|| {
+        match compare_all(
+            owned.iter().map(PathBuf::as_path),
+            false,
+            &Comparator::default())
+        {
+            Ok(idx) => {
+                // SAFETY: The caller guarantees `index` is writable.
+                unsafe { index.write(idx) };
+                FcmpStatus::Ok
+            },
+            Err(e) => FcmpStatus::from(&e),
+        }
+    })
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// fcmp_status_message
+////////////////////////////////////////////////////////////////////////////////
+/// Returns a static, NUL-terminated description of the given status code.
+#[no_mangle]
+pub extern "C" fn fcmp_status_message(status: FcmpStatus) -> *const c_char {
+    let message: &'static CStr = match status {
+        FcmpStatus::Ok           => c"ok",
+        FcmpStatus::NullPointer  => c"null pointer",
+        FcmpStatus::InvalidPath  => c"invalid path",
+        FcmpStatus::Incomparable => c"files can't be ordered",
+        FcmpStatus::Missing      => c"file is missing",
+        FcmpStatus::Io           => c"failed to read file",
+        FcmpStatus::Error        => c"comparison failed",
+        FcmpStatus::Panic        => c"comparison panicked",
+    };
+    message.as_ptr()
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helpers
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the path given by the NUL-terminated string.
+///
+/// ### Safety
+///
+/// `ptr` must be null or point to a NUL-terminated string.
+unsafe fn path(ptr: *const c_char) -> Result<PathBuf, FcmpStatus> {
+    if ptr.is_null() { return Err(FcmpStatus::NullPointer); }
+    // SAFETY: The caller guarantees `ptr` points to a NUL-terminated string.
+    let bytes = unsafe { CStr::from_ptr(ptr) }.to_bytes();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt as _;
+        Ok(PathBuf::from(std::ffi::OsStr::from_bytes(bytes)))
+    }
+    #[cfg(not(unix))]
+    {
+        std::str::from_utf8(bytes)
+            .ok()
+            .map(PathBuf::from)
+            .ok_or(FcmpStatus::InvalidPath)
+    }
+}
+
+/// Calls the given function, returning `FCMP_STATUS_PANIC` if it panics, as
+/// unwinding into C is undefined behavior.
+fn guard<F>(f: F) -> FcmpStatus
+    where F: FnOnce() -> FcmpStatus
+{
+    std::panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or(FcmpStatus::Panic)
+}
@@ -0,0 +1,430 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Files inside zip and tar archives.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+use flate2::read::DeflateDecoder;
+use flate2::read::MultiGzDecoder;
+use sha2::Digest as _;
+use sha2::Sha256;
+
+// Standard library imports.
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::Cursor;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Seek as _;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+/// The separator between the path of an archive and the path of a member
+/// inside it, as in `backup.zip!/etc/hosts`.
+pub const MEMBER_SEPARATOR: &str = "!/";
+
+/// The maximum number of tar archive indexes cached by [`tar_index`].
+///
+/// [`tar_index`]: tar_index
+const TAR_INDEX_CACHE_CAPACITY: usize = 64;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ArchiveFormat
+////////////////////////////////////////////////////////////////////////////////
+/// The format of an archive, determined by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// A zip archive, with a `.zip` extension.
+    Zip,
+    /// A tar archive, with a `.tar` extension.
+    Tar,
+    /// A gzip-compressed tar archive, with a `.tar.gz` or `.tgz` extension.
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Returns the format of the archive at the given path, if its extension
+    /// is recognized.
+    #[must_use]
+    pub fn of(path: &Path) -> Option<Self> {
+        let has_extension = |path: &Path, ext: &str| path.extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case(ext));
+        let stem = Path::new(path.file_stem()?);
+
+        if has_extension(path, "zip") {
+            Some(Self::Zip)
+        } else if has_extension(path, "tar") {
+            Some(Self::Tar)
+        } else if has_extension(path, "tgz")
+            || (has_extension(path, "gz") && has_extension(stem, "tar"))
+        {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// MemberMetadata
+////////////////////////////////////////////////////////////////////////////////
+/// The metadata of a file inside an archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemberMetadata {
+    /// The uncompressed size of the member in bytes.
+    pub len: u64,
+    /// The modification time of the member, if it is recorded. Zip archives
+    /// record times without a timezone, which are taken to be UTC.
+    pub modified: Option<SystemTime>,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ArchivePath
+////////////////////////////////////////////////////////////////////////////////
+/// The path of a file inside an archive, written as the path of the archive
+/// and the path of the member separated by `!/`, as in `backup.zip!/etc/hosts`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivePath {
+    /// The path of the archive.
+    pub archive: PathBuf,
+    /// The format of the archive.
+    pub format: ArchiveFormat,
+    /// The path of the member inside the archive, without a leading `/`.
+    pub member: String,
+}
+
+impl ArchivePath {
+    /// Parses an `ArchivePath` from the given path. Returns `None` if the path
+    /// does not contain a member separator following the path of an existing
+    /// archive in a recognized format.
+    #[must_use]
+    pub fn parse(path: &Path) -> Option<Self> {
+        let path = path.to_str()?;
+        let (archive, member) = path.split_once(MEMBER_SEPARATOR)?;
+        let archive = PathBuf::from(archive);
+        let format = ArchiveFormat::of(&archive)?;
+        if !archive.is_file() { return None; }
+
+        Some(Self {
+            archive,
+            format,
+            member: member.trim_start_matches('/').to_string(),
+        })
+    }
+
+    /// Returns the metadata of the member, or `None` if the archive has no
+    /// such file.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the archive can't be read.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn metadata(&self) -> Result<Option<MemberMetadata>, std::io::Error> {
+        match self.format {
+            ArchiveFormat::Zip => {
+                let file = BufReader::new(File::open(&self.archive)?);
+                let mut archive = zip::ZipArchive::new(file)?;
+                let Some(member) = zip_member(&mut archive, &self.member)?
+                    else { return Ok(None) };
+                Ok(Some(MemberMetadata {
+                    len: member.size(),
+                    modified: zip_time(member.last_modified()),
+                }))
+            },
+            ArchiveFormat::Tar | ArchiveFormat::TarGz => Ok(self
+                .tar_member()?
+                .map(|member| member.metadata)),
+        }
+    }
+
+    /// Returns a reader of the content of the member, or `None` if the
+    /// archive has no such file.
+    ///
+    /// The content is read from the archive as the reader is read. Members of
+    /// zip archives which are neither stored nor deflated are read into
+    /// memory.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the archive can't be read.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn open(&self) -> Result<Option<Box<dyn Read>>, std::io::Error> {
+        match self.format {
+            ArchiveFormat::Zip => self.open_zip_member(),
+            ArchiveFormat::Tar => {
+                let Some(member) = self.tar_member()?
+                    else { return Ok(None) };
+                let mut file = File::open(&self.archive)?;
+                let _ = file.seek(SeekFrom::Start(member.offset))?;
+                Ok(Some(Box::new(
+                    BufReader::new(file).take(member.metadata.len))))
+            },
+            ArchiveFormat::TarGz => {
+                let Some(member) = self.tar_member()?
+                    else { return Ok(None) };
+                let file = BufReader::new(File::open(&self.archive)?);
+                let mut decoder = MultiGzDecoder::new(file);
+                // Decompressed content can't be seeked, so it is skipped.
+                let skipped = std::io::copy(
+                    &mut (&mut decoder).take(member.offset),
+                    &mut std::io::sink())?;
+                if skipped < member.offset {
+                    return Err(ErrorKind::UnexpectedEof.into());
+                }
+                Ok(Some(Box::new(decoder.take(member.metadata.len))))
+            },
+        }
+    }
+
+    /// Returns a reader of the content of the member of a zip archive, or
+    /// `None` if the archive has no such file.
+    fn open_zip_member(&self)
+        -> Result<Option<Box<dyn Read>>, std::io::Error>
+    {
+        let file = BufReader::new(File::open(&self.archive)?);
+        let mut archive = zip::ZipArchive::new(file)?;
+        let Some(mut member) = zip_member(&mut archive, &self.member)?
+            else { return Ok(None) };
+
+        let compression = member.compression();
+        if !matches!(compression,
+            zip::CompressionMethod::Stored | zip::CompressionMethod::Deflated)
+        {
+            let capacity = usize::try_from(member.size()).unwrap_or(0);
+            let mut content = Vec::with_capacity(capacity);
+            let _ = member.read_to_end(&mut content)?;
+            return Ok(Some(Box::new(Cursor::new(content))));
+        }
+
+        let mut file = File::open(&self.archive)?;
+        let _ = file.seek(SeekFrom::Start(member.data_start()))?;
+        let data = BufReader::new(file).take(member.compressed_size());
+        Ok(Some(match compression {
+            zip::CompressionMethod::Stored => Box::new(data),
+            _ => Box::new(DeflateDecoder::new(data)),
+        }))
+    }
+
+    /// Returns the location and metadata of the member of a tar archive, or
+    /// `None` if the archive has no such file.
+    fn tar_member(&self) -> Result<Option<TarMember>, std::io::Error> {
+        let index = tar_index(&self.archive, self.format)?;
+        Ok(index.members.get(Path::new(&self.member)).copied())
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// TarIndex
+////////////////////////////////////////////////////////////////////////////////
+/// The location and metadata of a file inside a tar archive.
+#[derive(Debug, Clone, Copy)]
+struct TarMember {
+    /// The offset of the member's content in the uncompressed archive.
+    offset: u64,
+    /// The metadata of the member.
+    metadata: MemberMetadata,
+}
+
+/// The files inside a tar archive, cached by [`tar_index`] so that the
+/// archive is only read once to find its members.
+///
+/// [`tar_index`]: tar_index
+#[derive(Debug)]
+struct TarIndex {
+    /// The size of the archive when it was indexed.
+    len: u64,
+    /// The modification time of the archive when it was indexed.
+    modified: Option<SystemTime>,
+    /// The files inside the archive, keyed by their path inside it. If a path
+    /// occurs more than once, the first file is used.
+    members: HashMap<PathBuf, TarMember>,
+}
+
+impl TarIndex {
+    /// Reads the index of the given tar archive.
+    fn read<R>(reader: R, len: u64, modified: Option<SystemTime>)
+        -> Result<Self, std::io::Error>
+        where R: Read
+    {
+        let mut members = HashMap::new();
+        for entry in tar::Archive::new(reader).entries()? {
+            let entry = entry?;
+            if !entry.header().entry_type().is_file() { continue; }
+            let member = TarMember {
+                offset: entry.raw_file_position(),
+                metadata: MemberMetadata {
+                    len: entry.size(),
+                    modified: entry.header().mtime().ok()
+                        .and_then(|secs| SystemTime::UNIX_EPOCH
+                            .checked_add(Duration::from_secs(secs))),
+                },
+            };
+            let _ = members
+                .entry(member_name(&entry.path()?))
+                .or_insert(member);
+        }
+        Ok(Self { len, modified, members })
+    }
+}
+
+/// Returns the index of the tar archive at the given path, reading it only if
+/// it is not cached or the archive's size or modification time has changed.
+fn tar_index(path: &Path, format: ArchiveFormat)
+    -> Result<Arc<TarIndex>, std::io::Error>
+{
+    static INDEXES: OnceLock<Mutex<HashMap<PathBuf, Arc<TarIndex>>>>
+        = OnceLock::new();
+    let indexes = INDEXES.get_or_init(Mutex::default);
+
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+    let (len, modified) = (metadata.len(), metadata.modified().ok());
+
+    let cached = indexes
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(path)
+        .cloned();
+    if let Some(index) = cached.filter(|index| {
+        index.len == len && index.modified.is_some()
+            && index.modified == modified
+    }) {
+        return Ok(index);
+    }
+
+    let file = BufReader::new(file);
+    let index = Arc::new(match format {
+        ArchiveFormat::TarGz => {
+            TarIndex::read(MultiGzDecoder::new(file), len, modified)?
+        },
+        _ => TarIndex::read(file, len, modified)?,
+    });
+
+    {
+        let mut indexes = indexes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if indexes.len() >= TAR_INDEX_CACHE_CAPACITY { indexes.clear(); }
+        let _ = indexes.insert(path.to_path_buf(), Arc::clone(&index));
+    }
+    Ok(index)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// member_digests
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the SHA-256 digest of the content of each file in the archive at
+/// the given path, keyed by the path of the file inside the archive.
+///
+/// Directories and other non-file members are not included, and neither are
+/// the order of the members or any archive-level metadata, so archives
+/// containing the same files have the same digests.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if the archive can't be read.
+///
+/// [`std::io::Error`]: std::io::Error
+pub fn member_digests(path: &Path, format: ArchiveFormat)
+    -> Result<BTreeMap<PathBuf, [u8; 32]>, std::io::Error>
+{
+    fn digest(reader: &mut dyn Read) -> Result<[u8; 32], std::io::Error> {
+        let mut hasher = Sha256::new();
+        let _ = std::io::copy(reader, &mut hasher)?;
+        Ok(hasher.finalize().into())
+    }
+
+    fn tar_digests<R>(reader: R)
+        -> Result<BTreeMap<PathBuf, [u8; 32]>, std::io::Error>
+        where R: Read
+    {
+        let mut digests = BTreeMap::new();
+        for entry in tar::Archive::new(reader).entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() { continue; }
+            let name = member_name(&entry.path()?);
+            let _ = digests.insert(name, digest(&mut entry)?);
+        }
+        Ok(digests)
+    }
+
+    let file = BufReader::new(File::open(path)?);
+    match format {
+        ArchiveFormat::Zip => {
+            let mut archive = zip::ZipArchive::new(file)?;
+            let mut digests = BTreeMap::new();
+            for idx in 0..archive.len() {
+                let mut member = archive.by_index(idx)?;
+                if member.is_dir() { continue; }
+                let name = member_name(Path::new(member.name()));
+                let _ = digests.insert(name, digest(&mut member)?);
+            }
+            Ok(digests)
+        },
+        ArchiveFormat::Tar => tar_digests(file),
+        ArchiveFormat::TarGz => tar_digests(MultiGzDecoder::new(file)),
+    }
+}
+
+
+/// Returns the file with the given path inside the given zip archive, or
+/// `None` if the archive has no such file.
+fn zip_member<'a, R>(archive: &'a mut zip::ZipArchive<R>, member: &str)
+    -> Result<Option<zip::read::ZipFile<'a>>, std::io::Error>
+    where R: Read + std::io::Seek
+{
+    match archive.by_name(member) {
+        Ok(member) if !member.is_dir() => Ok(Some(member)),
+        Ok(_) | Err(zip::result::ZipError::FileNotFound) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Returns the path of a member inside an archive without any leading `/` or
+/// `./`.
+fn member_name(path: &Path) -> PathBuf {
+    let name = path.strip_prefix("/").unwrap_or(path);
+    name.strip_prefix(".").unwrap_or(name).to_path_buf()
+}
+
+/// Converts a zip modification time to a `SystemTime`, treating it as UTC.
+fn zip_time(time: zip::DateTime) -> Option<SystemTime> {
+    // Days since the epoch of the given civil date, from Howard Hinnant's
+    // `days_from_civil` algorithm.
+    let (month, day) = (u64::from(time.month()), u64::from(time.day()));
+    let year = u64::from(time.year()) - u64::from(month <= 2);
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100
+        + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+
+    let secs = days * 86_400
+        + u64::from(time.hour()) * 3_600
+        + u64::from(time.minute()) * 60
+        + u64::from(time.second());
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
@@ -0,0 +1,91 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Platform-dependent operations.
+//!
+//! WASI targets such as `wasm32-wasip1` can't spawn processes, handle
+//! signals, or reliably spawn threads, so those operations are stubbed here
+//! and the rest of the library uses them unchanged.
+////////////////////////////////////////////////////////////////////////////////
+
+// Standard library imports.
+use std::io::ErrorKind;
+use std::process::Child;
+use std::process::Command;
+use std::process::Output;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Subprocesses
+////////////////////////////////////////////////////////////////////////////////
+/// `true` if the target can run subprocesses.
+pub(crate) const SUBPROCESSES_SUPPORTED: bool = cfg!(not(target_os = "wasi"));
+
+/// Spawns the given command.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if the command fails to spawn, or with
+/// [`ErrorKind::Unsupported`] if the target can't run subprocesses.
+///
+/// [`std::io::Error`]: std::io::Error
+/// [`ErrorKind::Unsupported`]: std::io::ErrorKind::Unsupported
+pub(crate) fn spawn(command: &mut Command) -> Result<Child, std::io::Error> {
+    if !SUBPROCESSES_SUPPORTED { return Err(ErrorKind::Unsupported.into()); }
+    command.spawn()
+}
+
+/// Runs the given command to completion, collecting its output.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if the command fails to run, or with
+/// [`ErrorKind::Unsupported`] if the target can't run subprocesses.
+///
+/// [`std::io::Error`]: std::io::Error
+/// [`ErrorKind::Unsupported`]: std::io::ErrorKind::Unsupported
+pub(crate) fn output(command: &mut Command)
+    -> Result<Output, std::io::Error>
+{
+    if !SUBPROCESSES_SUPPORTED { return Err(ErrorKind::Unsupported.into()); }
+    command.output()
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Interrupts
+////////////////////////////////////////////////////////////////////////////////
+/// Installs the given function as the handler for Ctrl-C, returning `false`
+/// if it could not be installed.
+#[cfg(not(target_os = "wasi"))]
+pub(crate) fn set_interrupt_handler<F>(handler: F) -> bool
+    where F: FnMut() + Send + 'static
+{
+    ctrlc::set_handler(handler).is_ok()
+}
+
+/// Installs the given function as the handler for Ctrl-C, returning `false`
+/// if it could not be installed. WASI has no signals, so no handler is
+/// installed.
+#[cfg(target_os = "wasi")]
+pub(crate) fn set_interrupt_handler<F>(_handler: F) -> bool
+    where F: FnMut() + Send + 'static
+{
+    false
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Threads
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the number of threads to use for parallel work. WASI targets use
+/// a single thread.
+pub(crate) fn available_threads() -> usize {
+    if cfg!(target_os = "wasi") { return 1; }
+    std::thread::available_parallelism()
+        .map_or(1, std::num::NonZeroUsize::get)
+}
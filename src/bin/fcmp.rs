@@ -40,68 +40,1080 @@
 #![warn(while_true)]
 
 // External library imports.
+use fcmp::command::FcmpCli;
+use fcmp::command::FcmpCommand;
 use fcmp::command::FcmpOptions;
+use fcmp::command::interrupt_token;
+use fcmp::command::EXIT_INTERRUPTED;
+use fcmp::command::OnEqual;
+use fcmp::command::Outcome;
+use fcmp::Comparator;
+use fcmp::CompareReport;
+use fcmp::Config;
+use fcmp::ContentDiff;
+use fcmp::DedupeAction;
 use fcmp::DiffOp;
+use fcmp::FcmpError;
+use fcmp::FileCmp;
+use fcmp::FileId;
+use fcmp::FileResult;
+use fcmp::first_difference;
+use fcmp::first_difference_paths;
+use fcmp::JobFile;
+use fcmp::Manifest;
+use fcmp::PlannedOp;
+use fcmp::ProgressBarReporter;
+use fcmp::read_path_list;
+use fcmp::Server;
+use fcmp::Snapshot;
+use fcmp::Source as _;
+use fcmp::SpooledStdin;
+use fcmp::StatsRecorder;
+use fcmp::STDIN_PATH;
+use fcmp::TimeStyle;
+use fcmp::unified_diff;
 
 // External library imports.
-use clap::Parser;
+use clap::CommandFactory as _;
+use console::style;
+use console::Style;
+use console::Term;
+use indicatif::HumanBytes;
 use clap::ErrorKind;
+use anyhow::anyhow;
 use anyhow::Error;
 
+// Standard library imports.
+use std::fs::File;
+use std::io::BufReader;
+use std::io::Read as _;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
 
 ////////////////////////////////////////////////////////////////////////////////
 // main
 ////////////////////////////////////////////////////////////////////////////////
 /// The application entry point.
 pub fn main() {
-    if let Err(err) = main_facade() {
-        // Print errors to stderr and exit with error code.
-        eprintln!("{:?}", err);
-        
-        let exit_code = match err.downcast::<clap::Error>()
-            .map(|e| e.kind())
-        {
-            Ok(ErrorKind::DisplayHelp)    |
-            Ok(ErrorKind::DisplayVersion) => 0,
-            _ => 1,
-        };
+    match main_facade() {
+        Ok(0) => (),
+        Ok(exit_code) => std::process::exit(exit_code),
+        // Output piped to a command which has exited, such as `head`, is
+        // not an error.
+        Err(err) if is_broken_pipe(&err) => (),
+        Err(err) => {
+            // Print errors to stderr and exit with error code.
+            eprintln!("{:?}", err);
+            
+            if is_cancelled(&err) { std::process::exit(EXIT_INTERRUPTED); }
+            let exit_code = match err.downcast::<clap::Error>()
+                .map(|e| e.kind())
+            {
+                Ok(ErrorKind::DisplayHelp)    |
+                Ok(ErrorKind::DisplayVersion) => 0,
+                _ => 1,
+            };
 
-        std::process::exit(exit_code);
+            std::process::exit(exit_code);
+        },
     }
 }
 
+/// The exit status used when other files have the same content as the result.
+const EXIT_EQUAL_CONTENT: i32 = 3;
+
+/// The exit status used by the check and diff commands when the files differ.
+const EXIT_CHECK_DIFFERENT: i32 = 1;
+
+/// The exit status used by the uptodate command when the target is out of
+/// date.
+const EXIT_OUT_OF_DATE: i32 = 1;
+
+/// The exit status used by the changed command when any file has changed.
+const EXIT_CHANGED: i32 = 1;
+
+/// The exit status used by the check and uptodate commands when an error
+/// occurs.
+const EXIT_CHECK_ERROR: i32 = 2;
+
+/// Returns `true` if the given error is due to writing to a closed pipe.
+fn is_broken_pipe(err: &Error) -> bool {
+    err.chain().any(|e| e.downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe))
+}
+
+/// Returns `true` if the given error is due to a cancelled operation.
+fn is_cancelled(err: &Error) -> bool {
+    err.chain().any(|e| matches!(
+            e.downcast_ref::<FcmpError>(),
+            Some(FcmpError::Cancelled))
+        || e.downcast_ref::<fcmp::Cancelled>().is_some())
+}
+
 
 ////////////////////////////////////////////////////////////////////////////////
 // main_facade
 ////////////////////////////////////////////////////////////////////////////////
-/// The application facade for propagating user errors.
-pub fn main_facade() -> Result<(), Error> {
+/// The application facade for propagating user errors. Returns the exit
+/// status code.
+pub fn main_facade() -> Result<i32, Error> {
     // Parse command line options.
-    let opts = FcmpOptions::try_parse()?;
+    let cli = FcmpCli::try_parse_with_env()?;
+    cli.color.apply();
+    #[cfg(feature = "tracing")]
+    cli.install_subscriber();
+
+    match cli.into_command() {
+        FcmpCommand::Newest(opts)    => select(opts),
+        FcmpCommand::Oldest(mut opts) => {
+            opts.reverse = !opts.reverse;
+            select(opts)
+        },
+        FcmpCommand::Sort(mut opts)  => {
+            opts.sort = true;
+            select(opts)
+        },
+        FcmpCommand::Rotate { keep, options } => rotate(keep, options),
+        FcmpCommand::Run { job_file } => run_jobs(&job_file),
+        #[cfg(feature = "archive")]
+        FcmpCommand::Check { a, b, archive } => {
+            let diff_op = if archive {
+                DiffOp::Archive
+            } else {
+                DiffOp::internal()
+            };
+            Ok(check(&a, &b, &diff_op))
+        },
+        #[cfg(not(feature = "archive"))]
+        FcmpCommand::Check { a, b } => Ok(check(&a, &b, &DiffOp::internal())),
+        FcmpCommand::Uptodate { target, deps, missing, mtime_tolerance } => {
+            let comparator = Comparator {
+                missing,
+                mtime_tolerance,
+                ..Comparator::default()
+            };
+            Ok(uptodate(&target, &deps, &comparator))
+        },
+        FcmpCommand::Dups { paths, verify, emit, apply } => {
+            dups(&paths, verify, emit, apply)
+        },
+        FcmpCommand::Diff { a, b, context, brief, offset } => if offset {
+            diff_offset(&a, &b)
+        } else {
+            diff(&a, &b, context, brief)
+        },
+        #[cfg(feature = "watch")]
+        FcmpCommand::Watch(opts)      => watch(opts),
+        FcmpCommand::Snapshot { root, output } => {
+            Snapshot::take(&root, &Manifest::new())?.save(&output)?;
+            Ok(0)
+        },
+        FcmpCommand::Changed { root, against } => changed(&root, &against),
+        FcmpCommand::Serve { socket } => serve(socket.as_deref()),
+        FcmpCommand::Completions { shell } => {
+            clap_complete::generate(
+                shell,
+                &mut FcmpCli::command(),
+                "fcmp",
+                &mut std::io::stdout());
+            Ok(0)
+        },
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// select
+////////////////////////////////////////////////////////////////////////////////
+/// Selects files from the paths given by the options, reporting errors using
+/// the exit status if an exit map is configured. Returns the exit status code.
+fn select(opts: FcmpOptions) -> Result<i32, Error> {
+    let exit_map = opts.exit_map();
+    match (compare_paths(opts), exit_map) {
+        (Err(err), Some(exit_map)) if !is_broken_pipe(&err) => {
+            eprintln!("{:?}", err);
+            Ok(exit_map.code(Outcome::Error))
+        },
+        (res, _) => res,
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// compare_paths
+////////////////////////////////////////////////////////////////////////////////
+/// Compares the paths given by the options and prints the results. Returns
+/// the exit status code.
+fn compare_paths(mut opts: FcmpOptions) -> Result<i32, Error> {
+    // Print the planned operations without performing them.
+    if opts.plan {
+        print_plan(opts)?;
+        return Ok(0);
+    }
+
+    expand_paths(&mut opts, None)?;
+
+    // Copy standard input to a temporary file so it can be compared.
+    let spooled = if opts.paths.iter().any(|p| fcmp::is_stdin(p)) {
+        let modified = opts.stdin_mtime.resolve(opts.paths
+            .iter()
+            .filter(|p| !fcmp::is_stdin(p))
+            .map(PathBuf::as_path));
+        let spooled = SpooledStdin::new(modified)?;
+        for path in opts.paths.iter_mut().filter(|p| fcmp::is_stdin(p)) {
+            *path = spooled.path().to_path_buf();
+        }
+        Some(spooled)
+    } else {
+        None
+    };
+    let display_path = |idx: usize| match &spooled {
+        Some(spooled) if opts.paths[idx] == spooled.path()
+            => Path::new(STDIN_PATH),
+        _ => opts.paths[idx].as_path(),
+    };
+
+    let mut comparator = opts.comparator();
+    comparator.cancel = Some(interrupt_token());
+    // Statistics are always recorded, so they can be printed if the
+    // comparison is cancelled.
+    let stats = comparator.stats
+        .get_or_insert_with(|| Arc::new(StatsRecorder::new()))
+        .clone();
+    let progress = opts.progress.then(|| Arc::new(ProgressBarReporter::new()));
+    if let Some(progress) = &progress {
+        comparator.progress = Some(progress.clone());
+    }
+
+    // Select the candidate paths satisfying the filter expression.
+    let now = comparator.clock.now();
+    let candidates: Vec<usize> = (0..opts.paths.len())
+        .filter(|&i| opts.filter.as_ref().is_none_or(|filter| filter
+            .matches(&FileResult::new(i, &opts.paths[i]), now)))
+        .collect();
 
     // Exit early if no paths to compare.
-    if opts.paths.is_empty() { return Ok(()); }
+    if candidates.is_empty() {
+        return Ok(opts.exit_map()
+            .map_or(0, |exit_map| exit_map.code(Outcome::Other)));
+    }
+
+    let paths = || candidates.iter().map(|&i| opts.paths[i].as_path());
+    let selected = select_candidates(&opts, &candidates, &comparator);
+
+    if let Some(progress) = &progress {
+        progress.finish();
+    }
+
+    // Print the partial statistics of a cancelled comparison.
+    if let Err(FcmpError::Cancelled) = &selected {
+        eprint!("{}", stats.stats());
+    }
+    let (mut results, all_equal, report_out) = selected?;
+
+    // Write the per-file timings.
+    if let (Some(timings_file), Some(timings))
+        = (&opts.timings, &comparator.timings)
+    {
+        let mut file = File::create(timings_file)?;
+        timings.write_json(&mut file)?;
+        writeln!(file)?;
+    }
+
+    // Print the runtime statistics.
+    if opts.stats {
+        eprint!("{}", stats.stats());
+    }
+
+    // Explain how the result was selected.
+    if let (true, Some(report)) = (opts.explain, &report_out) {
+        eprint!("{}", report
+            .explain(opts.time_style(), now)
+            .styled(console::colors_enabled_stderr()));
+    }
+
+    // Print the comparison report instead of the results.
+    #[cfg(feature = "serde")]
+    if let (true, Some(report)) = (opts.json, &report_out) {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, report)?;
+        writeln!(stdout)?;
+        return Ok(0);
+    }
+
+    let outcome = match results.first() {
+        _ if all_equal => Outcome::Equal,
+        Some(&idx) if candidates[idx] == 0 => Outcome::First,
+        _ => Outcome::Other,
+    };
+
+    // Report the outcome without printing.
+    if opts.is_quiet() {
+        return Ok(opts.exit_map().unwrap_or_default().code(outcome));
+    }
+
+    // Find other files with the same content as the result.
+    let equal: Vec<usize> = match (opts.on_equal, results.first()) {
+        (OnEqual::PrintFirst, _) | (_, None) => Vec::new(),
+        (_, Some(&idx)) => fcmp::content_equal(
+                paths(),
+                idx,
+                &opts.content_diff_op())
+            .into_iter()
+            .filter(|i| *i != idx)
+            .collect(),
+    };
+
+    if opts.on_equal == OnEqual::PrintAll {
+        for i in &equal {
+            if !results.contains(i) { results.push(*i); }
+        }
+    }
+
+    // Map the results back to the indices of the given paths.
+    let results = results.into_iter().map(|i| candidates[i]);
 
-    let diff_op = if opts.diff {
-        DiffOp::posix_cmp()
+    // Print the results and exit.
+    let results: Vec<FileResult> = results
+        .map(|idx| {
+            let mut result = FileResult::new(idx, &opts.paths[idx]);
+            result.path = display_path(idx).to_path_buf();
+            result
+        })
+        .collect();
+
+    // Sorted paths are highlighted, and aligned in columns on a terminal.
+    let listing = (opts.sort || opts.top.is_some())
+        && opts.output.is_none()
+        && opts.output_format().is_none()
+        && !opts.index
+        && !opts.print0;
+    if listing && Term::stdout().is_term() {
+        print_columns(&results, opts.time_style(), now)?;
+    } else {
+        print_results(&opts, &results, listing, now)?;
+    }
+
+    if opts.on_equal == OnEqual::ExitSpecial && !equal.is_empty() {
+        Ok(EXIT_EQUAL_CONTENT)
+    } else {
+        Ok(opts.exit_map().map_or(0, |exit_map| exit_map.code(outcome)))
+    }
+}
+
+
+/// Prints the given results in the output format given by the options, or as
+/// paths, which are highlighted if the results are a sorted listing.
+fn print_results(
+    opts: &FcmpOptions,
+    results: &[FileResult],
+    listing: bool,
+    now: SystemTime)
+    -> Result<(), std::io::Error>
+{
+    let terminator = if opts.print0 { '\0' } else { '\n' };
+    let output_format = opts.output_format();
+    let mut stdout = std::io::stdout().lock();
+    if let Some(table) = opts.output {
+        write!(stdout, "{}{}", table.header(), terminator)?;
+    }
+    for (rank, result) in results.iter().enumerate() {
+        if let Some(table) = opts.output {
+            let row = table.row(result, rank + 1, opts.time_style(), now);
+            write!(stdout, "{row}{terminator}")?;
+        } else if let Some(format) = &output_format {
+            let formatted = format.format(result, opts.time_style(), now);
+            write!(stdout, "{formatted}{terminator}")?;
+        } else if opts.index {
+            write!(stdout, "{}{}", result.index, terminator)?;
+        } else if listing {
+            let path = result_style(rank, result)
+                .apply_to(result.path.display());
+            write!(stdout, "{path}{terminator}")?;
+        } else {
+            write!(stdout, "{}{}", result.path.display(), terminator)?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints the given sorted results aligned in columns giving the modification
+/// time, size, and path of each file.
+fn print_columns(results: &[FileResult], style: TimeStyle, now: SystemTime)
+    -> Result<(), std::io::Error>
+{
+    let columns: Vec<(String, String)> = results
+        .iter()
+        .map(|result| match (result.found, result.modified, result.size) {
+            (false, _, _) => ("missing".to_string(), "-".to_string()),
+            (true, modified, size) => (
+                modified.map_or_else(|| "-".to_string(), |t| style
+                    .format_seconds(t, now)),
+                size.map_or_else(|| "-".to_string(), |size| HumanBytes(size)
+                    .to_string())),
+        })
+        .collect();
+    let mtime_width = columns.iter().map(|(m, _)| m.chars().count()).max();
+    let size_width = columns.iter().map(|(_, s)| s.chars().count()).max();
+    let (mtime_width, size_width) = (
+        mtime_width.unwrap_or(0),
+        size_width.unwrap_or(0));
+
+    let mut stdout = std::io::stdout().lock();
+    for (rank, (result, (mtime, size))) in results
+        .iter()
+        .zip(columns)
+        .enumerate()
+    {
+        let line = format!("{mtime:<mtime_width$}  {size:>size_width$}  {}",
+            result.path.display());
+        writeln!(stdout, "{}", result_style(rank, result).apply_to(line))?;
+    }
+    Ok(())
+}
+
+/// Returns the terminal style of a sorted result at the given rank. The first
+/// file is highlighted and missing files are dimmed.
+fn result_style(rank: usize, result: &FileResult) -> Style {
+    match (rank, result.found) {
+        (_, false) => Style::new().dim(),
+        (0, true)  => Style::new().green().bold(),
+        _          => Style::new(),
+    }
+}
+
+/// Compares the candidate paths given by the options, returning the indices
+/// of the selected candidates, whether all of them compare equal, and the
+/// comparison report if one was made.
+fn select_candidates(
+    opts: &FcmpOptions,
+    candidates: &[usize],
+    comparator: &Comparator)
+    -> Result<(Vec<usize>, bool, Option<CompareReport>), FcmpError>
+{
+    let paths = || candidates.iter().map(|&i| opts.paths[i].as_path());
+
+    if opts.sort {
+        let sorted = fcmp::sort_paths(paths(), opts.reverse, comparator)?;
+        Ok((sorted, false, None))
+    } else if let Some(n) = opts.top {
+        let top = fcmp::compare_top(paths(), n, opts.reverse, comparator)?;
+        Ok((top, false, None))
+    } else if let Some(n) = opts.nth {
+        let nth = fcmp::compare_nth(paths(), n - 1, opts.reverse, comparator)?;
+        Ok((nth.into_iter().collect(), false, None))
+    } else {
+        let report = fcmp::compare_all_report(
+            paths(),
+            opts.reverse,
+            comparator)?;
+        let ties = report.ties.clone();
+        let idx = ties.first().copied().unwrap_or(0);
+        let all_equal = candidates.len() > 1 && ties.len() == candidates.len();
+        let selected = if opts.all { ties } else { vec![idx] };
+        Ok((selected, all_equal, Some(report)))
+    }
+}
+
+/// Prints the IO operations expected to be performed when comparing the paths
+/// given by the options, without running any source command or writing any
+/// file.
+fn print_plan(mut opts: FcmpOptions) -> Result<(), Error> {
+    let mut ops = Vec::new();
+    expand_paths(&mut opts, Some(&mut ops))?;
+    let paths = || opts.paths.iter().map(PathBuf::as_path);
+
+    // Filtering reads the metadata of every file.
+    if opts.filter.is_some() {
+        for path in paths() {
+            ops.push(PlannedOp::Open(path.to_path_buf()));
+            ops.push(PlannedOp::Stat(path.to_path_buf()));
+        }
+    }
+
+    // Each pair is planned with the diff its '--diff-for' override selects.
+    let mut comparator = opts.comparator();
+    let diff = !matches!(comparator.diff_op, DiffOp::None);
+    comparator.diff_op = DiffOp::None;
+    ops.extend(fcmp::plan_compare(paths(), &comparator));
+    let on_equal = opts.on_equal != OnEqual::PrintFirst;
+    if let Some((first, rest)) = opts.paths.split_first() {
+        for path in rest {
+            let diff_op = opts.content_diff_op_for(first, path);
+            if diff {
+                ops.extend(fcmp::plan_diff(first, path, &diff_op));
+            }
+            if on_equal {
+                ops.extend(fcmp::plan_diff(first, path, &diff_op));
+            }
+        }
+    }
+
+    for op in ops {
+        println!("{op}");
+    }
+    Ok(())
+}
+
+/// Returns the name of the path set referred to by the given path, if it has
+/// the form '@NAME'.
+fn set_name(path: &Path) -> Option<&str> {
+    path.to_str().and_then(|p| p.strip_prefix('@'))
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// expand_paths
+////////////////////////////////////////////////////////////////////////////////
+/// Replaces the paths given by the options with the paths they refer to,
+/// adding the paths read from path lists, globs, and sources, and expanding
+/// named path sets and directories.
+///
+/// If a plan is given, source commands are added to it instead of being run,
+/// and the manifest is not saved.
+fn expand_paths(opts: &mut FcmpOptions, plan: Option<&mut Vec<PlannedOp>>)
+    -> Result<(), Error>
+{
+    // Replace named path sets with their paths.
+    if opts.paths.iter().any(|p| set_name(p).is_some()) {
+        let config = match &opts.config {
+            Some(path) => Config::load(path)?,
+            None       => Config::load_default()?,
+        };
+        let mut paths = Vec::with_capacity(opts.paths.len());
+        for path in opts.paths.drain(..) {
+            match set_name(&path) {
+                Some(name) => paths.extend(config.expand_set(name)?),
+                None       => paths.push(path),
+            }
+        }
+        opts.paths = paths;
+    }
+
+    // Read paths from standard input or a path list file.
+    if opts.paths.len() == 1 && opts.paths[0] == Path::new("-") {
+        opts.paths = read_paths(Path::new("-"), opts.null)?;
+    }
+    if let Some(from_file) = &opts.from_file {
+        let paths = read_paths(from_file, opts.null)?;
+        opts.paths.extend(paths);
+    }
+    for pattern in &opts.globs {
+        opts.paths.extend(fcmp::expand_glob(pattern)?);
+    }
+    let planning = plan.is_some();
+    match plan {
+        Some(plan) => plan.extend(opts.sources.iter().flat_map(|s| s.plan())),
+        None => for source in &opts.sources {
+            opts.paths.extend(source.paths()?);
+        },
+    }
+
+    // Expand directories into the files they contain.
+    if opts.recursive {
+        opts.paths = expand_dirs(opts, !planning)?;
+    }
+    Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// read_paths
+////////////////////////////////////////////////////////////////////////////////
+/// Reads a list of paths from the given file, or from standard input if the
+/// path is '-'.
+fn read_paths(path: &Path, nul_separated: bool) -> Result<Vec<PathBuf>, Error> {
+    if path == Path::new("-") {
+        Ok(read_path_list(std::io::stdin().lock(), nul_separated)?)
     } else {
-        DiffOp::None
+        let reader = BufReader::new(File::open(path)?);
+        Ok(read_path_list(reader, nul_separated)?)
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// expand_dirs
+////////////////////////////////////////////////////////////////////////////////
+/// Replaces each directory in the given paths with the files it contains,
+/// using the manifest file if one is given, and updating it if `save` is
+/// `true`.
+fn expand_dirs(opts: &FcmpOptions, save: bool) -> Result<Vec<PathBuf>, Error> {
+    let manifest_path = opts.manifest.as_deref();
+    let manifest = match manifest_path {
+        Some(path) if path.exists() => Manifest::load(path)?,
+        _ => Manifest::new(),
     };
 
-    let idx = fcmp::compare_all(
-        opts.paths.iter().map(|p| p.as_path()),
+    let mut expanded = Vec::with_capacity(opts.paths.len());
+    for path in &opts.paths {
+        if !path.is_dir() {
+            expanded.push(path.clone());
+            continue;
+        }
+        #[cfg(feature = "gitignore")]
+        if opts.gitignore {
+            expanded.extend(fcmp::walk_tree_ignoring_cancellable(
+                path,
+                &interrupt_token())?);
+            continue;
+        }
+        let files = manifest.walk_cancellable(path, &interrupt_token())?;
+        #[cfg(feature = "gitignore")]
+        let files = fcmp::filter_fcmpignore(path, files)?;
+        expanded.extend(files);
+    }
+
+    if let Some(path) = manifest_path.filter(|_| save) {
+        manifest.save(path)?;
+    }
+    Ok(expanded)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// rotate
+////////////////////////////////////////////////////////////////////////////////
+/// Prints the files beyond the given number of files to keep from the paths
+/// given by the options. Returns the exit status code.
+fn rotate(keep: usize, mut opts: FcmpOptions) -> Result<i32, Error> {
+    expand_paths(&mut opts, None)?;
+    if opts.paths.iter().any(|p| fcmp::is_stdin(p)) {
+        return Err(anyhow!("standard input can't be rotated"));
+    }
+
+    let mut comparator = opts.comparator();
+    comparator.cancel = Some(interrupt_token());
+
+    // Select the candidate paths satisfying the filter expression.
+    let now = comparator.clock.now();
+    let candidates: Vec<usize> = (0..opts.paths.len())
+        .filter(|&i| opts.filter.as_ref().is_none_or(|filter| filter
+            .matches(&FileResult::new(i, &opts.paths[i]), now)))
+        .collect();
+
+    let removed = fcmp::rotate_paths(
+        candidates.iter().map(|&i| opts.paths[i].as_path()),
+        keep,
         opts.reverse,
-        &diff_op,
-        opts.missing)?;
+        &comparator)?;
+
+    let removed: Vec<usize> = removed.into_iter()
+        .map(|i| candidates[i])
+        .collect();
+    // A newline in a path would split it into several lines, naming other
+    // files to remove.
+    if !opts.print0 {
+        if let Some(idx) = removed.iter().copied().find(|&idx| opts.paths[idx]
+            .as_os_str()
+            .as_encoded_bytes()
+            .contains(&b'\n'))
+        {
+            return Err(anyhow!("path contains a newline: {:?} \
+                (use --print0)", opts.paths[idx]));
+        }
+    }
+
+    let terminator = if opts.print0 { '\0' } else { '\n' };
+    let output_format = opts.output_format();
+    let mut stdout = std::io::stdout().lock();
+    for idx in removed {
+        if let Some(format) = &output_format {
+            let result = FileResult::new(idx, &opts.paths[idx]);
+            let formatted = format.format(&result, opts.time_style(), now);
+            write!(stdout, "{formatted}{terminator}")?;
+        } else {
+            write!(stdout, "{}{}", opts.paths[idx].display(), terminator)?;
+        }
+    }
+    Ok(0)
+}
 
-    // Print the result and exit.
-    if opts.index {
-        println!("{}", idx);
+
+////////////////////////////////////////////////////////////////////////////////
+// watch
+////////////////////////////////////////////////////////////////////////////////
+/// Watches the paths given by the options, printing the selected file each
+/// time any of them changes. Returns the exit status code.
+#[cfg(feature = "watch")]
+fn watch(mut opts: FcmpOptions) -> Result<i32, Error> {
+    expand_paths(&mut opts, None)?;
+    if opts.paths.is_empty() {
+        return Err(anyhow!("no paths to watch"));
+    }
+    if opts.paths.iter().any(|p| fcmp::is_stdin(p)) {
+        return Err(anyhow!("standard input can't be watched"));
+    }
+
+    let mut comparator = opts.comparator();
+    comparator.cancel = Some(interrupt_token());
+
+    let paths = &opts.paths;
+    fcmp::watch_paths(paths, opts.reverse, &comparator, |changed, result| {
+        let mut stdout = std::io::stdout().lock();
+        let written = match (result, opts.json) {
+            (Ok(idx), false) => writeln!(stdout, "{}", paths[idx].display()),
+            (Ok(idx), true) => writeln!(stdout, "{}", serde_json::json!({
+                "changed": changed,
+                "selected": paths[idx],
+            })),
+            (Err(err), false) => {
+                eprintln!("{err}");
+                Ok(())
+            },
+            (Err(err), true) => writeln!(stdout, "{}", serde_json::json!({
+                "changed": changed,
+                "error": err.to_string(),
+            })),
+        };
+        // Stop watching once the output is closed.
+        written.and_then(|()| stdout.flush()).is_ok()
+    })?;
+    Ok(0)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// serve
+////////////////////////////////////////////////////////////////////////////////
+/// Answers comparison queries from standard input or the given Unix socket
+/// until the input ends or Ctrl-C is pressed. Returns the exit status code.
+fn serve(socket: Option<&Path>) -> Result<i32, Error> {
+    let server = Arc::new(Server::new());
+    match socket {
+        #[cfg(unix)]
+        Some(socket) => server.serve_socket(socket, &interrupt_token())?,
+        #[cfg(not(unix))]
+        Some(_) => return Err(anyhow!(
+            "Unix sockets are not supported on this platform")),
+        None => server.serve(std::io::stdin().lock(), std::io::stdout())?,
+    }
+    Ok(0)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// run_jobs
+////////////////////////////////////////////////////////////////////////////////
+/// Runs the jobs in the given job file and prints a report line for each.
+/// Returns the exit status code.
+fn run_jobs(job_file: &Path) -> Result<i32, Error> {
+    let job_file = JobFile::load(job_file)?;
+
+    let mut exit_code = 0;
+    for job in &job_file.jobs {
+        match job.run() {
+            Ok(result) => println!("{}\t{}", job.name, result.display()),
+            Err(err) => {
+                eprintln!("{}\terror: {:#}", job.name, Error::from(err));
+                exit_code = 1;
+            },
+        }
+    }
+    Ok(exit_code)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// check
+////////////////////////////////////////////////////////////////////////////////
+/// Checks whether the given files are identical according to the given
+/// [`DiffOp`]. Returns the exit status code.
+fn check(a: &Path, b: &Path, diff_op: &DiffOp) -> i32 {
+    for path in [a, b].into_iter().filter(|p| !fcmp::is_stdin(p)) {
+        if let Err(err) = find(path) {
+            eprintln!("{}: {}", path.display(), err);
+            return EXIT_CHECK_ERROR;
+        }
+    }
+
+    match diff_paths(a, b, diff_op) {
+        Ok(false) => 0,
+        Ok(true)  => EXIT_CHECK_DIFFERENT,
+        Err(err)  => {
+            eprintln!("{}", err);
+            EXIT_CHECK_ERROR
+        },
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// uptodate
+////////////////////////////////////////////////////////////////////////////////
+/// Checks whether the target file is up to date with the given dependencies
+/// according to the given [`Comparator`]. Returns the exit status code.
+fn uptodate(target: &Path, deps: &[PathBuf], comparator: &Comparator) -> i32 {
+    match fcmp::is_up_to_date(
+        target,
+        deps.iter().map(PathBuf::as_path),
+        comparator)
+    {
+        Ok(true)  => 0,
+        Ok(false) => EXIT_OUT_OF_DATE,
+        Err(err)  => {
+            eprintln!("{}", err);
+            EXIT_CHECK_ERROR
+        },
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// dups
+////////////////////////////////////////////////////////////////////////////////
+/// Prints the groups of the given files with identical content, comparing
+/// files with the same digest byte by byte if `verify` is set. If an action is
+/// given, prints a shell script taking it on the duplicates instead, or takes
+/// it if `apply` is set. Returns the exit status code.
+///
+/// Paths which refer to the same underlying file as an earlier path in their
+/// group, such as through a hardlink or a bind mount, are not duplicates.
+/// They are skipped with a notice, so that acting on a group can't remove the
+/// only copy of a file.
+fn dups(
+    paths: &[PathBuf],
+    verify: bool,
+    emit: Option<DedupeAction>,
+    apply: bool)
+    -> Result<i32, Error>
+{
+    let ids = paths
+        .iter()
+        .map(|p| FileId::from_path(p).ok())
+        .collect::<Vec<_>>();
+
+    let mut groups = Vec::new();
+    let duplicates = fcmp::duplicate_groups(
+        paths.iter().map(PathBuf::as_path),
+        verify);
+    for duplicates in duplicates {
+        let mut group: Vec<usize> = Vec::new();
+        for i in duplicates {
+            let same = group.iter().find(|j| ids[i].is_some()
+                && ids[i] == ids[**j]);
+            match same {
+                Some(&j) => eprintln!("{}", style(format!(
+                        "skipping '{}': same file as '{}'",
+                        paths[i].display(),
+                        paths[j].display()))
+                    .for_stderr()
+                    .dim()),
+                None => group.push(i),
+            }
+        }
+        if group.len() > 1 { groups.push(group); }
+    }
+
+    if let Some(action) = emit {
+        return dedupe(paths, &groups, action, apply);
+    }
+
+    let terminal = Term::stdout().is_term();
+    for (n, group) in groups.into_iter().enumerate() {
+        if n > 0 { println!(); }
+        // Each group is headed by its size on a terminal.
+        if terminal {
+            let size = std::fs::metadata(&paths[group[0]])
+                .map(|m| m.len())?;
+            println!("{}", style(format!(
+                    "{} identical files, {} each",
+                    group.len(),
+                    HumanBytes(size)))
+                .bold());
+        }
+        for i in group {
+            println!("{}", paths[i].display());
+        }
+    }
+    Ok(0)
+}
+
+/// Takes the given action on every file of each group of duplicates except
+/// the most recently modified, or prints a shell script doing so if `apply`
+/// is not set. Returns the exit status code.
+fn dedupe(
+    paths: &[PathBuf],
+    groups: &[Vec<usize>],
+    action: DedupeAction,
+    apply: bool)
+    -> Result<i32, Error>
+{
+    let mut stdout = std::io::stdout().lock();
+    if !apply { writeln!(stdout, "#!/bin/sh")?; }
+    for group in groups {
+        let steps = fcmp::dedupe_steps(
+            group.iter().map(|&i| paths[i].as_path()),
+            action)?;
+        if apply {
+            for step in steps {
+                step.apply()
+                    .map_err(|e| anyhow!("{}: {}", step.path.display(), e))?;
+            }
+            continue;
+        }
+
+        writeln!(stdout)?;
+        if let Some(step) = steps.first() {
+            // Debug formatting escapes newlines, which would end the comment.
+            writeln!(stdout, "# Keep {:?}", step.kept)?;
+        }
+        for step in steps {
+            let command = step.command()
+                .map_err(|e| anyhow!("{}: {}", step.path.display(), e))?;
+            stdout.write_all(&command)?;
+        }
+    }
+    Ok(0)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// changed
+////////////////////////////////////////////////////////////////////////////////
+/// Prints the changes to the directory tree at `root` since the snapshot in
+/// the given file was taken. Returns the exit status code.
+fn changed(root: &Path, snapshot_file: &Path) -> Result<i32, Error> {
+    let snapshot = Snapshot::load(snapshot_file)?;
+    let current = Snapshot::take(root, &Manifest::new())?;
+
+    let changes = snapshot.changes(&current);
+    for change in &changes {
+        println!("{}\t{}", change.code(), root.join(change.path()).display());
+    }
+    Ok(if changes.is_empty() { 0 } else { EXIT_CHANGED })
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// diff
+////////////////////////////////////////////////////////////////////////////////
+/// Prints the differences between the given files as a unified diff with the
+/// given number of context lines, or only whether they differ if `brief` is
+/// set. Returns the exit status code.
+fn diff(a: &Path, b: &Path, context: usize, brief: bool) -> Result<i32, Error> {
+    for path in [a, b].into_iter().filter(|p| !fcmp::is_stdin(p)) {
+        find(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+    }
+
+    if brief {
+        if !diff_paths(a, b, &DiffOp::internal())? { return Ok(0); }
+        println!("Files {} and {} differ", a.display(), b.display());
+        return Ok(EXIT_CHECK_DIFFERENT);
+    }
+
+    let content_diff = if fcmp::is_stdin(a) || fcmp::is_stdin(b) {
+        // Standard input can only be read once, so it is read into memory.
+        let mut stdin = Vec::new();
+        let _ = std::io::stdin().lock().read_to_end(&mut stdin)?;
+        let read = |path: &Path| if fcmp::is_stdin(path) {
+            Ok(stdin.clone())
+        } else {
+            std::fs::read(path)
+        };
+        ContentDiff::new(
+            &read(a)?,
+            &read(b)?,
+            (&a.to_string_lossy(), &b.to_string_lossy()),
+            context)
     } else {
-        println!("{}", opts.paths[idx].display());
+        unified_diff(a, b, context)?
+    };
+
+    match content_diff {
+        ContentDiff::Same => return Ok(0),
+        ContentDiff::Binary => {
+            println!("Binary files {} and {} differ", a.display(), b.display());
+        },
+        ContentDiff::Text(text) => {
+            let mut stdout = std::io::stdout().lock();
+            for line in text.lines() {
+                writeln!(stdout, "{}", diff_line_style(line).apply_to(line))?;
+            }
+        },
     }
-    Ok(())
+    Ok(EXIT_CHECK_DIFFERENT)
+}
+
+/// Prints the position of the first difference between the given files, like
+/// `cmp`. Returns the exit status code.
+fn diff_offset(a: &Path, b: &Path) -> Result<i32, Error> {
+    let stdin = || std::io::stdin().lock();
+    let difference = match (fcmp::is_stdin(a), fcmp::is_stdin(b)) {
+        (false, false) => first_difference_paths(a, b)?,
+        (true, true)   => None,
+        (true, false)  => first_difference(stdin(), File::open(b)?)?,
+        (false, true)  => first_difference(File::open(a)?, stdin())?,
+    };
+    let Some(difference) = difference else { return Ok(0) };
+
+    match difference.ended {
+        Some(ended) if difference.offset == 0 => println!(
+            "EOF on {} which is empty",
+            [a, b][ended].display()),
+        Some(ended) => println!("EOF on {} after byte {}",
+            [a, b][ended].display(),
+            difference.offset),
+        None => println!("{} {} differ: byte {}, line {}",
+            a.display(),
+            b.display(),
+            difference.offset + 1,
+            difference.line),
+    }
+    Ok(EXIT_CHECK_DIFFERENT)
 }
 
+/// Returns the terminal style of a line of a unified diff.
+fn diff_line_style(line: &str) -> Style {
+    if line.starts_with("---") || line.starts_with("+++") {
+        Style::new().bold()
+    } else if line.starts_with("@@") {
+        Style::new().cyan()
+    } else if line.starts_with('-') {
+        Style::new().red()
+    } else if line.starts_with('+') {
+        Style::new().green()
+    } else {
+        Style::new()
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// diff_paths
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the given files differ according to the given
+/// [`DiffOp`]. A path of '-' refers to standard input, which is streamed
+/// against the other file by internal diffs.
+fn diff_paths(a: &Path, b: &Path, diff_op: &DiffOp) -> Result<bool, Error> {
+    let other = match (fcmp::is_stdin(a), fcmp::is_stdin(b)) {
+        (false, false) => return Ok(diff_op.diff(a, b)?),
+        (true, true)   => return Ok(false),
+        (true, false)  => b,
+        (false, true)  => a,
+    };
 
+    if let DiffOp::Internal = diff_op {
+        let file = File::open(other)?;
+        Ok(!fcmp::content_eq(std::io::stdin().lock(), file)?)
+    } else {
+        // Other diffs need a file to read.
+        let spooled = SpooledStdin::new(SystemTime::now())?;
+        Ok(diff_op.diff(spooled.path(), other)?)
+    }
+}
 
+/// Returns an error if the file, archive member, or remote file at the given
+/// path can't be found.
+fn find(path: &Path) -> Result<(), std::io::Error> {
+    if FileCmp::try_from(path)?.is_found() {
+        Ok(())
+    } else {
+        // Report the same error as reading the metadata of a local file.
+        std::fs::metadata(path).map(|_| ())
+    }
+}
@@ -0,0 +1,113 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Helpers for cargo build scripts.
+//!
+//! A build script which generates code can skip the generation when its
+//! output is already newer than its inputs:
+//!
+//! ```no_run
+//! let out = std::path::Path::new("src/generated.rs");
+//! if fcmp::build_support::rerun_if_newer(out, ["schema/*.json"])? {
+//!     // Regenerate `out` from the schemas.
+//! }
+//! # Ok::<(), fcmp::build_support::BuildError>(())
+//! ```
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::Comparator;
+use crate::expand_glob;
+use crate::FcmpError;
+use crate::GlobError;
+use crate::is_up_to_date;
+
+// Standard library imports.
+use std::io::Write;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// rerun_if_newer
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the target file is missing or older than any of the
+/// files matching the given glob patterns, and must be regenerated.
+///
+/// A `cargo:rerun-if-changed` line is printed for each matching file, and for
+/// the directory containing each pattern's wildcards so that newly added
+/// files also rerun the build script. Patterns without a directory, such as
+/// `*.proto`, are only rerun for the files they match.
+///
+/// ### Errors
+///
+/// Returns a [`BuildError`] if a pattern is invalid, a matched path can't be
+/// read, the files can't be compared, or the output can't be written.
+///
+/// [`BuildError`]: BuildError
+pub fn rerun_if_newer<T, I, S>(target: T, globs: I)
+    -> Result<bool, BuildError>
+    where
+        T: AsRef<Path>,
+        I: IntoIterator<Item=S>,
+        S: AsRef<str>,
+{
+    let mut inputs = Vec::new();
+    let mut stdout = std::io::stdout().lock();
+    for pattern in globs {
+        let pattern = pattern.as_ref();
+        if let Some(root) = glob_root(pattern) {
+            writeln!(stdout, "cargo:rerun-if-changed={}", root.display())?;
+        }
+        for path in expand_glob(pattern)? {
+            writeln!(stdout, "cargo:rerun-if-changed={}", path.display())?;
+            inputs.push(path);
+        }
+    }
+
+    let up_to_date = is_up_to_date(
+        target.as_ref(),
+        inputs.iter().map(PathBuf::as_path),
+        &Comparator::default())?;
+    Ok(!up_to_date)
+}
+
+/// An error which occurs while checking whether generated files must be
+/// regenerated with [`rerun_if_newer`].
+///
+/// [`rerun_if_newer`]: rerun_if_newer
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// A pattern can't be expanded.
+    #[error(transparent)]
+    Glob(#[from] GlobError),
+
+    /// Comparing the files failed.
+    #[error(transparent)]
+    Compare(#[from] FcmpError),
+
+    /// The instructions for cargo can't be written to stdout.
+    #[error("failed to write build script output")]
+    Output(#[from] std::io::Error),
+}
+
+/// Returns the directory containing the first wildcard of the given glob
+/// pattern, or `None` if the pattern has no wildcards or no directory.
+fn glob_root(pattern: &str) -> Option<PathBuf> {
+    let mut root = PathBuf::new();
+    for component in Path::new(pattern).components() {
+        let is_wildcard = matches!(component, Component::Normal(name)
+            if name.to_string_lossy().contains(['*', '?', '[']));
+        if is_wildcard {
+            return root.components().next().is_some().then_some(root);
+        }
+        root.push(component);
+    }
+    None
+}
@@ -0,0 +1,97 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Cooperative cancellation.
+////////////////////////////////////////////////////////////////////////////////
+
+// Standard library imports.
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// CancelToken
+////////////////////////////////////////////////////////////////////////////////
+/// A flag used to abort a long-running operation from another thread.
+///
+/// Clones of a `CancelToken` share the same flag, so a token may be given to
+/// an operation while a clone is kept to cancel it. Operations check the
+/// token between steps, such as before reading each file's metadata or each
+/// block of content, and fail with a [`Cancelled`] error once it is
+/// cancelled.
+///
+/// [`Cancelled`]: Cancelled
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    /// Whether the token has been cancelled.
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Returns a `CancelToken` which has not been cancelled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels every operation using this token or its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns `true` if the token has been cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns an error if the token has been cancelled.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] wrapping a [`Cancelled`] error if the
+    /// token has been cancelled.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    /// [`Cancelled`]: Cancelled
+    pub fn check(&self) -> Result<(), std::io::Error> {
+        if self.is_cancelled() {
+            Err(std::io::Error::other(Cancelled))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Cancelled
+////////////////////////////////////////////////////////////////////////////////
+/// An error indicating that an operation was aborted by its [`CancelToken`].
+///
+/// [`CancelToken`]: CancelToken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl Cancelled {
+    /// Returns `true` if the given IO error wraps a `Cancelled` error.
+    #[must_use]
+    pub fn is(error: &std::io::Error) -> bool {
+        error.get_ref()
+            .and_then(|e| e.downcast_ref::<Self>())
+            .is_some()
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "operation cancelled")
+    }
+}
@@ -0,0 +1,128 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Parallel chunked content comparison.
+////////////////////////////////////////////////////////////////////////////////
+
+// Standard library imports.
+use std::fs::File;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// chunked_eq
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the given files of the given length have the same
+/// content, along with the number of bytes read from each file.
+///
+/// The files are split into one contiguous chunk per thread, and the chunks
+/// are compared on the given number of threads using reads of the given
+/// buffer size. All threads stop once any chunk is found to differ.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if either file fails to read, including if it
+/// is shorter than the given length.
+///
+/// [`std::io::Error`]: std::io::Error
+pub(crate) fn chunked_eq(
+    a: &File,
+    b: &File,
+    len: u64,
+    threads: usize,
+    buffer_size: usize)
+    -> Result<(bool, u64), std::io::Error>
+{
+    let threads = u64::try_from(threads.max(1)).unwrap_or(u64::MAX);
+    let chunk_len = len.div_ceil(threads).max(1);
+    let different = AtomicBool::new(false);
+    let read = AtomicU64::new(0);
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|idx| idx * chunk_len)
+            .take_while(|&start| start < len)
+            .map(|start| {
+                let end = (start + chunk_len).min(len);
+                let (different, read) = (&different, &read);
+                scope.spawn(move || compare_chunk(
+                    a, b, start..end, buffer_size, different, read))
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().expect("join compare thread"))
+    })?;
+
+    Ok((!different.load(Ordering::Relaxed), read.load(Ordering::Relaxed)))
+}
+
+/// Compares the given range of the given files, setting `different` if they
+/// differ and adding the number of bytes read from each file to `read`.
+/// Returns early once `different` is set by any thread.
+fn compare_chunk(
+    a: &File,
+    b: &File,
+    range: std::ops::Range<u64>,
+    buffer_size: usize,
+    different: &AtomicBool,
+    read: &AtomicU64)
+    -> Result<(), std::io::Error>
+{
+    let buffer_size = buffer_size.max(1);
+    let mut buf_a = vec![0; buffer_size];
+    let mut buf_b = vec![0; buffer_size];
+    let mut offset = range.start;
+
+    while offset < range.end {
+        if different.load(Ordering::Relaxed) { return Ok(()); }
+
+        let n = usize::try_from(range.end - offset)
+            .map_or(buffer_size, |remaining| remaining.min(buffer_size));
+        read_exact_at(a, &mut buf_a[..n], offset)?;
+        read_exact_at(b, &mut buf_b[..n], offset)?;
+        let _ = read.fetch_add(n as u64, Ordering::Relaxed);
+
+        if buf_a[..n] != buf_b[..n] {
+            different.store(true, Ordering::Relaxed);
+            return Ok(());
+        }
+        offset += n as u64;
+    }
+    Ok(())
+}
+
+/// Fills the given buffer from the given file, starting at the given offset,
+/// without moving the file's cursor.
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64)
+    -> Result<(), std::io::Error>
+{
+    use std::os::unix::fs::FileExt as _;
+    file.read_exact_at(buf, offset)
+}
+
+/// Fills the given buffer from the given file, starting at the given offset.
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64)
+    -> Result<(), std::io::Error>
+{
+    use std::os::windows::fs::FileExt as _;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset)? {
+            0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            n => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            },
+        }
+    }
+    Ok(())
+}
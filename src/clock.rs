@@ -0,0 +1,58 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Time sources.
+////////////////////////////////////////////////////////////////////////////////
+
+// Standard library imports.
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Clock
+////////////////////////////////////////////////////////////////////////////////
+/// A source of the current time.
+///
+/// Time-dependent comparisons, such as excluding recently modified files, use
+/// a `Clock` instead of querying the system time directly, so that their
+/// behavior can be controlled.
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// SystemClock
+////////////////////////////////////////////////////////////////////////////////
+/// A [`Clock`] which returns the current system time.
+///
+/// [`Clock`]: Clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FixedClock
+////////////////////////////////////////////////////////////////////////////////
+/// A [`Clock`] which always returns the same time.
+///
+/// [`Clock`]: Clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub SystemTime);
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        self.0
+    }
+}
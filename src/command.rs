@@ -7,60 +7,1514 @@
 ////////////////////////////////////////////////////////////////////////////////
 //! Command line interface options.
 ////////////////////////////////////////////////////////////////////////////////
+// Clap's subcommand derive generates qualified paths.
+#![allow(unused_qualifications)]
 
 // Internal library imports.
+use crate::backend::set_interrupt_handler;
+use crate::BrokenSymlinkBehavior;
+use crate::CancelToken;
+use crate::CommandSource;
+use crate::Comparator;
+use crate::CompareKey;
+use crate::Decompression;
+use crate::DedupeAction;
+use crate::DiffOp;
+use crate::DiffOverride;
+use crate::EntryKind;
+use crate::Field;
+use crate::FileTypeFilter;
+use crate::FilterExpr;
+use crate::InternalOptions;
 use crate::MissingFileBehavior;
+use crate::MissingOverride;
+use crate::OutputFormat;
+use crate::PathFilter;
+use crate::ReadLimitBehavior;
+use crate::RetryPolicy;
+use crate::SampleOptions;
+use crate::SpecialFilePolicy;
+use crate::StatsRecorder;
+use crate::StdinMtime;
+use crate::SymlinkPolicy;
+use crate::TableFormat;
+use crate::TextOptions;
+use crate::TieBreak;
+use crate::TimeFormat;
+use crate::TimeStyle;
+use crate::UnreadableFilePolicy;
+use crate::parse_size;
+#[cfg(feature = "serde")]
+use crate::TimingRecorder;
 
 // External library imports.
+use anyhow::anyhow;
+use clap::Args;
+use clap::CommandFactory as _;
 use clap::Parser;
+use clap::Subcommand;
+use clap_complete::Shell;
 
 // Standard library imports.
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::path::Path;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::OnceLock;
+use std::time::Duration;
 
 
 ////////////////////////////////////////////////////////////////////////////////
-// FcmpOptions
+// FcmpCli
 ////////////////////////////////////////////////////////////////////////////////
 /// Takes a list of file names and returns the most recently modified file.
 /// 
 /// If the result would be ambiguous, the first occurring ambiguous item in the
 /// file list will be returned.
+///
+/// Running fcmp without a subcommand is the same as running 'fcmp newest'. A
+/// path with the same name as a subcommand must be given with a leading './'.
 #[derive(Debug, Clone)]
 #[derive(Parser)]
 #[clap(name = "fcmp")]
 #[clap(author, version, about)]
+#[clap(args_conflicts_with_subcommands = true)]
+#[clap(args_override_self = true)]
+#[clap(after_help = "Arguments in the FCMP_OPTS environment variable, such \
+    as '--missing error', are inserted before the command line arguments, \
+    which override them.")]
+pub struct FcmpCli {
+    /// The subcommand to run.
+    #[clap(subcommand)]
+    pub command: Option<FcmpCommand>,
+
+    /// The options used when no subcommand is given.
+    #[clap(flatten)]
+    pub options: FcmpOptions,
+
+    /// When to color output: 'auto' colors output written to a terminal,
+    /// unless the 'NO_COLOR' environment variable is set.
+    ///
+    /// Sorted results written to a terminal are also aligned in columns
+    /// giving the modification time and size of each file.
+    #[allow(clippy::doc_markdown)] // Displayed as help text.
+    #[clap(
+        long = "color",
+        value_name = "WHEN",
+        global = true,
+        default_value = "auto",
+        arg_enum)]
+    pub color: ColorChoice,
+
+    /// Log each content comparison, subprocess launch, and retry to stderr.
+    /// Given twice, also log each file metadata read and open.
+    #[cfg(feature = "tracing")]
+    #[clap(
+        short = 'v',
+        long = "verbose",
+        global = true,
+        parse(from_occurrences))]
+    pub verbose: u8,
+}
+
+impl FcmpCli {
+    /// Parses the command line arguments, with the arguments given by the
+    /// [`OPTIONS_ENV_VAR`] environment variable inserted before them.
+    ///
+    /// ### Errors
+    ///
+    /// Returns an error if the environment variable has unterminated quotes,
+    /// or if the arguments can't be parsed.
+    ///
+    /// [`OPTIONS_ENV_VAR`]: OPTIONS_ENV_VAR
+    pub fn try_parse_with_env() -> Result<Self, anyhow::Error> {
+        Self::try_parse_from_with_opts(
+            std::env::args_os(),
+            std::env::var_os(OPTIONS_ENV_VAR).as_deref())
+    }
+
+    /// Parses the given arguments, with the arguments split from `env_opts`
+    /// inserted before them. An option given in both takes the value from
+    /// the given arguments.
+    ///
+    /// The inserted arguments are only used for the `newest`, `oldest`,
+    /// `sort`, `rotate`, and `watch` subcommands, or if no subcommand is
+    /// given.
+    ///
+    /// ### Errors
+    ///
+    /// Returns an error if `env_opts` has unterminated quotes, or if the
+    /// arguments can't be parsed.
+    pub fn try_parse_from_with_opts<I, T>(args: I, env_opts: Option<&OsStr>)
+        -> Result<Self, anyhow::Error>
+        where
+            I: IntoIterator<Item=T>,
+            T: Into<OsString>,
+    {
+        let mut args = args.into_iter().map(Into::into).collect::<Vec<_>>();
+        if let Some(env_opts) = env_opts {
+            let env_args = split_args(&env_opts.to_string_lossy())?;
+            let subcommand = args.get(1).and_then(|a| a.to_str());
+            let idx = match subcommand {
+                Some("newest" | "oldest" | "sort" | "rotate" | "watch")
+                    => Some(2),
+                Some(name) if Self::command()
+                    .get_subcommands()
+                    .any(|c| c.get_name() == name) => None,
+                _ => Some(1),
+            };
+            if let Some(idx) = idx.filter(|idx| *idx <= args.len()) {
+                let env_args = env_args.into_iter().map(Into::into);
+                drop(args.splice(idx..idx, env_args));
+            }
+        }
+        Ok(Self::try_parse_from(args)?)
+    }
+
+    /// Installs a subscriber which logs the library's `tracing` events to
+    /// stderr at the level given by the verbosity flags. Nothing is logged if
+    /// no verbosity flag is given.
+    #[cfg(feature = "tracing")]
+    pub fn install_subscriber(&self) {
+        let level = match self.verbose {
+            0 => return,
+            1 => tracing::Level::DEBUG,
+            _ => tracing::Level::TRACE,
+        };
+        tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_writer(std::io::stderr)
+            .init();
+    }
+
+    /// Returns the subcommand to run, which is `newest` with the top-level
+    /// options if no subcommand is given.
+    #[must_use]
+    pub fn into_command(self) -> FcmpCommand {
+        self.command.unwrap_or(FcmpCommand::Newest(self.options))
+    }
+}
+
+/// The exit status used when a comparison is cancelled by Ctrl-C.
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+/// Returns the [`CancelToken`] which is cancelled when Ctrl-C is pressed,
+/// installing the Ctrl-C handler on first use. Pressing Ctrl-C again exits
+/// immediately with [`EXIT_INTERRUPTED`].
+///
+/// [`CancelToken`]: crate::CancelToken
+/// [`EXIT_INTERRUPTED`]: EXIT_INTERRUPTED
+pub fn interrupt_token() -> CancelToken {
+    static TOKEN: OnceLock<CancelToken> = OnceLock::new();
+    TOKEN.get_or_init(|| {
+        let token = CancelToken::new();
+        let handler_token = token.clone();
+        // Without a handler, Ctrl-C terminates the process as usual.
+        let _ = set_interrupt_handler(move || {
+            if handler_token.is_cancelled() {
+                // Exit as the default handler would.
+                #[allow(clippy::exit)]
+                std::process::exit(EXIT_INTERRUPTED);
+            }
+            handler_token.cancel();
+        });
+        token
+    }).clone()
+}
+
+/// The environment variable containing arguments which are inserted before
+/// the command line arguments, such as `--missing error`.
+///
+/// Arguments are separated by whitespace, and may be quoted with single or
+/// double quotes.
+pub const OPTIONS_ENV_VAR: &str = "FCMP_OPTS";
+
+/// Splits the given string into whitespace-separated arguments, removing any
+/// single or double quotes.
+///
+/// ### Errors
+///
+/// Returns an error if a quote is not terminated.
+pub fn split_args(s: &str) -> Result<Vec<String>, anyhow::Error> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut quote = None;
+    for c in s.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                let _ = arg.get_or_insert_with(String::new);
+            },
+            (None, c) if c.is_whitespace() => args.extend(arg.take()),
+            (_, c) => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(anyhow!("unterminated quote in {OPTIONS_ENV_VAR}"));
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FcmpOptions
+////////////////////////////////////////////////////////////////////////////////
+/// Options for selecting files from a list of paths.
+#[derive(Debug, Clone)]
+#[derive(Args)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct FcmpOptions {
-    /// File paths to compare.
+    /// File paths to compare. If the only path is '-', the paths are read
+    /// from standard input. Otherwise a path of '-' compares the content of
+    /// standard input, whose modification time is set by '--stdin-mtime'.
+    /// Paths of the form '@NAME' are replaced by the
+    /// named path set from the config file. Files inside zip and tar archives
+    /// may be given as '<ARCHIVE>!/<MEMBER>', e.g., 'backup.zip!/etc/hosts'.
+    /// If fcmp was built with the 'http' feature, 'http://' and 'https://'
+    /// URLs may be given, and use the Last-Modified and Content-Length
+    /// headers as their metadata. If fcmp was built with the 'git' feature,
+    /// files in a git revision may be given as '<REV>:<PATH>', e.g.,
+    /// 'HEAD:src/main.rs', and use the commit time of the revision as their
+    /// modification time.
     #[clap(parse(from_os_str))]
     pub paths: Vec<PathBuf>,
 
+    /// Read named path sets from the given config file instead of the default
+    /// config file.
+    #[cfg(feature = "serde")]
+    #[clap(
+        long = "config",
+        value_name = "FILE",
+        parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// Read additional file paths to compare from the given file, one per
+    /// line. Use '-' to read from standard input.
+    #[clap(
+        long = "from-file",
+        value_name = "FILE",
+        parse(from_os_str))]
+    pub from_file: Option<PathBuf>,
+
+    /// Compare the files matching the given glob pattern in addition to the
+    /// given paths.
+    ///
+    /// Patterns are expanded by fcmp instead of the shell, and should be
+    /// quoted. May be given multiple times.
+    #[clap(
+        short = 'g',
+        long = "glob",
+        value_name = "PATTERN",
+        number_of_values = 1)]
+    pub globs: Vec<String>,
+
+    /// Add the paths generated by a source. May be given multiple times.
+    ///
+    /// Sources are 'git-ls-files' for the files tracked by git,
+    /// 'find:<ARGS>' for the files found by 'find' with the given arguments,
+    /// or 'cmd:<COMMAND>' for the newline-separated paths printed by a shell
+    /// command.
+    #[clap(
+        long = "from",
+        value_name = "SOURCE",
+        number_of_values = 1)]
+    pub sources: Vec<CommandSource>,
+
+    /// Read paths separated by NUL characters instead of newlines, such as
+    /// the output of 'find -print0'.
+    #[clap(
+        short = '0',
+        long = "null")]
+    pub null: bool,
+
+    /// Compare the files within any directories given, recursively.
+    ///
+    /// Files matching the gitignore-style patterns in a '.fcmpignore' file
+    /// at the root of a given directory are skipped.
+    #[clap(
+        short = 'R',
+        long = "recursive")]
+    pub recursive: bool,
+
+    /// Cache directory listings in the given file to speed up repeated
+    /// recursive comparisons.
+    #[clap(
+        long = "manifest",
+        value_name = "FILE",
+        requires = "recursive",
+        parse(from_os_str))]
+    pub manifest: Option<PathBuf>,
+
+    /// Skip files excluded by .gitignore, .ignore, and global git exclude
+    /// files when comparing recursively.
+    #[cfg(feature = "gitignore")]
+    #[clap(
+        long = "gitignore",
+        requires = "recursive",
+        conflicts_with = "manifest")]
+    pub gitignore: bool,
+
+    /// Only compare files satisfying the given filter expression, e.g.,
+    /// 'size > 1M && age < 7d'.
+    ///
+    /// Conditions are combined with '&&', '||', '!', and parentheses. The
+    /// available conditions compare size (with K, M, G, or T suffixes), age
+    /// (e.g., 7d), or mtime (RFC 3339) using <, <=, >, >=, ==, or !=, or match
+    /// name or path against a glob using == or !=.
+    #[clap(
+        long = "where",
+        value_name = "EXPR")]
+    pub filter: Option<FilterExpr>,
+
     /// Return the oldest file instead of the newest.
     #[clap(
         short = 'r',
         long = "reverse")]
     pub reverse: bool,
 
+    /// Return every file which ties for the result instead of only the first.
+    #[clap(
+        short = 'a',
+        long = "all")]
+    pub all: bool,
+
+    /// Return all files in order instead of only the first.
+    #[clap(
+        short = 's',
+        long = "sort",
+        conflicts_with_all = &["all", "top", "nth"])]
+    pub sort: bool,
+
+    /// Return the given number of files in order instead of only the first.
+    #[clap(
+        long = "top",
+        value_name = "N",
+        conflicts_with_all = &["all", "nth"])]
+    pub top: Option<usize>,
+
+    /// Return the file at the given (1-based) rank instead of the first.
+    #[clap(
+        long = "nth",
+        value_name = "N",
+        conflicts_with = "all",
+        validator = |s: &str| match s.parse::<usize>() {
+            Ok(0) => Err("rank must be at least 1".to_owned()),
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        })]
+    pub nth: Option<usize>,
+
     /// Return the (0-based) index of the file instead of the path.
     #[clap(
         short = 'i',
         long = "index")]
     pub index: bool,
 
+    /// Print nothing, and exit with status 0 if the first path is selected or
+    /// all paths are equal, 1 if another path is selected, or 2 if an error
+    /// occurs.
+    #[clap(
+        short = 'q',
+        long = "quiet",
+        conflicts_with_all = &[
+            "index", "format", "print", "output", "print0"])]
+    pub quiet: bool,
+
+    /// Exit with the given status codes for each outcome, e.g.,
+    /// 'first=0,other=1,equal=0,error=2'.
+    ///
+    /// The outcomes are 'first' when the first path is selected, 'other' when
+    /// another path is selected, 'equal' when all paths compare equal, and
+    /// 'error'. Outcomes not given use the codes shown above, which are also
+    /// used in quiet mode. Codes must be between 0 and 255.
+    #[clap(
+        long = "exit-map",
+        value_name = "MAP")]
+    pub exit_map: Option<ExitMap>,
+
+    /// Print nothing, and exit with status 0 if the files are equal, 10 if
+    /// the first is selected, 11 if the other is selected, or 2 if an error
+    /// occurs. Intended for comparing two files.
+    #[clap(
+        long = "exit-direction",
+        conflicts_with_all = &[
+            "exit-map", "index", "format", "print", "output", "print0"])]
+    pub exit_direction: bool,
+
+    /// Terminate each output line with a NUL character instead of a newline,
+    /// for use with 'xargs -0'.
+    #[clap(
+        long = "print0")]
+    pub print0: bool,
+
+    /// Print each result using the given template instead of its path.
+    ///
+    /// Templates contain field names in braces, e.g., '{path}\t{mtime}'. The
+    /// available fields are index, path, name, mtime, atime, ctime, and size.
+    /// Use '{{' and '}}' for literal braces.
+    #[clap(
+        long = "format",
+        value_name = "TEMPLATE",
+        conflicts_with = "index")]
+    pub format: Option<OutputFormat>,
+
+    /// Print the given fields of each result separated by tabs instead of its
+    /// path, e.g., 'mtime,size,path'.
+    ///
+    /// The available fields are index, path, name, mtime, atime, ctime, and
+    /// size. Unavailable values are printed as '-'.
+    #[clap(
+        long = "print",
+        value_name = "FIELDS",
+        use_value_delimiter = true,
+        conflicts_with_all = &["index", "format", "output"])]
+    pub print: Vec<Field>,
+
+    /// Print the results as a table with a header row, giving the path,
+    /// modification time, size, and rank of each file.
+    #[clap(
+        long = "output",
+        value_name = "TABLE",
+        conflicts_with_all = &["index", "format"],
+        arg_enum)]
+    pub output: Option<TableFormat>,
+
+    /// The format of printed times, in '--format', '--print', '--output',
+    /// and '--explain' output.
+    ///
+    /// 'iso8601' prints timestamps in the local time zone, 'epoch' prints the
+    /// number of seconds since the Unix epoch, and 'relative' prints the time
+    /// since the current time, e.g., '3 minutes ago'.
+    #[clap(
+        long = "time-format",
+        value_name = "FORMAT",
+        default_value = "iso8601",
+        arg_enum)]
+    pub time_format: TimeFormat,
+
+    /// Print ISO 8601 timestamps in UTC instead of the local time zone.
+    #[clap(
+        long = "utc")]
+    pub utc: bool,
+
+    /// Print an explanation of how the result was selected to standard
+    /// error. Each file is given with its modification time, size, and how
+    /// it compared against the greatest file found before it.
+    #[clap(
+        long = "explain",
+        conflicts_with_all = &["sort", "top", "nth", "plan"])]
+    pub explain: bool,
+
+    /// Print the number of files stat'ed, content diffs performed, bytes
+    /// read by internal diffs, and diff subprocesses spawned, along with the
+    /// wall time, to standard error.
+    #[clap(
+        long = "stats",
+        conflicts_with = "plan")]
+    pub stats: bool,
+
+    /// Show a progress bar of the files processed and bytes compared on
+    /// standard error while comparing. Nothing is shown if standard error is
+    /// not a terminal.
+    #[clap(
+        long = "progress",
+        conflicts_with = "plan")]
+    pub progress: bool,
+
+    /// Print a report of how the result was selected as JSON instead of the
+    /// result. The report gives the metadata of each file and how it
+    /// compared against the greatest file found before it.
+    ///
+    /// Paths excluded by '--where' are not included in the report, and the
+    /// indices in the report refer to its entries.
+    #[cfg(feature = "serde")]
+    #[clap(
+        long = "json",
+        conflicts_with_all = &[
+            "sort", "top", "nth", "index", "format", "print", "output",
+            "print0", "quiet", "exit-direction", "plan",
+        ])]
+    pub json: bool,
+
+    /// Print the IO operations which the comparison would perform, such as
+    /// opening and reading files or running subprocesses, without performing
+    /// them.
+    ///
+    /// Paths are still gathered from directories and globs, but source
+    /// commands are printed instead of run, and neither the manifest nor
+    /// standard input is written. Each file after the first is assumed to be
+    /// compared against the first, and the metadata of files is read to
+    /// estimate the number of bytes read.
+    #[clap(
+        long = "plan")]
+    pub plan: bool,
+
+    /// Write the time spent opening, reading the metadata of, and diffing
+    /// each file, along with the number of bytes read from it, to the given
+    /// file as JSON.
+    #[cfg(feature = "serde")]
+    #[clap(
+        long = "timings",
+        value_name = "FILE",
+        parse(from_os_str))]
+    pub timings: Option<PathBuf>,
+
+    /// Determines which file property is used to order the files.
+    #[clap(
+        short = 'k',
+        long = "key",
+        default_value = "modified",
+        arg_enum)]
+    pub key: CompareKey,
+
+    /// The file property used to order files whose key is unavailable, such
+    /// as a modification time on filesystems which don't record one. By
+    /// default, an unavailable modification, access, or creation time is an
+    /// error.
+    #[clap(
+        long = "fallback-key",
+        value_name = "KEY",
+        arg_enum)]
+    pub fallback_key: Option<CompareKey>,
+
+    /// Additional file properties used to order files with equal keys, in
+    /// order of priority.
+    #[clap(
+        short = 't',
+        long = "tie-break",
+        use_value_delimiter = true,
+        arg_enum)]
+    pub tie_break: Vec<CompareKey>,
+
+    /// Consider modification times within the given duration of each other
+    /// as equal.
+    ///
+    /// Accepts durations such as '1s' or '500ms'.
+    #[clap(
+        long = "mtime-tolerance",
+        default_value = "0s",
+        parse(try_from_str = humantime::parse_duration))]
+    pub mtime_tolerance: Duration,
+
+    /// Exclude files modified within the given duration of the current time.
+    ///
+    /// Accepts durations such as '5s' or '1m'. This avoids selecting files
+    /// which may still be being written.
+    #[clap(
+        long = "settled",
+        default_value = "0s",
+        parse(try_from_str = humantime::parse_duration))]
+    pub settled: Duration,
+
+    /// Exclude files whose size or modification time changes between two
+    /// checks separated by the given delay.
+    ///
+    /// The delay defaults to 100ms if not given. This avoids selecting files
+    /// which are still being written.
+    #[clap(
+        long = "stable",
+        value_name = "DELAY",
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "100ms",
+        parse(try_from_str = humantime::parse_duration))]
+    pub stable: Option<Duration>,
+
+    /// Retry reading metadata, opening files, and comparing content up to the
+    /// given number of times when they fail with transient IO errors, such as
+    /// EIO or timeouts on network filesystems.
+    #[clap(
+        long = "retries",
+        value_name = "N",
+        default_value = "0")]
+    pub retries: u32,
+
+    /// The delay before the first retry given by '--retries'. Each later retry
+    /// waits twice as long as the one before it.
+    ///
+    /// Accepts durations such as '100ms' or '1s'.
+    #[clap(
+        long = "retry-backoff",
+        value_name = "DELAY",
+        default_value = "100ms",
+        parse(try_from_str = humantime::parse_duration))]
+    pub retry_backoff: Duration,
+
+    /// Compare the files which symbolic links point to. This is the default.
+    #[clap(
+        short = 'L',
+        long = "follow-symlinks",
+        overrides_with = "no-dereference")]
+    pub follow_symlinks: bool,
+
+    /// Compare symbolic links themselves instead of the files they point to.
+    ///
+    /// The metadata of each link is used instead of its target's. When
+    /// comparing content, links have the same content if they point to the
+    /// same path.
+    #[clap(
+        short = 'P',
+        long = "no-dereference",
+        overrides_with = "follow-symlinks")]
+    pub no_dereference: bool,
+
     /// Consider files with the same content as equal.
     #[clap(
         short = 'd',
         long = "diff")]
     pub diff: bool,
 
+    /// Require files to also have the same permissions to be considered
+    /// equal when comparing content. On unix platforms, the files must also
+    /// have the same owning user and group.
+    #[clap(long = "compare-permissions")]
+    pub compare_permissions: bool,
+
+    /// The size of the read buffer used for each file when comparing content,
+    /// e.g., '4M'. Sizes may have a K, M, G, or T suffix giving a power of
+    /// 1024. Giving a buffer size compares content internally instead of
+    /// with 'cmp'.
+    #[clap(
+        long = "buffer-size",
+        value_name = "SIZE",
+        parse(try_from_str = parse_size))]
+    pub buffer_size: Option<u64>,
+
+    /// The number of threads used to compare the content of each pair of
+    /// files. Each file is split into one chunk per thread, which may be much
+    /// faster for very large files on fast storage. Giving a thread count
+    /// compares content internally instead of with 'cmp'.
+    #[clap(
+        long = "diff-threads",
+        value_name = "N")]
+    pub diff_threads: Option<usize>,
+
+    /// The largest number of bytes read from each file when comparing
+    /// content, e.g., '100M'. Sizes may have a K, M, G, or T suffix giving a
+    /// power of 1024. Files which are the same up to the limit are handled as
+    /// given by '--on-read-limit'. Giving a read limit compares content
+    /// internally instead of with 'cmp'.
+    #[clap(
+        long = "max-read-bytes",
+        value_name = "SIZE",
+        conflicts_with_all = &[
+            "ignore-line-endings",
+            "ignore-trailing-space",
+            "ignore-all-space",
+            "ignore-encoding",
+            "decompress",
+            "sample",
+        ],
+        parse(try_from_str = parse_size))]
+    pub max_read_bytes: Option<u64>,
+
+    /// Determines how to handle files which are the same up to the limit
+    /// given by '--max-read-bytes'. Use 'different' to assume their content
+    /// differs, or 'error' to fail.
+    #[clap(
+        long = "on-read-limit",
+        default_value = "different",
+        requires = "max-read-bytes",
+        arg_enum)]
+    pub on_read_limit: ReadLimitBehavior,
+
+    /// When comparing content, compare it as text and treat CRLF and LF line
+    /// endings as equivalent.
+    #[clap(long = "ignore-line-endings")]
+    pub ignore_line_endings: bool,
+
+    /// When comparing content, compare it as text and ignore whitespace at
+    /// the end of each line.
+    #[clap(long = "ignore-trailing-space")]
+    pub ignore_trailing_space: bool,
+
+    /// When comparing content, compare it as text and ignore all whitespace
+    /// within each line.
+    #[clap(long = "ignore-all-space")]
+    pub ignore_all_space: bool,
+
+    /// When comparing content, compare it as text and decode text beginning
+    /// with a UTF-8 or UTF-16 byte order mark, so that the same text stored
+    /// in different encodings is equivalent.
+    #[clap(long = "ignore-encoding")]
+    pub ignore_encoding: bool,
+
+    /// When comparing content, decompress compressed files first, so that a
+    /// compressed file may compare equal to an uncompressed copy.
+    ///
+    /// Compressed files are detected by their magic bytes or extension.
+    /// 'auto' decompresses all supported formats, and 'gzip', 'zstd', and
+    /// 'bzip2' decompress only files in that format. Zstd and bzip2 support
+    /// depend on the features fcmp was built with. This can't be combined
+    /// with the text comparison options.
+    #[clap(
+        long = "decompress",
+        value_name = "FORMAT",
+        conflicts_with_all = &[
+            "ignore-line-endings",
+            "ignore-trailing-space",
+            "ignore-all-space",
+            "ignore-encoding",
+        ],
+        arg_enum)]
+    pub decompress: Option<Decompression>,
+
+    /// When comparing content, compare only the first and last blocks of
+    /// files with the same size and the given number of randomly placed
+    /// interior blocks, which defaults to 16.
+    ///
+    /// This is much faster for very large files such as media files, but is
+    /// probabilistic: files which differ only outside of the sampled blocks
+    /// compare equal. This can't be combined with '--buffer-size',
+    /// '--diff-threads', or the text comparison or decompression options.
+    #[clap(
+        long = "sample",
+        value_name = "BLOCKS",
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "16",
+        conflicts_with_all = &[
+            "ignore-line-endings",
+            "ignore-trailing-space",
+            "ignore-all-space",
+            "ignore-encoding",
+            "decompress",
+            "buffer-size",
+            "diff-threads",
+        ])]
+    pub sample: Option<usize>,
+
+    /// The size of each block compared by '--sample', e.g., '1M'. Sizes may
+    /// have a K, M, G, or T suffix giving a power of 1024. Defaults to 64K.
+    #[clap(
+        long = "sample-size",
+        value_name = "SIZE",
+        requires = "sample",
+        parse(try_from_str = parse_size))]
+    pub sample_size: Option<u64>,
+
+    /// Overrides how file contents are compared for pairs of paths which both
+    /// match a glob pattern.
+    ///
+    /// Overrides are given in the form '<GLOB>=<DIFF>', where DIFF is one of
+    /// none, internal, sampled, decompress, gzip, zstd, bzip2, cmp, diff,
+    /// json, yaml, or archive, e.g., '*.log=none'. 'sampled' compares blocks
+    /// of content as '--sample' does. 'decompress' compares decompressed
+    /// content as '--decompress=auto' does, and 'gzip', 'zstd', and 'bzip2'
+    /// decompress only that format. 'json' and 'yaml' compare the parsed
+    /// values of the files, ignoring formatting and key order. 'archive'
+    /// compares the files inside zip and tar archives, ignoring their order
+    /// and archive-level metadata. If multiple overrides match, the first is
+    /// used.
+    #[clap(
+        long = "diff-for",
+        value_name = "GLOB=DIFF",
+        number_of_values = 1)]
+    pub diff_for: Vec<DiffOverride>,
+
+    /// Save the progress of content comparisons to the given state file, and
+    /// resume any interrupted comparison recorded there.
+    #[cfg(feature = "serde")]
+    #[clap(
+        long = "resume",
+        value_name = "STATE_FILE",
+        parse(from_os_str))]
+    pub resume: Option<PathBuf>,
+
+    /// Determines the output when other files have the same content as the
+    /// result.
+    ///
+    /// Content is compared using the '--diff' operation, or an internal
+    /// comparison if '--diff' is not given. 'print-first' prints only the
+    /// result, 'print-all' prints the result followed by every file with the
+    /// same content, and 'exit-special' prints the result and exits with status
+    /// 3 if any other file has the same content.
+    #[clap(
+        long = "on-equal",
+        default_value = "print-first",
+        arg_enum)]
+    pub on_equal: OnEqual,
+
     /// Determines how to handle missing files.
     /// 
     /// By default, missing files will be treated as older than all other files.
+    /// Use 'newest' to treat missing files as newer than all other files,
+    /// 'ignore' to skip them, or 'error' to fail if any file is missing.
     #[clap(
         short = 'm',
         long = "missing",
         default_value = "oldest",
         arg_enum)]
     pub missing: MissingFileBehavior,
+
+    /// Determines the modification time of standard input when it is given
+    /// as '-' with other paths.
+    ///
+    /// By default, standard input is treated as modified when it is read.
+    /// Use 'oldest' or 'newest' to treat it as older or newer than all other
+    /// files.
+    #[clap(
+        long = "stdin-mtime",
+        default_value = "now",
+        arg_enum)]
+    pub stdin_mtime: StdinMtime,
+
+    /// Determines how to handle symbolic links whose targets are missing.
+    ///
+    /// By default, broken links are handled like missing files. Use 'oldest'
+    /// or 'newest' to treat them as older or newer than all other files, or
+    /// 'error' to fail if any link is broken.
+    #[clap(
+        long = "broken-symlinks",
+        default_value = "missing",
+        arg_enum)]
+    pub broken_symlinks: BrokenSymlinkBehavior,
+
+    /// Only compare files of the given kind, which is one of regular, dir,
+    /// symlink, or special. May be given multiple times.
+    ///
+    /// Symbolic links are compared if 'symlink' or the kind of their target
+    /// is given. Missing files are handled using '--missing'.
+    #[clap(
+        long = "only",
+        value_name = "KIND",
+        number_of_values = 1)]
+    pub only: Vec<EntryKind>,
+
+    /// Determines how to handle FIFOs, sockets, and device nodes, which are
+    /// never opened.
+    ///
+    /// By default, special files are compared by their metadata only, and
+    /// their content is considered different from all other files. Use 'skip'
+    /// to ignore them, or 'error' to fail if any file is a special file.
+    #[clap(
+        long = "special-files",
+        default_value = "metadata",
+        arg_enum)]
+    pub special_files: SpecialFilePolicy,
+
+    /// Determines how to handle files which can't be read due to insufficient
+    /// permissions.
+    ///
+    /// By default, an unreadable file is an error. Use 'skip' to ignore
+    /// unreadable files, or 'missing' to handle them as given by '--missing'.
+    /// The content of a file is only read when comparing content.
+    #[clap(
+        long = "unreadable",
+        default_value = "error",
+        arg_enum)]
+    pub unreadable: UnreadableFilePolicy,
+
+    /// Overrides how to handle missing files whose paths match a glob pattern.
+    ///
+    /// Overrides are given in the form '<GLOB>=<MISSING>', e.g.,
+    /// 'build/*.stamp=newest'. If multiple overrides match a path, the first
+    /// is used.
+    #[clap(
+        long = "missing-for",
+        value_name = "GLOB=MISSING",
+        number_of_values = 1)]
+    pub missing_for: Vec<MissingOverride>,
 }
 
+impl FcmpOptions {
+    /// Returns the [`OutputFormat`] used to print each result, if results are
+    /// not printed as paths.
+    ///
+    /// [`OutputFormat`]: crate::OutputFormat
+    #[must_use]
+    pub fn output_format(&self) -> Option<OutputFormat> {
+        if self.print.is_empty() {
+            self.format.clone()
+        } else {
+            Some(OutputFormat::from_fields(self.print.iter().copied()))
+        }
+    }
+
+    /// Returns the [`TimeStyle`] used to print times.
+    ///
+    /// [`TimeStyle`]: crate::TimeStyle
+    #[must_use]
+    pub fn time_style(&self) -> TimeStyle {
+        TimeStyle {
+            format: self.time_format,
+            utc: self.utc,
+        }
+    }
+
+    /// Returns the [`Comparator`] described by the options.
+    ///
+    /// [`Comparator`]: crate::Comparator
+    #[must_use]
+    pub fn comparator(&self) -> Comparator {
+        Comparator {
+            key: self.key,
+            fallback_key: self.fallback_key,
+            tie_break: TieBreak::new(self.tie_break.iter().copied()),
+            diff_op: if self.diff {
+                self.content_diff_op()
+            } else {
+                DiffOp::None
+            },
+            compare_permissions: self.compare_permissions,
+            missing: self.missing,
+            broken_symlinks: self.broken_symlinks,
+            special_files: self.special_files,
+            unreadable: self.unreadable,
+            path_filter: self.path_filter(),
+            missing_overrides: self.missing_for.clone(),
+            mtime_tolerance: self.mtime_tolerance,
+            settle_time: self.settled,
+            stability_delay: self.stable,
+            symlinks: if self.no_dereference {
+                SymlinkPolicy::NoFollow
+            } else {
+                SymlinkPolicy::Follow
+            },
+            #[cfg(feature = "serde")]
+            timings: self.timings
+                .as_ref()
+                .map(|_| Arc::new(TimingRecorder::new())),
+            stats: self.stats.then(|| Arc::new(StatsRecorder::new())),
+            retry: RetryPolicy {
+                retries: self.retries,
+                backoff: self.retry_backoff,
+            },
+            ..Comparator::default()
+        }
+    }
+
+    /// Returns the [`PathFilter`] selecting the kinds of file given by
+    /// '--only', if any are given.
+    ///
+    /// [`PathFilter`]: crate::PathFilter
+    fn path_filter(&self) -> Option<Arc<dyn PathFilter>> {
+        if self.only.is_empty() { return None; }
+        Some(Arc::new(FileTypeFilter::new(self.only.iter().copied())))
+    }
+
+    /// Returns `true` if results should be reported only by exit status.
+    #[must_use]
+    pub const fn is_quiet(&self) -> bool {
+        self.quiet || self.exit_direction
+    }
+
+    /// Returns the [`ExitMap`] used to determine the exit status, or `None`
+    /// if the exit status does not depend on the outcome.
+    ///
+    /// [`ExitMap`]: ExitMap
+    #[must_use]
+    pub fn exit_map(&self) -> Option<ExitMap> {
+        if self.exit_direction {
+            Some(ExitMap::direction())
+        } else if self.quiet {
+            Some(self.exit_map.unwrap_or_default())
+        } else {
+            self.exit_map
+        }
+    }
 
+    /// Returns the [`DiffOp`] used to find files with the same content as the
+    /// result.
+    ///
+    /// [`DiffOp`]: crate::DiffOp
+    #[must_use]
+    pub fn content_diff_op(&self) -> DiffOp {
+        DiffOp::routed(self.diff_for.clone(), self.default_diff_op())
+    }
+
+    /// Returns the [`DiffOp`] which the [`content_diff_op`] uses for the
+    /// given pair of paths, taking the first `--diff-for` override matching
+    /// both.
+    ///
+    /// [`DiffOp`]: crate::DiffOp
+    /// [`content_diff_op`]: FcmpOptions::content_diff_op
+    #[must_use]
+    pub fn content_diff_op_for(&self, a: &Path, b: &Path) -> DiffOp {
+        self.diff_for
+            .iter()
+            .find(|o| o.matches(a, b))
+            .map_or_else(|| self.default_diff_op(), |o| o.diff_op.clone())
+    }
+
+    /// Returns the [`DiffOp`] used for paths not matching any `--diff-for`
+    /// override.
+    ///
+    /// [`DiffOp`]: crate::DiffOp
+    fn default_diff_op(&self) -> DiffOp {
+        #[cfg(feature = "serde")]
+        if let Some(state_file) = &self.resume {
+            return DiffOp::resumable(state_file.clone());
+        }
+        match self.decompress {
+            None | Some(Decompression::None) => (),
+            Some(decompression) => return DiffOp::Decompressed(decompression),
+        }
+        if let Some(options) = self.sample_options() {
+            return DiffOp::Sampled(options);
+        }
+        let text_options = self.text_options();
+        if !text_options.is_exact() { return DiffOp::Text(text_options); }
+        if self.diff
+            && self.buffer_size.is_none()
+            && self.diff_threads.is_none()
+            && self.max_read_bytes.is_none()
+        {
+            DiffOp::posix_cmp()
+        } else {
+            DiffOp::internal_with(self.internal_options())
+        }
+    }
+
+    /// Returns the [`InternalOptions`] used to compare content internally.
+    ///
+    /// [`InternalOptions`]: crate::InternalOptions
+    #[must_use]
+    pub fn internal_options(&self) -> InternalOptions {
+        let mut options = InternalOptions::default();
+        if let Some(size) = self.buffer_size {
+            options.buffer_size = usize::try_from(size).unwrap_or(usize::MAX);
+        }
+        if let Some(threads) = self.diff_threads {
+            options.threads = threads;
+        }
+        options.max_read_bytes = self.max_read_bytes;
+        options.read_limit = self.on_read_limit;
+        options
+    }
+
+    /// Returns the [`SampleOptions`] used to compare sampled content, if
+    /// content is sampled.
+    ///
+    /// [`SampleOptions`]: crate::SampleOptions
+    #[must_use]
+    pub fn sample_options(&self) -> Option<SampleOptions> {
+        let blocks = self.sample?;
+        let mut options = SampleOptions { blocks, ..SampleOptions::default() };
+        if let Some(size) = self.sample_size {
+            options.block_size = usize::try_from(size).unwrap_or(usize::MAX);
+        }
+        Some(options)
+    }
+
+    /// Returns the [`TextOptions`] used to compare content as text.
+    ///
+    /// [`TextOptions`]: crate::TextOptions
+    #[must_use]
+    pub fn text_options(&self) -> TextOptions {
+        TextOptions {
+            ignore_line_endings: self.ignore_line_endings,
+            ignore_trailing_space: self.ignore_trailing_space,
+            ignore_all_space: self.ignore_all_space,
+            ignore_encoding: self.ignore_encoding,
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FcmpCommand
+////////////////////////////////////////////////////////////////////////////////
+/// Fcmp subcommands.
+#[derive(Debug, Clone)]
+#[derive(Subcommand)]
+pub enum FcmpCommand {
+    /// Print the most recently modified of the given files.
+    Newest(FcmpOptions),
+
+    /// Print the least recently modified of the given files.
+    ///
+    /// This is the same as 'newest --reverse'.
+    Oldest(FcmpOptions),
+
+    /// Print all of the given files, from most to least recently modified.
+    ///
+    /// This is the same as 'newest --sort'.
+    Sort(FcmpOptions),
+
+    /// Print the files beyond the most recently modified files to keep, such
+    /// as old backups to remove.
+    ///
+    /// The files are printed from most to least recently modified. Files
+    /// which compare equal to the least recent kept file are also kept, and
+    /// missing files are never printed. Use '--print0' to give the output to
+    /// 'xargs -0 rm'. Paths containing a newline are refused without
+    /// '--print0'.
+    ///
+    /// Options which select or report results in other ways, such as
+    /// '--quiet', '--json', '--output', and '--sort', can't be used.
+    Rotate {
+        /// The number of files to keep.
+        // The output is a list of files to remove, so options which would
+        // change what it means are refused rather than ignored.
+        #[clap(
+            long = "keep",
+            value_name = "N",
+            conflicts_with_all = &[
+                "all", "sort", "top", "nth", "index", "quiet", "exit-map",
+                "exit-direction", "output", "explain", "stats", "progress",
+                "json", "plan", "timings", "on-equal"])]
+        keep: usize,
+        /// Options for selecting the files.
+        #[clap(flatten)]
+        options: FcmpOptions,
+    },
+
+    /// Run the comparison jobs defined in a TOML or JSON job file.
+    ///
+    /// Each job selects a file from its paths and the results are reported
+    /// with one line per job.
+    Run {
+        /// The job file to run. Files with a '.json' extension are parsed as
+        /// JSON, and all others as TOML.
+        #[clap(parse(from_os_str))]
+        job_file: PathBuf,
+    },
+
+    /// Check whether two files are byte-identical.
+    ///
+    /// Prints nothing, and exits with status 0 if the files are identical, 1
+    /// if they differ, or 2 if an error occurs, like 'cmp -s'. Either file may
+    /// be '-' to compare the content of standard input.
+    Check {
+        /// The first file to compare.
+        #[clap(parse(from_os_str))]
+        a: PathBuf,
+        /// The second file to compare.
+        #[clap(parse(from_os_str))]
+        b: PathBuf,
+        /// Compare zip or tar archives by the files they contain instead,
+        /// ignoring the order of the files and any archive-level metadata.
+        #[cfg(feature = "archive")]
+        #[clap(long = "archive")]
+        archive: bool,
+    },
+
+    /// Check whether a target file is up to date with the files it depends
+    /// on.
+    ///
+    /// Prints nothing, and exits with status 0 if the target exists and is
+    /// not older than any dependency, 1 if it is out of date, or 2 if an error
+    /// occurs. Dependencies modified at the same time as the target don't
+    /// make it out of date, as with 'make'.
+    Uptodate {
+        /// The target file.
+        #[clap(parse(from_os_str))]
+        target: PathBuf,
+        /// The files the target depends on.
+        #[clap(parse(from_os_str))]
+        deps: Vec<PathBuf>,
+        /// Determines how to handle missing dependencies.
+        ///
+        /// By default, missing dependencies are older than the target. Use
+        /// 'newest' to make the target out of date, 'ignore' to skip them, or
+        /// 'error' to fail.
+        #[clap(
+            short = 'm',
+            long = "missing",
+            default_value = "oldest",
+            arg_enum)]
+        missing: MissingFileBehavior,
+        /// Consider modification times within the given duration of each
+        /// other as equal.
+        ///
+        /// Accepts durations such as '1s' or '500ms'.
+        #[clap(
+            long = "mtime-tolerance",
+            default_value = "0s",
+            parse(try_from_str = humantime::parse_duration))]
+        mtime_tolerance: Duration,
+    },
+
+    /// Print the groups of files with identical content.
+    ///
+    /// Each group is printed as one path per line, and groups are separated
+    /// by an empty line. Files without duplicates are not printed. Paths which
+    /// refer to the same file as another path in the group, such as hardlinks,
+    /// are skipped with a notice.
+    ///
+    /// Only files of the same size are read, and their content is compared
+    /// by SHA-256 digest.
+    Dups {
+        /// File paths to compare.
+        #[clap(parse(from_os_str), required = true)]
+        paths: Vec<PathBuf>,
+        /// Compare the content of files with the same digest byte by byte.
+        #[clap(long = "verify")]
+        verify: bool,
+        /// Print a shell script which keeps the most recently modified file
+        /// of each group and replaces the others with hardlinks or symbolic
+        /// links to it, or removes them with 'rm-script'.
+        #[clap(long = "emit", value_name = "ACTION", arg_enum)]
+        emit: Option<DedupeAction>,
+        /// Take the actions given by '--emit' instead of printing them.
+        #[clap(long = "apply", requires = "emit")]
+        apply: bool,
+    },
+
+    /// Record the size, modification time, and content digest of every file
+    /// in a directory tree, for use with 'fcmp changed'.
+    #[cfg(feature = "serde")]
+    Snapshot {
+        /// The root of the directory tree.
+        #[clap(parse(from_os_str))]
+        root: PathBuf,
+        /// The file to write the snapshot to.
+        #[clap(
+            short = 'o',
+            long = "output",
+            value_name = "FILE",
+            parse(from_os_str))]
+        output: PathBuf,
+    },
+
+    /// Print the files in a directory tree which are new, modified, or
+    /// deleted since a snapshot was taken.
+    ///
+    /// Each change is printed as 'A', 'M', or 'D', followed by a tab and the
+    /// path of the file. Exits with status 1 if any file has changed.
+    #[cfg(feature = "serde")]
+    Changed {
+        /// The root of the directory tree.
+        #[clap(parse(from_os_str))]
+        root: PathBuf,
+        /// The snapshot file to compare against.
+        #[clap(
+            long = "against",
+            value_name = "FILE",
+            parse(from_os_str))]
+        against: PathBuf,
+    },
+
+    /// Answer comparison queries until stopped, keeping content digests
+    /// cached between queries.
+    ///
+    /// Each query is a line of JSON with an 'op' of 'newest' or 'equal' and a
+    /// list of 'paths', and is answered with a line of JSON giving the
+    /// selected 'path' and 'index' or whether the files are 'equal'. Queries
+    /// are read from standard input unless a socket is given.
+    #[cfg(feature = "serde")]
+    Serve {
+        /// Listen for clients on a Unix socket at the given path instead of
+        /// using standard input and output.
+        #[clap(long = "socket", value_name = "PATH", parse(from_os_str))]
+        socket: Option<PathBuf>,
+    },
+
+    /// Print a completion script for the given shell.
+    ///
+    /// The script completes subcommands, options, and the values of options
+    /// such as '--key' and '--missing'.
+    Completions {
+        /// The shell to generate the completion script for.
+        #[clap(arg_enum)]
+        shell: Shell,
+    },
+
+    /// Print the most recently modified of the given files, and again each
+    /// time any of them changes.
+    ///
+    /// The files are watched until Ctrl-C is pressed. Each change prints one
+    /// line, or with '--json' one JSON object giving the changed paths and
+    /// the selected file.
+    #[cfg(feature = "watch")]
+    Watch(FcmpOptions),
+
+    /// Print the differences between two files as a unified diff.
+    ///
+    /// Prints the changed lines and exits with status 1 if the files differ,
+    /// or exits with status 0 if they are identical. Binary files are only
+    /// reported as differing. Either file may be '-' to compare the content
+    /// of standard input.
+    Diff {
+        /// The first file to compare.
+        #[clap(parse(from_os_str))]
+        a: PathBuf,
+        /// The second file to compare.
+        #[clap(parse(from_os_str))]
+        b: PathBuf,
+        /// The number of unchanged lines to print around each change.
+        #[clap(
+            short = 'U',
+            long = "unified",
+            value_name = "LINES",
+            default_value = "3")]
+        context: usize,
+        /// Only report whether the files differ, without printing the
+        /// differences.
+        #[clap(short = 'q', long = "brief")]
+        brief: bool,
+        /// Only report the byte offset and line number of the first
+        /// difference, like 'cmp'.
+        #[clap(short = 'b', long = "offset", conflicts_with = "brief")]
+        offset: bool,
+    },
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// OnEqual
+////////////////////////////////////////////////////////////////////////////////
+/// Options for handling files with the same content as the result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(clap::ArgEnum)]
+pub enum OnEqual {
+    /// Print all files with the same content as the result.
+    PrintAll,
+    /// Print only the result.
+    PrintFirst,
+    /// Exit with a dedicated status code if other files have the same content
+    /// as the result.
+    ExitSpecial,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ColorChoice
+////////////////////////////////////////////////////////////////////////////////
+/// Options for coloring output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(clap::ArgEnum)]
+pub enum ColorChoice {
+    /// Color output written to a terminal, unless the `NO_COLOR` environment
+    /// variable is set.
+    #[default]
+    Auto,
+    /// Always color output, even when it is written to a pipe or file.
+    Always,
+    /// Never color output.
+    Never,
+}
+
+impl ColorChoice {
+    /// Enables or disables colored output on standard output and standard
+    /// error according to the choice.
+    pub fn apply(self) {
+        let enabled = match self {
+            Self::Auto   => return,
+            Self::Always => true,
+            Self::Never  => false,
+        };
+        console::set_colors_enabled(enabled);
+        console::set_colors_enabled_stderr(enabled);
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Outcome
+////////////////////////////////////////////////////////////////////////////////
+/// The outcome of selecting a file, used to determine the exit status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The first path was selected.
+    First,
+    /// A path other than the first was selected, or no path was selected.
+    Other,
+    /// All paths compared equal.
+    Equal,
+    /// An error occurred.
+    Error,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ExitMap
+////////////////////////////////////////////////////////////////////////////////
+/// The exit status codes used for each [`Outcome`]. Exit statuses are limited
+/// to a single byte, so each code is in the range `0..=255`.
+///
+/// [`Outcome`]: Outcome
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitMap {
+    /// The exit status used when the first path is selected.
+    pub first: u8,
+    /// The exit status used when another path is selected.
+    pub other: u8,
+    /// The exit status used when all paths compare equal.
+    pub equal: u8,
+    /// The exit status used when an error occurs.
+    pub error: u8,
+}
+
+impl ExitMap {
+    /// Returns an `ExitMap` which encodes the direction of a comparison:
+    /// 0 if all paths are equal, 10 if the first path is selected, 11 if
+    /// another path is selected, and 2 on error.
+    #[must_use]
+    pub const fn direction() -> Self {
+        Self {
+            first: 10,
+            other: 11,
+            equal: 0,
+            error: 2,
+        }
+    }
+
+    /// Returns the exit status code for the given [`Outcome`].
+    ///
+    /// [`Outcome`]: Outcome
+    #[must_use]
+    pub fn code(&self, outcome: Outcome) -> i32 {
+        i32::from(match outcome {
+            Outcome::First => self.first,
+            Outcome::Other => self.other,
+            Outcome::Equal => self.equal,
+            Outcome::Error => self.error,
+        })
+    }
+}
+
+impl Default for ExitMap {
+    fn default() -> Self {
+        Self {
+            first: 0,
+            other: 1,
+            equal: 0,
+            error: 2,
+        }
+    }
+}
+
+impl FromStr for ExitMap {
+    type Err = ExitMapParseError;
+
+    /// Parses an `ExitMap` of the form `first=0,other=1,equal=0,error=2`.
+    /// Outcomes which are not given use their default exit status, and codes
+    /// outside the range `0..=255` are rejected.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut map = Self::default();
+        for entry in s.split(',') {
+            let (outcome, code) = entry
+                .split_once('=')
+                .ok_or(ExitMapParseError)?;
+            let code = code.trim().parse().map_err(|_e| ExitMapParseError)?;
+            match outcome.trim() {
+                o if o.eq_ignore_ascii_case("first") => map.first = code,
+                o if o.eq_ignore_ascii_case("other") => map.other = code,
+                o if o.eq_ignore_ascii_case("equal") => map.equal = code,
+                o if o.eq_ignore_ascii_case("error") => map.error = code,
+                _ => return Err(ExitMapParseError),
+            }
+        }
+        Ok(map)
+    }
+}
+
+/// An error indicating a failure to parse an [`ExitMap`].
+///
+/// [`ExitMap`]: ExitMap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitMapParseError;
+
+impl std::error::Error for ExitMapParseError {}
+
+impl std::fmt::Display for ExitMapParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse ExitMap")
+    }
+}
@@ -9,182 +9,1905 @@
 ////////////////////////////////////////////////////////////////////////////////
 
 // Internal library imports.
+#[cfg(feature = "archive")]
+use crate::archive::ArchivePath;
+#[cfg(feature = "archive")]
+use crate::archive::MemberMetadata;
+use crate::backend::available_threads;
+use crate::cancel::CancelToken;
+use crate::cancel::Cancelled;
+use crate::clock::Clock;
+use crate::clock::SystemClock;
+use crate::error::FcmpError;
+use crate::file_id::FileId;
+use crate::fs::FileSystem;
+use crate::fs::FsMetadata;
+#[cfg(feature = "git")]
+use crate::git::BlobMetadata;
+#[cfg(feature = "git")]
+use crate::git::GitBlob;
+use crate::filter::PathFilter;
 use crate::ops::DiffOp;
+use crate::ops::is_virtual;
+use crate::ops::open_content;
+use crate::ops::ReadLimitExceeded;
+use crate::ops::subprocesses_spawned;
+use crate::ops::with_cancel;
+use crate::ops::with_file_system;
+use crate::paths::canonical_path;
+use crate::progress::Progress;
+#[cfg(feature = "http")]
+use crate::remote::RemoteMetadata;
+#[cfg(feature = "http")]
+use crate::remote::RemoteUrl;
+use crate::report::compare_all_report;
+use crate::report::Decision;
+use crate::report::DecisionBasis;
+use crate::retry::RetryPolicy;
+use crate::stats::StatsRecorder;
+use crate::timing::TimingRecorder;
+use crate::walk::EntryKind;
+use crate::walk::is_special;
 
 // External library imports.
-use anyhow::anyhow;
+use glob::Pattern;
+use sha2::Digest as _;
+use sha2::Sha256;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
 
 // Standard library imports.
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fs::File;
 use std::fs::Metadata;
+use std::hash::Hash;
 use std::io::ErrorKind;
 use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::time::Instant;
 use std::time::SystemTime;
 
 
 ////////////////////////////////////////////////////////////////////////////////
 // FileCmp
 ////////////////////////////////////////////////////////////////////////////////
-/// A [`File`] wrapper which provides methods for doing file comparisons.
-/// operations.
+/// A wrapper for the path and metadata of a file which provides methods for
+/// doing file comparisons.
 ///
-/// [`File`]: std::fs::File
+/// The file is only opened while its content is being read, so a `FileCmp`
+/// does not hold a file descriptor.
 #[derive(Debug)]
 pub struct FileCmp {
     /// The path of the file.
     path: PathBuf,
-    /// The opened file, if it has been found.
-    file: Option<File>,
+    /// Whether the file has been found, following any symbolic links.
+    exists: bool,
     /// The file's metadata, if available.
     metadata: Option<Metadata>,
+    /// The file's platform identity, once it has been determined.
+    id: OnceLock<Option<FileId>>,
+    /// Whether the file is missing because it is a broken symbolic link.
+    broken_link: bool,
+    /// Whether the file is a FIFO, socket, or device node, which is not
+    /// opened.
+    special: bool,
+    /// The SHA-256 digest of the file's content, once it has been computed.
+    digest: OnceLock<Option<[u8; 32]>>,
+    /// The metadata of the file, if it is a member of an archive which has
+    /// been found.
+    #[cfg(feature = "archive")]
+    member: Option<MemberMetadata>,
+    /// The metadata of the file, if it is a remote file which has been found.
+    #[cfg(feature = "http")]
+    remote: Option<RemoteMetadata>,
+    /// The metadata of the file, if it is a file in a git repository which
+    /// has been found.
+    #[cfg(feature = "git")]
+    blob: Option<BlobMetadata>,
+    /// The file system the file is read from, if it is not the local file
+    /// system.
+    fs: Option<Arc<dyn FileSystem>>,
+    /// The metadata of the file, if it is read from a [`FileSystem`] and has
+    /// been found.
+    ///
+    /// [`FileSystem`]: crate::FileSystem
+    fs_metadata: Option<FsMetadata>,
 }
 
 impl TryFrom<PathBuf> for FileCmp {
     type Error = std::io::Error;
     fn try_from(path: PathBuf) -> Result<Self, Self::Error> {
-        match File::options()
-            .read(true)
-            .open(&path)
-        {
-            Ok(file) => Ok(Self {
-                path,
-                metadata: Some(file.metadata()?),
-                file: Some(file),
+        Self::open_timed(
+            path,
+            SymlinkPolicy::Follow,
+            RetryPolicy::none(),
+            None)
+    }
+}
+
+impl TryFrom<&Path> for FileCmp {
+    type Error = std::io::Error;
+    fn try_from(path: &Path) -> Result<Self, Self::Error> {
+        Self::try_from(path.to_path_buf())
+    }
+}
+
+
+impl FileCmp {
+    /// Opens a file comparer for the given path, recording the time spent
+    /// reading its metadata in the given [`TimingRecorder`] and retrying
+    /// transient errors according to the given [`RetryPolicy`]. The file
+    /// itself is not opened.
+    ///
+    /// If the [`SymlinkPolicy`] is `NoFollow` and the path is a symbolic
+    /// link, the link's own metadata is used. If
+    /// the path is an [`ArchivePath`], the member is found in the archive and
+    /// its recorded metadata is used. If the path is a [`RemoteUrl`], the
+    /// metadata is taken from the headers of a `HEAD` request. If the path is
+    /// a [`GitBlob`], the blob is found in the repository's object database.
+    ///
+    /// [`ArchivePath`]: crate::ArchivePath
+    /// [`GitBlob`]: crate::GitBlob
+    /// [`RemoteUrl`]: crate::RemoteUrl
+    /// [`RetryPolicy`]: RetryPolicy
+    /// [`SymlinkPolicy`]: SymlinkPolicy
+    /// [`TimingRecorder`]: TimingRecorder
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "stat",
+        level = "trace",
+        skip_all,
+        fields(path = %path.display())))]
+    fn open_timed(
+        path: PathBuf,
+        symlinks: SymlinkPolicy,
+        retry: RetryPolicy,
+        timings: Option<&TimingRecorder>)
+        -> Result<Self, std::io::Error>
+    {
+        #[cfg(feature = "archive")]
+        if let Some(archive_path) = ArchivePath::parse(&path) {
+            let start = Instant::now();
+            let member = archive_path.metadata()?;
+            let open_time = start.elapsed();
+            #[cfg(feature = "tracing")]
+            tracing::trace!(
+                found = member.is_some(),
+                elapsed = ?open_time,
+                "read archive member metadata");
+            if let Some(timings) = timings {
+                timings.record(&path, |t| t.open += open_time);
+            }
+            return Ok(Self { member, ..Self::not_found(path) });
+        }
+
+        #[cfg(feature = "http")]
+        if let Some(url) = RemoteUrl::parse(&path) {
+            let start = Instant::now();
+            let remote = retry.run(|| url.metadata())?;
+            let stat_time = start.elapsed();
+            #[cfg(feature = "tracing")]
+            tracing::trace!(
+                found = remote.is_some(),
+                elapsed = ?stat_time,
+                "read remote metadata");
+            if let Some(timings) = timings {
+                timings.record(&path, |t| t.stat += stat_time);
+            }
+            return Ok(Self { remote, ..Self::not_found(path) });
+        }
+
+        #[cfg(feature = "git")]
+        if let Some(git_blob) = GitBlob::parse(&path) {
+            let start = Instant::now();
+            let blob = git_blob.metadata()?;
+            let stat_time = start.elapsed();
+            #[cfg(feature = "tracing")]
+            tracing::trace!(
+                found = blob.is_some(),
+                elapsed = ?stat_time,
+                "read git blob metadata");
+            if let Some(timings) = timings {
+                timings.record(&path, |t| t.stat += stat_time);
+            }
+            return Ok(Self { blob, ..Self::not_found(path) });
+        }
+
+        if symlinks == SymlinkPolicy::NoFollow {
+            let start = Instant::now();
+            let link = retry.run(|| std::fs::symlink_metadata(&path))
+                .ok()
+                .filter(Metadata::is_symlink);
+            if let (Some(timings), Some(_)) = (timings, &link) {
+                let stat_time = start.elapsed();
+                timings.record(&path, |t| t.stat += stat_time);
+            }
+            if let Some(metadata) = link {
+                return Ok(Self {
+                    metadata: Some(metadata),
+                    ..Self::not_found(path)
+                });
+            }
+        }
+
+        // The file is not opened until its content is needed.
+        let start = Instant::now();
+        let result = match retry.run(|| std::fs::metadata(&path)) {
+            // Special files are never opened, as opening a FIFO may block.
+            Ok(metadata) if is_special(metadata.file_type()) => Ok(Self {
+                metadata: Some(metadata),
+                special: true,
+                ..Self::not_found(path)
+            }),
+
+            Ok(metadata) => Ok(Self {
+                exists: true,
+                metadata: Some(metadata),
+                ..Self::not_found(path)
             }),
 
             Err(e) => match e.kind() {
-                ErrorKind::NotFound => Ok(Self::not_found(path)),
+                ErrorKind::NotFound => Ok(Self {
+                    broken_link: std::fs::symlink_metadata(&path)
+                        .is_ok_and(|m| m.is_symlink()),
+                    ..Self::not_found(path)
+                }),
                 _ => Err(e),
             },
+        };
+        let stat_time = start.elapsed();
+
+        #[cfg(feature = "tracing")]
+        match &result {
+            Ok(file_cmp) => tracing::trace!(
+                found = file_cmp.is_found(),
+                elapsed = ?stat_time,
+                "read metadata"),
+            Err(e) => tracing::trace!(error = %e, "failed to read metadata"),
+        }
+        if let (Some(timings), Ok(file_cmp)) = (timings, &result) {
+            timings.record(&file_cmp.path, |t| t.stat += stat_time);
         }
+        result
     }
-}
 
+    /// Opens a file comparer for the given path in the given [`FileSystem`],
+    /// recording the time spent reading its metadata in the given
+    /// [`TimingRecorder`] and retrying transient errors according to the
+    /// given [`RetryPolicy`]. Symbolic links are always followed.
+    ///
+    /// [`FileSystem`]: crate::FileSystem
+    /// [`RetryPolicy`]: RetryPolicy
+    /// [`TimingRecorder`]: TimingRecorder
+    fn open_fs(
+        path: PathBuf,
+        fs: &Arc<dyn FileSystem>,
+        retry: RetryPolicy,
+        timings: Option<&TimingRecorder>)
+        -> Result<Self, std::io::Error>
+    {
+        let start = Instant::now();
+        let metadata = retry.run(|| fs.metadata(&path))?;
+        let stat_time = start.elapsed();
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            path = %path.display(),
+            found = metadata.is_some(),
+            elapsed = ?stat_time,
+            "read file system metadata");
+        if let Some(timings) = timings {
+            timings.record(&path, |t| t.stat += stat_time);
+        }
+        Ok(Self {
+            special: metadata.is_some_and(|m| m.kind == EntryKind::Other),
+            fs: Some(Arc::clone(fs)),
+            fs_metadata: metadata,
+            ..Self::not_found(path)
+        })
+    }
 
-impl FileCmp {
     /// Returns a file comparer which behaves like a non-existent file.
     #[must_use]
     pub fn not_found(path: PathBuf) -> Self {
         Self {
             path,
-            file: None,
+            exists: false,
             metadata: None,
+            id: OnceLock::new(),
+            broken_link: false,
+            special: false,
+            digest: OnceLock::new(),
+            #[cfg(feature = "archive")]
+            member: None,
+            #[cfg(feature = "http")]
+            remote: None,
+            #[cfg(feature = "git")]
+            blob: None,
+            fs: None,
+            fs_metadata: None,
+        }
+    }
+
+    /// Returns the path of the file.
+    #[must_use]
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns `true` if the file has been found.
+    #[must_use]
+    pub fn is_found(&self) -> bool {
+        self.exists
+            || self.is_symlink()
+            || self.special
+            || self.is_member()
+            || self.is_remote()
+            || self.is_blob()
+            || self.fs_metadata.is_some()
+    }
+
+    /// Returns `true` if the file is a member of an archive which has been
+    /// found.
+    #[cfg(feature = "archive")]
+    fn is_member(&self) -> bool {
+        self.member.is_some()
+    }
+
+    /// Returns `true` if the file is a member of an archive which has been
+    /// found.
+    #[cfg(not(feature = "archive"))]
+    #[allow(clippy::unused_self)]
+    fn is_member(&self) -> bool {
+        false
+    }
+
+    /// Returns `true` if the file is a remote file which has been found.
+    #[cfg(feature = "http")]
+    fn is_remote(&self) -> bool {
+        self.remote.is_some()
+    }
+
+    /// Returns `true` if the file is a remote file which has been found.
+    #[cfg(not(feature = "http"))]
+    #[allow(clippy::unused_self)]
+    fn is_remote(&self) -> bool {
+        false
+    }
+
+    /// Returns `true` if the file is a file in a git repository which has been
+    /// found.
+    #[cfg(feature = "git")]
+    fn is_blob(&self) -> bool {
+        self.blob.is_some()
+    }
+
+    /// Returns `true` if the file is a file in a git repository which has been
+    /// found.
+    #[cfg(not(feature = "git"))]
+    #[allow(clippy::unused_self)]
+    fn is_blob(&self) -> bool {
+        false
+    }
+
+    /// Opens the file if it has been found, recording the time spent opening
+    /// it in the given [`TimingRecorder`]. The handle is owned by the caller,
+    /// so the file is closed once it is dropped. Transient errors are retried
+    /// according to the given [`RetryPolicy`].
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the file can't be opened.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    /// [`RetryPolicy`]: RetryPolicy
+    /// [`TimingRecorder`]: TimingRecorder
+    fn open_file(&self, retry: RetryPolicy, timings: Option<&TimingRecorder>)
+        -> Result<Option<File>, std::io::Error>
+    {
+        if !self.exists { return Ok(None); }
+
+        let start = Instant::now();
+        let file = retry.run(|| File::options().read(true).open(&self.path))?;
+        let open_time = start.elapsed();
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            path = %self.path.display(),
+            elapsed = ?open_time,
+            "opened file");
+        if let Some(timings) = timings {
+            timings.record(&self.path, |t| t.open += open_time);
+        }
+        Ok(Some(file))
+    }
+
+    /// Returns `true` if the file is an unfollowed symbolic link.
+    fn is_symlink(&self) -> bool {
+        self.metadata.as_ref().is_some_and(Metadata::is_symlink)
+    }
+
+    /// Returns `true` if either file is an unfollowed symbolic link, and the
+    /// files are not links to the same path.
+    fn symlinks_differ(&self, other: &Self) -> bool {
+        match (self.is_symlink(), other.is_symlink()) {
+            (false, false) => false,
+            (true, true) => std::fs::read_link(&self.path).ok()
+                .is_none_or(|a| std::fs::read_link(&other.path).ok()
+                    .is_none_or(|b| a != b)),
+            _ => true,
+        }
+    }
+
+    /// Returns `true` if the given `FileCmp` refers to the same underlying
+    /// file, e.g., through a hardlink.
+    #[must_use]
+    pub fn is_same_file(&self, other: &Self) -> bool {
+        // Files of different sizes can't be the same file, so their identity
+        // is not needed.
+        if self.size() != other.size() { return false; }
+
+        match (self.id(), other.id()) {
+            (Some(a), Some(b)) => a == b,
+            _                  => false,
+        }
+    }
+
+    /// Returns the platform identity of the wrapped file, if it has been
+    /// found. The identity is determined once and cached, and on platforms
+    /// other than unix this requires opening the file.
+    fn id(&self) -> Option<FileId> {
+        *self.id.get_or_init(|| {
+            if !self.exists { return None; }
+            #[cfg(unix)]
+            return self.metadata.as_ref().map(FileId::from_metadata);
+            #[cfg(not(unix))]
+            return FileId::from_path(&self.path).ok();
+        })
+    }
+
+    /// Returns `true` if the wrapped files have the same permissions. On unix
+    /// platforms, the files must also have the same owning user and group.
+    #[must_use]
+    #[cfg(unix)]
+    pub fn same_permissions(&self, other: &Self) -> bool {
+        use std::os::unix::fs::MetadataExt as _;
+
+        match (&self.metadata, &other.metadata) {
+            (Some(a), Some(b)) => a.mode() & 0o7777 == b.mode() & 0o7777
+                && a.uid() == b.uid()
+                && a.gid() == b.gid(),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the wrapped files have the same permissions. On unix
+    /// platforms, the files must also have the same owning user and group.
+    #[must_use]
+    #[cfg(not(unix))]
+    pub fn same_permissions(&self, other: &Self) -> bool {
+        match (&self.metadata, &other.metadata) {
+            (Some(a), Some(b)) => a.permissions() == b.permissions(),
+            _ => false,
+        }
+    }
+
+    /// Returns the modification time of the wrapped file, if it can be
+    /// determined. This is equivalent to a call to [`Metadata::modified`], or
+    /// the recorded modification time of an archive member, and is
+    /// unavailable on platforms and filesystems which don't record it.
+    ///
+    /// [`Metadata::modified`]: std::fs::Metadata::modified
+    #[must_use]
+    pub(crate) fn modified(&self) -> Option<SystemTime> {
+        #[cfg(feature = "archive")]
+        if let Some(member) = &self.member { return member.modified; }
+        #[cfg(feature = "http")]
+        if let Some(remote) = &self.remote { return remote.modified; }
+        #[cfg(feature = "git")]
+        if let Some(blob) = &self.blob { return blob.modified; }
+        if let Some(metadata) = &self.fs_metadata { return metadata.modified; }
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.modified().ok())
+    }
+
+    /// Returns the last access time of the wrapped file, if it can be
+    /// determined. This is equivalent to a call to [`Metadata::accessed`], and
+    /// is unavailable on platforms and filesystems which don't record it.
+    ///
+    /// [`Metadata::accessed`]: std::fs::Metadata::accessed
+    #[must_use]
+    pub(crate) fn accessed(&self) -> Option<SystemTime> {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.accessed().ok())
+    }
+
+    /// Returns the creation time of the wrapped file, if it can be determined.
+    /// This is equivalent to a call to [`Metadata::created`], and is
+    /// unavailable on platforms and filesystems which don't record it.
+    ///
+    /// [`Metadata::created`]: std::fs::Metadata::created
+    #[must_use]
+    pub(crate) fn created(&self) -> Option<SystemTime> {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.created().ok())
+    }
+
+    /// Returns the status change time of the wrapped file, if it can be
+    /// determined. Status change times are only available on unix platforms.
+    #[must_use]
+    #[cfg(unix)]
+    pub(crate) fn changed(&self) -> Option<SystemTime> {
+        use std::os::unix::fs::MetadataExt as _;
+
+        self.metadata
+            .as_ref()
+            .and_then(|m| {
+                let secs = u64::try_from(m.ctime()).ok()?;
+                let nanos = u32::try_from(m.ctime_nsec()).ok()?;
+                SystemTime::UNIX_EPOCH
+                    .checked_add(Duration::new(secs, nanos))
+            })
+    }
+
+    /// Returns the status change time of the wrapped file, if it can be
+    /// determined. Status change times are only available on unix platforms.
+    #[must_use]
+    #[cfg(not(unix))]
+    pub(crate) fn changed(&self) -> Option<SystemTime> {
+        None
+    }
+
+    /// Returns the size of the wrapped file in bytes, if it can be determined.
+    #[must_use]
+    pub(crate) fn size(&self) -> Option<u64> {
+        #[cfg(feature = "archive")]
+        if let Some(member) = &self.member { return Some(member.len); }
+        #[cfg(feature = "http")]
+        if let Some(remote) = &self.remote { return remote.len; }
+        #[cfg(feature = "git")]
+        if let Some(blob) = &self.blob { return Some(blob.len); }
+        if let Some(metadata) = &self.fs_metadata { return Some(metadata.len); }
+        self.metadata
+            .as_ref()
+            .map(Metadata::len)
+    }
+
+    /// Returns `true` if the given [`CompareKey`] can be determined for the
+    /// wrapped file.
+    ///
+    /// [`CompareKey`]: CompareKey
+    fn key_available(&self, key: CompareKey) -> bool {
+        match key {
+            CompareKey::Modified => self.modified().is_some(),
+            CompareKey::Changed  => self.changed().is_some(),
+            CompareKey::Accessed => self.accessed().is_some(),
+            CompareKey::Created  => self.created().is_some(),
+            CompareKey::Size     => self.size().is_some(),
+            CompareKey::Name | CompareKey::Path | CompareKey::Digest => true,
+        }
+    }
+
+    /// Returns an ordering between the given `FileCmp`s based on the given
+    /// [`CompareKey`] alone.
+    ///
+    /// ### Parameters
+    /// + `other`: The other `FileCmp` to compare to.
+    /// + `key`: The [`CompareKey`] used to order the files.
+    /// + `comparator`: The [`Comparator`] providing the missing file behavior
+    ///   and modification time tolerance.
+    #[must_use]
+    fn key_cmp(&self, other: &Self, key: CompareKey, comparator: &Comparator)
+        -> Option<Ordering>
+    {
+        let promote = (
+            comparator.promote_newest(self),
+            comparator.promote_newest(other));
+
+        match key {
+            CompareKey::Modified => match (self.modified(), other.modified()) {
+                (Some(a), Some(b)) if time_difference(a, b)
+                    <= comparator.mtime_tolerance => Some(Ordering::Equal),
+                (a, b) => cmp_available(a, b, promote),
+            },
+            CompareKey::Changed => cmp_available(
+                self.changed(),
+                other.changed(),
+                promote),
+            CompareKey::Accessed => cmp_available(
+                self.accessed(),
+                other.accessed(),
+                promote),
+            CompareKey::Created => cmp_available(
+                self.created(),
+                other.created(),
+                promote),
+            CompareKey::Size => cmp_available(
+                self.size(),
+                other.size(),
+                promote),
+            CompareKey::Name => Some(self.path.file_name()
+                .cmp(&other.path.file_name())),
+            CompareKey::Path => Some(self.path.cmp(&other.path)),
+            CompareKey::Digest => cmp_available(
+                self.digest(),
+                other.digest(),
+                promote),
+        }
+    }
+
+    /// Returns the SHA-256 digest of the wrapped file's content, if it can be
+    /// read. The digest is computed once and cached.
+    fn digest(&self) -> Option<[u8; 32]> {
+        *self.digest.get_or_init(|| {
+            if !self.is_found() || self.special || self.is_symlink() {
+                return None;
+            }
+            let mut content = with_file_system(self.fs.as_ref(), || {
+                    open_content(&self.path)
+                })
+                .ok()??;
+            let mut hasher = Sha256::new();
+            let _ = std::io::copy(&mut content, &mut hasher).ok()?;
+            Some(hasher.finalize().into())
+        })
+    }
+
+    /// Returns true if the content of the given `FileCmp`s differs according
+    /// to the given [`Comparator`]'s `DiffOp`, along with the number of bytes
+    /// read from each file. Files which have been found are opened for the
+    /// diff and closed once it is done.
+    ///
+    /// [`Comparator`]: Comparator
+    fn diff_open(&self, other: &Self, comparator: &Comparator)
+        -> Result<(bool, u64), std::io::Error>
+    {
+        let timings = comparator.timings.as_deref();
+        let retry = comparator.retry;
+        let a = self.open_file(retry, timings)?;
+        let b = other.open_file(retry, timings)?;
+        match (&a, &b) {
+            (Some(a), Some(b)) => comparator.diff_op
+                .diff_open_counted((&self.path, a), (&other.path, b)),
+            _ => comparator.diff_op
+                .diff_counted(self.path.as_path(), other.path.as_path()),
+        }
+    }
+
+    /// Returns an ordering between the given `FileCmp`s based on the given
+    /// [`Comparator`], if such an ordering exists.
+    ///
+    /// If the `Comparator`'s `DiffOp` finds that the files do not differ, they
+    /// will compare equal regardless of their keys, provided they have the same
+    /// permissions if the `Comparator` requires it. Files with equal keys are
+    /// further ordered by the `Comparator`'s [`TieBreak`] policy.
+    ///
+    /// ### Parameters
+    /// + `other`: The other `FileCmp` to compare to.
+    /// + `comparator`: The [`Comparator`] used to order the files.
+    #[must_use]
+    pub fn partial_cmp(&self, other: &Self, comparator: &Comparator)
+        -> CompareOutcome
+    {
+        let content = self.content_match(other, comparator)
+            .unwrap_or(ContentMatch::FAILED);
+        self.decide(other, comparator, content).outcome
+    }
+
+    /// Returns an ordering between the given `FileCmp`s based on the given
+    /// [`Comparator`], if such an ordering exists. This is the same as
+    /// [`FileCmp::partial_cmp`], except that read limit and cancellation
+    /// errors are returned instead of treating the files as different.
+    ///
+    /// ### Parameters
+    /// + `other`: The other `FileCmp` to compare to.
+    /// + `comparator`: The [`Comparator`] used to order the files.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] wrapping a [`ReadLimitExceeded`] error if
+    /// the files are the same up to the read limit of the `Comparator`'s
+    /// `DiffOp` and it uses [`ReadLimitBehavior::Error`], or wrapping a
+    /// [`Cancelled`] error if the `Comparator`'s [`CancelToken`] is cancelled
+    /// while comparing content.
+    ///
+    /// [`Cancelled`]: crate::Cancelled
+    /// [`CancelToken`]: crate::CancelToken
+    /// [`FileCmp::partial_cmp`]: FileCmp::partial_cmp
+    /// [`std::io::Error`]: std::io::Error
+    /// [`ReadLimitExceeded`]: crate::ReadLimitExceeded
+    /// [`ReadLimitBehavior::Error`]: crate::ReadLimitBehavior::Error
+    pub fn try_partial_cmp(&self, other: &Self, comparator: &Comparator)
+        -> Result<CompareOutcome, std::io::Error>
+    {
+        self.try_decide(other, comparator).map(|decision| decision.outcome)
+    }
+
+    /// Returns the [`Decision`] ordering the given `FileCmp`s based on the
+    /// given [`Comparator`], recording what determined the ordering. The
+    /// ordering is the same as that of [`FileCmp::try_partial_cmp`].
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] wrapping a [`ReadLimitExceeded`] error if
+    /// the files are the same up to the read limit of the `Comparator`'s
+    /// `DiffOp` and it uses [`ReadLimitBehavior::Error`], or wrapping a
+    /// [`Cancelled`] error if the `Comparator`'s [`CancelToken`] is cancelled
+    /// while comparing content.
+    ///
+    /// [`Cancelled`]: crate::Cancelled
+    /// [`CancelToken`]: crate::CancelToken
+    /// [`Decision`]: crate::Decision
+    /// [`Comparator`]: Comparator
+    /// [`FileCmp::try_partial_cmp`]: FileCmp::try_partial_cmp
+    /// [`std::io::Error`]: std::io::Error
+    /// [`ReadLimitExceeded`]: crate::ReadLimitExceeded
+    /// [`ReadLimitBehavior::Error`]: crate::ReadLimitBehavior::Error
+    pub(crate) fn try_decide(&self, other: &Self, comparator: &Comparator)
+        -> Result<Decision, std::io::Error>
+    {
+        let content = match self.content_match(other, comparator) {
+            Ok(content) => content,
+            Err(e) if ReadLimitExceeded::is(&e) || Cancelled::is(&e) => {
+                return Err(e);
+            },
+            Err(_) => ContentMatch::FAILED,
+        };
+        Ok(self.decide(other, comparator, content))
+    }
+
+    /// Returns the [`Decision`] ordering the given `FileCmp`s based on the
+    /// given [`Comparator`] and the result of comparing their content.
+    ///
+    /// [`Decision`]: crate::Decision
+    /// [`Comparator`]: Comparator
+    fn decide(
+        &self,
+        other: &Self,
+        comparator: &Comparator,
+        content: ContentMatch)
+        -> Decision
+    {
+        let (outcome, basis) = if content.same_file {
+            (CompareOutcome::Ordered(Ordering::Equal), DecisionBasis::SameFile)
+        } else if content.same {
+            (CompareOutcome::Ordered(Ordering::Equal), DecisionBasis::Content)
+        } else {
+            self.ordered(other, comparator)
+        };
+        Decision { outcome, basis, content_compared: content.compared }
+    }
+
+    /// Compares the content of the given `FileCmp`s according to the given
+    /// [`Comparator`], including their permissions if the `Comparator`
+    /// requires it.
+    ///
+    /// [`Comparator`]: Comparator
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "diff",
+        level = "debug",
+        skip_all,
+        fields(a = %self.path.display(), b = %other.path.display())))]
+    fn content_match(&self, other: &Self, comparator: &Comparator)
+        -> Result<ContentMatch, std::io::Error>
+    {
+        if self.is_same_file(other) {
+            return Ok(ContentMatch {
+                same_file: true,
+                same: true,
+                compared: false,
+            });
+        }
+
+        // The same remote file with an unchanged strong ETag has the same
+        // content.
+        #[cfg(feature = "http")]
+        if let (Some(a), Some(b)) = (&self.remote, &other.remote) {
+            if a.same_etag(b) {
+                return Ok(ContentMatch {
+                    same_file: false,
+                    same: true,
+                    compared: false,
+                });
+            }
+        }
+
+        // Blobs with the same object ID have the same content.
+        #[cfg(feature = "git")]
+        if let (Some(a), Some(b)) = (&self.blob, &other.blob) {
+            if a.same_id(b) {
+                return Ok(ContentMatch {
+                    same_file: false,
+                    same: true,
+                    compared: false,
+                });
+            }
+        }
+
+        let start = Instant::now();
+        let spawned = subprocesses_spawned();
+        let cancel = comparator.cancel.as_ref();
+        let fs = comparator.fs.as_ref();
+        let run_diff = || match comparator.diff_op {
+            DiffOp::None => (false, comparator.diff_op
+                .diff_counted(self.path.as_path(), other.path.as_path())),
+            // Missing files have no content to compare.
+            _ if !self.is_found() || !other.is_found() => {
+                (false, Ok((true, 0)))
+            },
+            // Special files are compared by metadata only.
+            _ if self.special || other.special => (false, Ok((true, 0))),
+            _ if self.symlinks_differ(other) => (false, Ok((true, 0))),
+            // Links to the same path have the same content.
+            _ if self.is_symlink() => (false, Ok((false, 0))),
+            _ if comparator.diff_op.reads_open_files() => (true, comparator
+                .retry
+                .run(|| self.diff_open(other, comparator))),
+            _ => (true, comparator.retry.run(|| comparator.diff_op
+                .diff_counted(self.path.as_path(), other.path.as_path()))),
+        };
+        let (compared, diff) = with_file_system(fs, || {
+            with_cancel(cancel, run_diff)
+        });
+        let diff_time = start.elapsed();
+        #[cfg(feature = "tracing")]
+        match &diff {
+            Ok((differ, read)) => tracing::debug!(
+                compared,
+                differ,
+                bytes_read = read,
+                elapsed = ?diff_time,
+                "compared content"),
+            Err(e) => tracing::debug!(error = %e, "failed to compare content"),
+        }
+        let read = diff.as_ref().map_or(0, |(_, read)| *read);
+        if let (Some(progress), true) = (&comparator.progress, compared) {
+            progress.bytes_compared(read);
+        }
+        if let (Some(stats), true) = (&comparator.stats, compared) {
+            stats.record_diff(read, subprocesses_spawned() - spawned);
+        }
+        if let Some(timings) = &comparator.timings {
+            for path in [&self.path, &other.path] {
+                timings.record(path, |t| {
+                    t.diff += diff_time;
+                    t.bytes_read += read;
+                });
+            }
+        }
+        let (differ, _) = diff?;
+        Ok(ContentMatch {
+            same_file: false,
+            same: !differ && (!comparator.compare_permissions
+                || self.same_permissions(other)),
+            compared,
+        })
+    }
+
+    /// Returns an ordering between the given `FileCmp`s based on the keys of
+    /// the given [`Comparator`], assuming their content differs, along with
+    /// what determined the ordering.
+    ///
+    /// [`Comparator`]: Comparator
+    fn ordered(&self, other: &Self, comparator: &Comparator)
+        -> (CompareOutcome, DecisionBasis)
+    {
+        use Ordering::*;
+
+        let promote_self = comparator.promote_newest(self);
+        let promote_other = comparator.promote_newest(other);
+
+        let file_cmp = match (self.is_found(), other.is_found()) {
+            (true,  true)  => Equal,
+            (false, true)  => if promote_self { Greater } else { Less },
+            (true,  false) => if promote_other { Less } else { Greater },
+            _ => return (
+                CompareOutcome::Incomparable(Incomparable::BothMissing),
+                DecisionBasis::Missing),
+        };
+        // Found files without the key are ordered by the fallback key.
+        let unavailable = |f: &Self| {
+            f.is_found() && !f.key_available(comparator.key)
+        };
+        let key = match comparator.fallback_key {
+            Some(fallback) if unavailable(self) || unavailable(other) => {
+                fallback
+            },
+            _ => comparator.key,
+        };
+        let Some(key_cmp) = self.key_cmp(other, key, comparator)
+            else {
+                return (
+                    CompareOutcome::Incomparable(
+                        Incomparable::KeyUnavailable(key)),
+                    DecisionBasis::Key(key));
+            };
+        if file_cmp != Equal {
+            return (CompareOutcome::Ordered(file_cmp), DecisionBasis::Missing);
+        }
+        if key_cmp != Equal {
+            return (CompareOutcome::Ordered(key_cmp), DecisionBasis::Key(key));
+        }
+        let tie = comparator.tie_break
+            .keys()
+            .iter()
+            .map(|k| (*k, self.key_cmp(other, *k, comparator).unwrap_or(Equal)))
+            .find(|(_, o)| *o != Equal);
+
+        match tie {
+            Some((k, tie_cmp)) => (
+                CompareOutcome::Ordered(tie_cmp),
+                DecisionBasis::TieBreak(k)),
+            None => (CompareOutcome::Ordered(Equal), DecisionBasis::Key(key)),
+        }
+    }
+}
+
+
+/// The result of comparing the content of two files.
+#[derive(Debug, Clone, Copy)]
+struct ContentMatch {
+    /// Whether the files are the same file.
+    same_file: bool,
+    /// Whether the files have the same content.
+    same: bool,
+    /// Whether a content comparison was run.
+    compared: bool,
+}
+
+impl ContentMatch {
+    /// The result of a content comparison which failed. The files are
+    /// considered different.
+    const FAILED: Self = Self {
+        same_file: false,
+        same: false,
+        compared: true,
+    };
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// CompareOutcome
+////////////////////////////////////////////////////////////////////////////////
+/// The outcome of comparing two files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOutcome {
+    /// The files are ordered.
+    Ordered(Ordering),
+    /// The files can't be ordered, for the given reason.
+    Incomparable(Incomparable),
+}
+
+impl CompareOutcome {
+    /// Returns the ordering of the files, or `None` if they can't be ordered.
+    #[must_use]
+    pub const fn ordering(self) -> Option<Ordering> {
+        match self {
+            Self::Ordered(ordering) => Some(ordering),
+            Self::Incomparable(_)   => None,
+        }
+    }
+
+    /// Returns `true` if the files are ordered.
+    #[must_use]
+    pub const fn is_ordered(self) -> bool {
+        matches!(self, Self::Ordered(_))
+    }
+
+    /// Returns the outcome with any ordering reversed.
+    #[must_use]
+    pub const fn reverse(self) -> Self {
+        match self {
+            Self::Ordered(ordering) => Self::Ordered(ordering.reverse()),
+            Self::Incomparable(reason) => Self::Incomparable(reason),
+        }
+    }
+}
+
+impl From<Ordering> for CompareOutcome {
+    fn from(ordering: Ordering) -> Self {
+        Self::Ordered(ordering)
+    }
+}
+
+/// The reason two files can't be ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Incomparable {
+    /// Neither file exists.
+    BothMissing,
+    /// The [`CompareKey`] is unavailable for both files, e.g., because the
+    /// platform does not provide it.
+    ///
+    /// [`CompareKey`]: CompareKey
+    KeyUnavailable(CompareKey),
+    /// Both files were excluded by the [`Comparator`]'s policies, e.g., because
+    /// they are missing and ignored, unsettled, or unstable.
+    ///
+    /// [`Comparator`]: Comparator
+    Excluded,
+}
+
+impl std::fmt::Display for Incomparable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BothMissing => write!(f, "both files are missing"),
+            Self::KeyUnavailable(key) => write!(f,
+                "{key:?} is unavailable for both files"),
+            Self::Excluded => write!(f, "both files are excluded by policy"),
+        }
+    }
+}
+
+
+/// Returns the absolute difference between two `SystemTime`s.
+fn time_difference(a: SystemTime, b: SystemTime) -> Duration {
+    a.duration_since(b).unwrap_or_else(|e| e.duration())
+}
+
+/// Compares two optional values, ordering unavailable values according to
+/// `promote`, which indicates for each value whether it should be considered
+/// greater than other values when it is unavailable. Returns `None` if neither
+/// value is available.
+fn cmp_available<T>(a: Option<T>, b: Option<T>, promote: (bool, bool))
+    -> Option<Ordering>
+    where T: Ord
+{
+    use Ordering::*;
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.cmp(&b)),
+        (None,    Some(_)) => Some(if promote.0 { Greater } else { Less }),
+        (Some(_), None)    => Some(if promote.1 { Less } else { Greater }),
+        _                  => None,
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Comparator
+////////////////////////////////////////////////////////////////////////////////
+/// Options determining how files are compared.
+///
+/// A `Comparator` is `Send` and `Sync`, so a configured comparator may be
+/// shared between threads.
+#[derive(Debug, Clone)]
+pub struct Comparator {
+    /// The [`CompareKey`] used to order files.
+    pub key: CompareKey,
+    /// The [`CompareKey`] used to order files whose key is unavailable, such
+    /// as a modification time on filesystems which don't record one. If not
+    /// set, an unavailable modification, access, or creation time is an
+    /// error.
+    pub fallback_key: Option<CompareKey>,
+    /// The [`TieBreak`] policy used to order files with equal keys.
+    pub tie_break: TieBreak,
+    /// The [`DiffOp`] used to compare file contents. Files which do not differ
+    /// compare equal regardless of their keys.
+    pub diff_op: DiffOp,
+    /// Whether files must also have the same permissions, and on unix
+    /// platforms the same owning user and group, to be considered equal by
+    /// the [`DiffOp`].
+    pub compare_permissions: bool,
+    /// The [`MissingFileBehavior`] indicating how to handle missing files.
+    pub missing: MissingFileBehavior,
+    /// Overrides of the [`MissingFileBehavior`] for specific paths. The first
+    /// matching override is used.
+    pub missing_overrides: Vec<MissingOverride>,
+    /// The largest difference between modification times for which the times
+    /// are considered equal. This accounts for filesystems with coarse
+    /// timestamps, such as FAT.
+    pub mtime_tolerance: Duration,
+    /// The minimum time since a file's last modification for it to be
+    /// considered. More recently modified files are excluded as if they were
+    /// ignored, as they may still be being written.
+    pub settle_time: Duration,
+    /// The [`Clock`] used to determine the current time.
+    pub clock: Arc<dyn Clock>,
+    /// If set, each file's size and modification time are checked twice,
+    /// separated by the given delay, and files which change between the checks
+    /// are excluded as if they were ignored.
+    pub stability_delay: Option<Duration>,
+    /// The [`SymlinkPolicy`] determining whether symbolic links are followed.
+    pub symlinks: SymlinkPolicy,
+    /// The [`BrokenSymlinkBehavior`] indicating how to handle followed
+    /// symbolic links whose targets are missing.
+    pub broken_symlinks: BrokenSymlinkBehavior,
+    /// The [`SpecialFilePolicy`] indicating how to handle FIFOs, sockets, and
+    /// device nodes.
+    pub special_files: SpecialFilePolicy,
+    /// The [`UnreadableFilePolicy`] indicating how to handle files which
+    /// can't be read due to insufficient permissions.
+    pub unreadable: UnreadableFilePolicy,
+    /// If set, only paths accepted by the given [`PathFilter`] are compared.
+    /// Other paths are excluded as if they were ignored.
+    pub path_filter: Option<Arc<dyn PathFilter>>,
+    /// If set, the time spent opening, reading, and diffing each file is
+    /// recorded in the given [`TimingRecorder`].
+    pub timings: Option<Arc<TimingRecorder>>,
+    /// If set, the number of files stat'ed, content diffs, bytes read, and
+    /// subprocesses spawned are counted in the given [`StatsRecorder`].
+    pub stats: Option<Arc<StatsRecorder>>,
+    /// If set, the files processed and bytes compared are reported to the
+    /// given [`Progress`].
+    pub progress: Option<Arc<dyn Progress>>,
+    /// If set, comparisons check the given [`CancelToken`] before reading
+    /// each file's metadata, before comparing each file, and while comparing
+    /// content, and fail with [`FcmpError::Cancelled`] once it is cancelled.
+    ///
+    /// [`FcmpError::Cancelled`]: crate::FcmpError::Cancelled
+    pub cancel: Option<CancelToken>,
+    /// The [`RetryPolicy`] applied to reading metadata, opening files, and
+    /// comparing content when they fail with transient IO errors.
+    pub retry: RetryPolicy,
+    /// If set, the metadata and content of files are read from the given
+    /// [`FileSystem`] instead of the local file system.
+    ///
+    /// [`FileSystem`]: crate::FileSystem
+    pub fs: Option<Arc<dyn FileSystem>>,
+    /// Whether paths are canonicalized before checking whether they are the
+    /// same path, so that aliases such as `./foo` and `foo`, or a followed
+    /// symbolic link and its target, compare equal without being opened.
+    pub canonicalize_paths: bool,
+}
+
+/// The smallest number of paths for which [`Comparator::open_all`] reads
+/// metadata on multiple threads.
+///
+/// [`Comparator::open_all`]: Comparator::open_all
+const PARALLEL_OPEN_PATHS: usize = 64;
+
+impl Default for Comparator {
+    fn default() -> Self {
+        Self {
+            key: CompareKey::default(),
+            fallback_key: None,
+            tie_break: TieBreak::default(),
+            diff_op: DiffOp::default(),
+            compare_permissions: false,
+            missing: MissingFileBehavior::default(),
+            missing_overrides: Vec::new(),
+            mtime_tolerance: Duration::ZERO,
+            settle_time: Duration::ZERO,
+            clock: Arc::new(SystemClock),
+            stability_delay: None,
+            symlinks: SymlinkPolicy::default(),
+            broken_symlinks: BrokenSymlinkBehavior::default(),
+            special_files: SpecialFilePolicy::default(),
+            unreadable: UnreadableFilePolicy::default(),
+            path_filter: None,
+            timings: None,
+            stats: None,
+            progress: None,
+            cancel: None,
+            retry: RetryPolicy::none(),
+            fs: None,
+            canonicalize_paths: true,
+        }
+    }
+}
+
+impl Comparator {
+    /// Returns, for each of the given paths, whether the file's size or
+    /// modification time changes over the stability delay. All paths are
+    /// checked over the same delay.
+    pub(crate) fn find_unstable(&self, paths: &[&Path]) -> Vec<bool> {
+        let Some(delay) = self.stability_delay else {
+            return vec![false; paths.len()];
+        };
+
+        let snapshot = || paths
+            .iter()
+            .map(|p| self.fs.as_ref().map_or_else(
+                || std::fs::metadata(p)
+                    .ok()
+                    .map(|m| (m.len(), m.modified().ok())),
+                |fs| fs.metadata(p)
+                    .ok()
+                    .flatten()
+                    .map(|m| (m.len, m.modified))))
+            .collect::<Vec<_>>();
+
+        let first = snapshot();
+        std::thread::sleep(delay);
+        let second = snapshot();
+
+        first.iter()
+            .zip(second.iter())
+            .map(|(a, b)| a != b)
+            .collect()
+    }
+
+    /// Returns `true` if the given paths have the same [`canonical_path`], if
+    /// the `Comparator` canonicalizes paths. Paths read from a [`FileSystem`],
+    /// archive members, remote files, and git blobs are not canonicalized.
+    ///
+    /// [`canonical_path`]: crate::canonical_path
+    /// [`FileSystem`]: crate::FileSystem
+    fn is_same_path(&self, a: &Path, b: &Path) -> bool {
+        if !self.canonicalize_paths
+            || self.fs.is_some()
+            || is_virtual(a)
+            || is_virtual(b)
+        {
+            return false;
+        }
+        let follow = self.symlinks == SymlinkPolicy::Follow;
+        canonical_path(a, follow)
+            .is_some_and(|a| canonical_path(b, follow) == Some(a))
+    }
+
+    /// Returns `true` if the given file was modified too recently to be
+    /// considered.
+    fn is_unsettled(&self, file_cmp: &FileCmp) -> bool {
+        if self.settle_time.is_zero() { return false; }
+
+        // Files modified in the future are not settled.
+        file_cmp.modified().is_some_and(|modified| self.clock.now()
+            .duration_since(modified)
+            .map_or(true, |age| age < self.settle_time))
+    }
+
+    /// Returns the [`MissingFileBehavior`] for the given path, taking any
+    /// matching [`MissingOverride`] into account.
+    #[must_use]
+    pub fn missing_for(&self, path: &Path) -> MissingFileBehavior {
+        self.missing_overrides
+            .iter()
+            .find(|o| o.pattern.matches_path(path))
+            .map_or(self.missing, |o| o.missing)
+    }
+
+    /// Returns the [`MissingFileBehavior`] for the given missing file, taking
+    /// the [`BrokenSymlinkBehavior`] into account if it is a broken symbolic
+    /// link.
+    fn missing_behavior(&self, file_cmp: &FileCmp) -> MissingFileBehavior {
+        if !file_cmp.broken_link {
+            return self.missing_for(&file_cmp.path);
+        }
+        match self.broken_symlinks {
+            BrokenSymlinkBehavior::Missing => self.missing_for(&file_cmp.path),
+            BrokenSymlinkBehavior::Oldest  => MissingFileBehavior::Oldest,
+            BrokenSymlinkBehavior::Newest  => MissingFileBehavior::Newest,
+            BrokenSymlinkBehavior::Error   => MissingFileBehavior::Error,
+        }
+    }
+
+    /// Returns `true` if the given file should be considered greater than
+    /// other files when it is missing.
+    fn promote_newest(&self, file_cmp: &FileCmp) -> bool {
+        matches!(self.missing_behavior(file_cmp), MissingFileBehavior::Newest)
+    }
+
+    /// Opens a [`FileCmp`] for the given path, applying the
+    /// [`MissingFileBehavior`], [`BrokenSymlinkBehavior`],
+    /// [`SpecialFilePolicy`], [`UnreadableFilePolicy`], [`PathFilter`], and
+    /// settle time. Returns `None` if the file should be ignored.
+    ///
+    /// ### Errors
+    ///
+    /// Returns an error if `MissingFileBehavior::Error` is used and the file is
+    /// missing, if `SpecialFilePolicy::Error` is used and the file is a
+    /// special file, if `UnreadableFilePolicy::Error` is used and the file is
+    /// unreadable, if the file's modification, access, or creation time is
+    /// used as the key and is unavailable without a fallback key, or if
+    /// reading the file's metadata results in an unexpected IO error.
+    fn open(&self, path: &Path) -> Result<Option<FileCmp>, FcmpError> {
+        if self.path_filter.as_ref().is_some_and(|f| !f.accepts(path)) {
+            return Ok(None);
+        }
+
+        let opened = self.fs.as_ref().map_or_else(
+                || FileCmp::open_timed(
+                    path.to_path_buf(),
+                    self.symlinks,
+                    self.retry,
+                    self.timings.as_deref()),
+                |fs| FileCmp::open_fs(
+                    path.to_path_buf(),
+                    fs,
+                    self.retry,
+                    self.timings.as_deref()))
+            .and_then(|file_cmp| {
+                if let Some(stats) = &self.stats { stats.record_stat(); }
+                self.check_readable(&file_cmp)?;
+                Ok(file_cmp)
+            });
+        let opened = match opened {
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                match self.unreadable {
+                    UnreadableFilePolicy::Skip => return Ok(None),
+                    UnreadableFilePolicy::Missing => {
+                        Ok(FileCmp::not_found(path.to_path_buf()))
+                    },
+                    UnreadableFilePolicy::Error => {
+                        return Err(FcmpError::Unreadable {
+                            path: path.to_path_buf(),
+                            source: e,
+                        });
+                    },
+                }
+            },
+            opened => opened,
+        };
+        match opened {
+            Ok(file_cmp) if !file_cmp.is_found() => match self
+                .missing_behavior(&file_cmp)
+            {
+                MissingFileBehavior::Error if file_cmp.broken_link => {
+                    Err(FcmpError::BrokenSymlink { path: file_cmp.path })
+                },
+                MissingFileBehavior::Error => {
+                    Err(FcmpError::Missing { path: file_cmp.path })
+                },
+
+                MissingFileBehavior::Ignore => Ok(None),
+                _ => Ok(Some(file_cmp)),
+            },
+            Ok(file_cmp) if file_cmp.special => match self.special_files {
+                SpecialFilePolicy::Skip => Ok(None),
+                SpecialFilePolicy::Error => {
+                    Err(FcmpError::SpecialFile { path: file_cmp.path })
+                },
+                SpecialFilePolicy::Metadata => Ok(Some(file_cmp)),
+            },
+            Ok(file_cmp) if self.is_unsettled(&file_cmp) => Ok(None),
+            Ok(file_cmp) => self.check_key_available(&file_cmp)
+                .map(|()| Some(file_cmp)),
+            Err(e) => Err(FcmpError::Io {
+                path: path.to_path_buf(),
+                source: e,
+            }),
+        }
+    }
+
+    /// Returns an error if the given file's modification, access, or creation
+    /// time is used as the key and is unavailable, and there is no fallback
+    /// key.
+    ///
+    /// This applies to files from a [`FileSystem`], archives, and other
+    /// sources as well as local files, using the local file's error as the
+    /// source if there is one.
+    ///
+    /// [`FileSystem`]: crate::FileSystem
+    fn check_key_available(&self, file_cmp: &FileCmp)
+        -> Result<(), FcmpError>
+    {
+        let is_time = matches!(self.key,
+            CompareKey::Modified | CompareKey::Accessed | CompareKey::Created);
+        if self.fallback_key.is_some()
+            || !is_time
+            || file_cmp.key_available(self.key)
+        {
+            return Ok(());
+        }
+
+        let source = file_cmp.metadata
+            .as_ref()
+            .and_then(|metadata| match self.key {
+                CompareKey::Modified => metadata.modified().err(),
+                CompareKey::Accessed => metadata.accessed().err(),
+                _                    => metadata.created().err(),
+            })
+            .unwrap_or_else(|| std::io::Error::new(
+                ErrorKind::Unsupported,
+                "the time is not recorded for this file"));
+        Err(FcmpError::KeyUnavailable {
+            path: file_cmp.path.clone(),
+            key: self.key,
+            source,
+        })
+    }
+
+    /// Returns an error if the content of the given file may be compared and
+    /// the file can't be opened due to insufficient permissions. The file
+    /// is closed again once it has been checked.
+    fn check_readable(&self, file_cmp: &FileCmp)
+        -> Result<(), std::io::Error>
+    {
+        if matches!(self.diff_op, DiffOp::None) { return Ok(()); }
+        match file_cmp.open_file(self.retry, self.timings.as_deref()) {
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error if the [`CancelToken`] has been cancelled.
+    ///
+    /// [`CancelToken`]: crate::CancelToken
+    pub(crate) fn check_cancelled(&self) -> Result<(), FcmpError> {
+        match &self.cancel {
+            Some(cancel) if cancel.is_cancelled() => Err(FcmpError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    /// Opens a [`FileCmp`] for each of the given paths, as [`open`] does,
+    /// skipping the paths marked as unstable. Returns `None` for each file
+    /// which should be ignored or is unstable.
+    ///
+    /// The metadata of large numbers of paths is read on multiple threads,
+    /// so that the files can then be compared without further syscalls. Each
+    /// path is reported to the `Comparator`'s [`Progress`] once processed.
+    ///
+    /// [`Progress`]: crate::Progress
+    ///
+    /// ### Errors
+    ///
+    /// Returns the error of the first path which [`open`] fails for.
+    ///
+    /// [`open`]: Comparator::open
+    pub(crate) fn open_all(&self, paths: &[&Path], unstable: &[bool])
+        -> Result<Vec<Option<FileCmp>>, FcmpError>
+    {
+        if let Some(progress) = &self.progress {
+            progress.start(paths.len() as u64);
+        }
+        let open = |(path, unstable): (&&Path, &bool)| {
+            self.check_cancelled()?;
+            let opened = if *unstable { Ok(None) } else { self.open(path) };
+            if let Some(progress) = &self.progress {
+                progress.file_processed();
+            }
+            opened
+        };
+
+        let threads = available_threads();
+        if threads == 1 || paths.len() < PARALLEL_OPEN_PATHS {
+            return paths.iter().zip(unstable).map(open).collect();
+        }
+
+        let chunk_len = paths.len().div_ceil(threads);
+        let open = &open;
+        std::thread::scope(|scope| {
+            // Every thread is spawned before any is joined.
+            #[allow(clippy::needless_collect)]
+            let handles: Vec<_> = paths.chunks(chunk_len)
+                .zip(unstable.chunks(chunk_len))
+                .map(|(paths, unstable)| scope.spawn(move || paths
+                    .iter()
+                    .zip(unstable)
+                    .map(open)
+                    .collect::<Vec<_>>()))
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("join open thread"))
+                .collect()
+        })
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// TieBreak
+////////////////////////////////////////////////////////////////////////////////
+/// A policy for ordering files whose [`CompareKey`]s are equal.
+///
+/// The policy is a chain of additional `CompareKey`s which are tried in order
+/// until one of them distinguishes the files. Using a key which always
+/// distinguishes files, such as `CompareKey::Path`, makes results independent
+/// of the order of the inputs.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TieBreak {
+    /// The keys to try, in order of priority.
+    keys: Vec<CompareKey>,
+}
+
+impl TieBreak {
+    /// Returns a `TieBreak` policy which leaves ties unbroken.
+    #[must_use]
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Returns a `TieBreak` policy using the given keys in order of priority.
+    #[must_use]
+    pub fn new<K>(keys: K) -> Self
+        where K: IntoIterator<Item=CompareKey>
+    {
+        Self {
+            keys: keys.into_iter().collect(),
+        }
+    }
+
+    /// Returns the keys used to break ties, in order of priority.
+    #[must_use]
+    pub fn keys(&self) -> &[CompareKey] {
+        &self.keys[..]
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// CompareKey
+////////////////////////////////////////////////////////////////////////////////
+/// The file property used to order files.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(clap::ArgEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum CompareKey {
+    /// Order files by their modification times.
+    #[default]
+    Modified,
+    /// Order files by their status change times. Only available on unix
+    /// platforms.
+    Changed,
+    /// Order files by their last access times.
+    Accessed,
+    /// Order files by their creation times. Only available on platforms and
+    /// filesystems which record them.
+    Created,
+    /// Order files by their sizes.
+    Size,
+    /// Order files by their file names.
+    Name,
+    /// Order files by their full paths.
+    Path,
+    /// Order files by the SHA-256 digests of their contents. This gives an
+    /// arbitrary but reproducible order to files with different content.
+    Digest,
+}
+
+impl FromStr for CompareKey {
+    type Err = CompareKeyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("modified") {
+            Ok(Self::Modified)
+        } else if s.eq_ignore_ascii_case("changed") {
+            Ok(Self::Changed)
+        } else if s.eq_ignore_ascii_case("accessed") {
+            Ok(Self::Accessed)
+        } else if s.eq_ignore_ascii_case("created") {
+            Ok(Self::Created)
+        } else if s.eq_ignore_ascii_case("size") {
+            Ok(Self::Size)
+        } else if s.eq_ignore_ascii_case("name") {
+            Ok(Self::Name)
+        } else if s.eq_ignore_ascii_case("path") {
+            Ok(Self::Path)
+        } else if s.eq_ignore_ascii_case("digest") {
+            Ok(Self::Digest)
+        } else {
+            Err(CompareKeyParseError)
+        }
+    }
+}
+
+/// An error indicating a failure to parse a [`CompareKey`].
+///
+/// [`CompareKey`]: CompareKey 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompareKeyParseError;
+
+impl std::error::Error for CompareKeyParseError {}
+
+impl std::fmt::Display for CompareKeyParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse CompareKey")
+    }
+}
+
+
+
+////////////////////////////////////////////////////////////////////////////////
+// MissingFileBehavior
+////////////////////////////////////////////////////////////////////////////////
+/// Options for handling missing files.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(clap::ArgEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum MissingFileBehavior {
+    /// Treat missing files as older than all others.
+    #[default]
+    Oldest,
+    /// Treat missing files as newer than all others.
+    Newest,
+    /// Ignore the file if it is missing.
+    Ignore,
+    /// Return an error if the file is missing.
+    Error,
+}
+
+impl FromStr for MissingFileBehavior {
+    type Err = MissingFileBehaviorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("oldest") {
+            Ok(Self::Oldest)
+        } else if s.eq_ignore_ascii_case("newest") {
+            Ok(Self::Newest)
+        } else if s.eq_ignore_ascii_case("ignore") {
+            Ok(Self::Ignore)
+        } else if s.eq_ignore_ascii_case("error") {
+            Ok(Self::Error)
+        } else {
+            Err(MissingFileBehaviorParseError)
+        }
+    }
+}
+
+/// An error indicating a failure to parse a [`MissingFileBehavior`].
+///
+/// [`MissingFileBehavior`]: MissingFileBehavior 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingFileBehaviorParseError;
+
+impl std::error::Error for MissingFileBehaviorParseError {}
+
+impl std::fmt::Display for MissingFileBehaviorParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse MissingFileBehavior")
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// SymlinkPolicy
+////////////////////////////////////////////////////////////////////////////////
+/// Options for handling symbolic links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(clap::ArgEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum SymlinkPolicy {
+    /// Compare the files which symbolic links point to.
+    #[default]
+    Follow,
+    /// Compare symbolic links themselves, using their own metadata. Links
+    /// have the same content if they point to the same path, and differ from
+    /// all files which are not links.
+    NoFollow,
+}
+
+impl FromStr for SymlinkPolicy {
+    type Err = SymlinkPolicyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("follow") {
+            Ok(Self::Follow)
+        } else if s.eq_ignore_ascii_case("nofollow")
+            || s.eq_ignore_ascii_case("no-follow")
+        {
+            Ok(Self::NoFollow)
+        } else {
+            Err(SymlinkPolicyParseError)
+        }
+    }
+}
+
+/// An error indicating a failure to parse a [`SymlinkPolicy`].
+///
+/// [`SymlinkPolicy`]: SymlinkPolicy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SymlinkPolicyParseError;
+
+impl std::error::Error for SymlinkPolicyParseError {}
+
+impl std::fmt::Display for SymlinkPolicyParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse SymlinkPolicy")
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// BrokenSymlinkBehavior
+////////////////////////////////////////////////////////////////////////////////
+/// Options for handling followed symbolic links whose targets are missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(clap::ArgEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum BrokenSymlinkBehavior {
+    /// Treat broken links as missing files, using the
+    /// [`MissingFileBehavior`].
+    #[default]
+    Missing,
+    /// Treat broken links as older than all other files.
+    Oldest,
+    /// Treat broken links as newer than all other files.
+    Newest,
+    /// Return an error if a link is broken.
+    Error,
+}
+
+impl FromStr for BrokenSymlinkBehavior {
+    type Err = BrokenSymlinkBehaviorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("missing") {
+            Ok(Self::Missing)
+        } else if s.eq_ignore_ascii_case("oldest") {
+            Ok(Self::Oldest)
+        } else if s.eq_ignore_ascii_case("newest") {
+            Ok(Self::Newest)
+        } else if s.eq_ignore_ascii_case("error") {
+            Ok(Self::Error)
+        } else {
+            Err(BrokenSymlinkBehaviorParseError)
+        }
+    }
+}
+
+/// An error indicating a failure to parse a [`BrokenSymlinkBehavior`].
+///
+/// [`BrokenSymlinkBehavior`]: BrokenSymlinkBehavior
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrokenSymlinkBehaviorParseError;
+
+impl std::error::Error for BrokenSymlinkBehaviorParseError {}
+
+impl std::fmt::Display for BrokenSymlinkBehaviorParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse BrokenSymlinkBehavior")
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// SpecialFilePolicy
+////////////////////////////////////////////////////////////////////////////////
+/// Options for handling special files, such as FIFOs, sockets, and device
+/// nodes. Special files are never opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(clap::ArgEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum SpecialFilePolicy {
+    /// Compare special files by their metadata only. Their content is never
+    /// read, and is considered different from all other files.
+    #[default]
+    Metadata,
+    /// Ignore special files.
+    Skip,
+    /// Return an error if a file is a special file.
+    Error,
+}
+
+impl FromStr for SpecialFilePolicy {
+    type Err = SpecialFilePolicyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("metadata") {
+            Ok(Self::Metadata)
+        } else if s.eq_ignore_ascii_case("skip") {
+            Ok(Self::Skip)
+        } else if s.eq_ignore_ascii_case("error") {
+            Ok(Self::Error)
+        } else {
+            Err(SpecialFilePolicyParseError)
         }
     }
+}
 
-    /// Returns `true` if the file has been found.
-    #[must_use]
-    pub fn is_found(&self) -> bool {
-        self.file.is_some()
-    }
+/// An error indicating a failure to parse a [`SpecialFilePolicy`].
+///
+/// [`SpecialFilePolicy`]: SpecialFilePolicy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpecialFilePolicyParseError;
 
-    /// Returns the modification time of the wrapped file, if it can be
-    /// determined. This is equivalent to a call to [`Metadata::modified`].
-    ///
-    /// [`Metadata::modified`]: std::fs::Metadata::modified
-    #[must_use]
-    fn modified(&self) -> Option<SystemTime> {
-        self.metadata
-            .as_ref()
-            .map(|m| m.modified().expect("get file modified time"))
+impl std::error::Error for SpecialFilePolicyParseError {}
+
+impl std::fmt::Display for SpecialFilePolicyParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse SpecialFilePolicy")
     }
+}
 
-    /// Returns an ordering between the given `FileCmp`s based on their
-    /// modification times, if such an ordering exists.
+
+////////////////////////////////////////////////////////////////////////////////
+// UnreadableFilePolicy
+////////////////////////////////////////////////////////////////////////////////
+/// Options for handling files which can't be read due to insufficient
+/// permissions. A file is unreadable if its metadata can't be read, or if its
+/// content is compared and it can't be opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(clap::ArgEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum UnreadableFilePolicy {
+    /// Return an error if a file is unreadable.
+    #[default]
+    Error,
+    /// Ignore unreadable files.
+    Skip,
+    /// Treat unreadable files as missing, applying the
+    /// [`MissingFileBehavior`].
     ///
-    /// ### Parameters
-    /// + `other`: The other `FileCmp` to compare to.
-    /// + `diff_op`: The `DiffOp` to compare file differences. If the files do
-    /// not differ, they will compare equal regardless of their modification
-    /// times. 
-    /// + `promote_newest`: If true, indicates that missing files should be
-    /// considered greater than other files. Otherwise, they are considered less
-    /// than other files.
-    #[must_use]
-    pub fn partial_cmp(
-        &self,
-        other: &Self,
-        diff_op: &DiffOp,
-        promote_newest: bool)
-        -> Option<Ordering>
-    {
-        use Ordering::*;
+    /// [`MissingFileBehavior`]: MissingFileBehavior
+    Missing,
+}
 
-        if let Ok(false) = diff_op
-            .diff(self.path.as_path(), other.path.as_path())
-        {
-            return Some(Equal);
+impl FromStr for UnreadableFilePolicy {
+    type Err = UnreadableFilePolicyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("error") {
+            Ok(Self::Error)
+        } else if s.eq_ignore_ascii_case("skip") {
+            Ok(Self::Skip)
+        } else if s.eq_ignore_ascii_case("missing") {
+            Ok(Self::Missing)
+        } else {
+            Err(UnreadableFilePolicyParseError)
         }
+    }
+}
 
-        let file_cmp = match (&self.file, &other.file) {
-            (Some(_), Some(_)) => Equal,
-            (None,    Some(_)) => if promote_newest { Greater } else { Less },
-            (Some(_), None)    => if promote_newest { Less } else { Greater },
-            _                  => return None,
-        };
-        let time_cmp = match (&self.modified(), &other.modified()) {
-            (Some(t1), Some(t2)) => t1.cmp(t2),
-            (None,    Some(_))   => if promote_newest { Greater } else { Less },
-            (Some(_), None)      => if promote_newest { Less } else { Greater },
-            _                    => return None,
-        };
+/// An error indicating a failure to parse an [`UnreadableFilePolicy`].
+///
+/// [`UnreadableFilePolicy`]: UnreadableFilePolicy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnreadableFilePolicyParseError;
+
+impl std::error::Error for UnreadableFilePolicyParseError {}
 
-        Some(file_cmp.then(time_cmp))
+impl std::fmt::Display for UnreadableFilePolicyParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse UnreadableFilePolicy")
     }
 }
 
 
-
 ////////////////////////////////////////////////////////////////////////////////
-// MissingFileBehavior
+// MissingOverride
 ////////////////////////////////////////////////////////////////////////////////
-/// Options for handling missing files.
-#[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[derive(clap::ArgEnum)]
-pub enum MissingFileBehavior {
-    /// Treat missing files as older than all others.
-    Oldest,
-    /// Treat missing files as newer than all others.
-    Newest,
-    /// Ignore the file if it is missing.
-    Ignore,
-    /// Return an error if the file is missing.
-    Error,
+/// A [`MissingFileBehavior`] which applies to paths matching a glob pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingOverride {
+    /// The pattern of paths to apply the `MissingFileBehavior` to.
+    pub pattern: Pattern,
+    /// The `MissingFileBehavior` to apply.
+    pub missing: MissingFileBehavior,
 }
 
-impl FromStr for MissingFileBehavior {
-    type Err = MissingFileBehaviorParseError;
+impl FromStr for MissingOverride {
+    type Err = MissingOverrideParseError;
 
+    /// Parses a `MissingOverride` of the form `<glob>=<behavior>`.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.eq_ignore_ascii_case("oldest") {
-            Ok(Self::Oldest)
-        } else if s.eq_ignore_ascii_case("newest") {
-            Ok(Self::Newest)
-        } else if s.eq_ignore_ascii_case("ignore") {
-            Ok(Self::Ignore)
-        } else if s.eq_ignore_ascii_case("error") {
-            Ok(Self::Error)
-        } else {
-            Err(MissingFileBehaviorParseError)
-        }
+        let (pattern, missing) = s
+            .rsplit_once('=')
+            .ok_or(MissingOverrideParseError)?;
+
+        Ok(Self {
+            pattern: Pattern::new(pattern)
+                .map_err(|_e| MissingOverrideParseError)?,
+            missing: missing.parse()
+                .map_err(|_e| MissingOverrideParseError)?,
+        })
     }
 }
 
-/// An error indicating a failure to parse a [`MissingFileBehavior`].
+/// An error indicating a failure to parse a [`MissingOverride`].
 ///
-/// [`MissingFileBehavior`]: MissingFileBehavior 
+/// [`MissingOverride`]: MissingOverride 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct MissingFileBehaviorParseError;
+pub struct MissingOverrideParseError;
 
-impl std::error::Error for MissingFileBehaviorParseError {}
+impl std::error::Error for MissingOverrideParseError {}
 
-impl std::fmt::Display for MissingFileBehaviorParseError {
+impl std::fmt::Display for MissingOverrideParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "failure to parse MissingFileBehavior")
+        write!(f, "failure to parse MissingOverride")
     }
 }
 
@@ -192,77 +1915,105 @@ impl std::fmt::Display for MissingFileBehaviorParseError {
 ////////////////////////////////////////////////////////////////////////////////
 // compare
 ////////////////////////////////////////////////////////////////////////////////
-/// Returns the ordering of two files based on their modification times. The
-/// order is a partial order, and as such, None will be returned if the file
-/// modification times cannot be determined.
+/// Returns the ordering of two files based on the given [`Comparator`].
+///
+/// The order is a partial order, and as such,
+/// [`CompareOutcome::Incomparable`] will be returned with the reason if the
+/// files cannot be ordered.
 ///
 ///
 /// ### Parameters
 /// 
-/// + `diff`: Whether to consider files with equivalent content to be equal.
-/// + `missing`: The [`MissingFileBehavior`] indicating how to handle missing
-/// files.
+/// + `comparator`: The [`Comparator`] used to order the files.
 /// 
 /// ### Errors
 ///
-/// Returns an error if `MissingFileBehavior::Error` is used and a provided
-/// file is missing, or if reading the file results in an unexpected IO error.
+/// Returns an [`FcmpError`] if a provided file is missing, unreadable, or
+/// otherwise rejected by the `Comparator`'s policies, or if reading or
+/// comparing the files results in an unexpected IO error.
 ///
-/// [`Path`]: std::path::Path
-/// [`MissingFileBehavior`]: MissingFileBehavior
-pub fn partial_cmp_paths(
-    a: &Path,
-    b: &Path,
-    diff_op: &DiffOp,
-    missing: MissingFileBehavior)
-    -> Result<Option<Ordering>, anyhow::Error>
+/// [`FcmpError`]: crate::FcmpError
+/// [`Comparator`]: Comparator
+/// [`CompareOutcome::Incomparable`]: CompareOutcome::Incomparable
+pub fn partial_cmp_paths(a: &Path, b: &Path, comparator: &Comparator)
+    -> Result<CompareOutcome, FcmpError>
 {
-    let promote_newest = matches!(missing, MissingFileBehavior::Newest);
-
-    // Check if they're the same paths.
-    if a == b { return Ok(Some(Ordering::Equal)); }
-
-    let a = match FileCmp::try_from(a.to_path_buf()) {
-        Ok(file_cmp) if !file_cmp.is_found() => match missing {
-            MissingFileBehavior::Error => return Err(
-                anyhow!("file '{}' not found", a.display())
-            ),
-
-            MissingFileBehavior::Ignore => None,
-            _ => Some(file_cmp),
-        },
-        Ok(file_cmp) => Some(file_cmp),
-        Err(e) => return Err(e.into()),
-    };
+    // Check if they're the same paths. Other paths to the same underlying
+    // file are found once the files are opened, without reading them.
+    if a == b || comparator.is_same_path(a, b) {
+        return Ok(CompareOutcome::Ordered(Ordering::Equal));
+    }
+
+    let unstable = comparator.find_unstable(&[a, b]);
+    let a = if unstable[0] { None } else { comparator.open(a)? };
+    let b = if unstable[1] { None } else { comparator.open(b)? };
 
-    let b = match FileCmp::try_from(b.to_path_buf()) {
-        Ok(file_cmp) if !file_cmp.is_found() => match missing {
-            MissingFileBehavior::Error => return Err(
-                anyhow!("file '{}' not found", b.display())
-            ),
-
-            MissingFileBehavior::Ignore => None,
-            _ => Some(file_cmp),
-        },
-        Ok(file_cmp) => Some(file_cmp),
-        Err(e) => return Err(e.into()),
+    let outcome = match (a, b) {
+        (Some(a), Some(b)) => a.try_partial_cmp(&b, comparator)
+            .map_err(|e| FcmpError::diff(a.path.clone(), b.path.clone(), e))?,
+        (None, None) => CompareOutcome::Incomparable(Incomparable::Excluded),
+        (None,    _) => CompareOutcome::Ordered(Ordering::Greater),
+        (_,    None) => CompareOutcome::Ordered(Ordering::Less),
     };
 
-    let ordering = match (a, b) {
-        (Some(a), Some(b)) => a.partial_cmp(&b, diff_op, promote_newest),
-        (None, None) => Some(Ordering::Equal),
-        (None,    _) => Some(Ordering::Greater),
-        (_,    None) => Some(Ordering::Less),
+    Ok(outcome)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// is_up_to_date
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the file at the target path exists and is not older
+/// than any of the files at the dependency paths, according to the given
+/// [`Comparator`].
+///
+/// As with `make`, a dependency which compares equal to the target, such as
+/// one modified within the `Comparator`'s `mtime_tolerance` of it, does not
+/// make the target out of date. Missing dependencies are handled according
+/// to the `Comparator`'s [`MissingFileBehavior`], and dependencies which are
+/// excluded by its policies are skipped. A target which can't be ordered
+/// against a dependency is out of date.
+///
+/// ### Errors
+///
+/// Returns an [`FcmpError`] if a dependency is missing, unreadable, or
+/// otherwise rejected by the `Comparator`'s policies, or if reading or
+/// comparing the files results in an unexpected IO error.
+///
+/// [`FcmpError`]: crate::FcmpError
+/// [`Comparator`]: Comparator
+/// [`MissingFileBehavior`]: MissingFileBehavior
+pub fn is_up_to_date<'p, D>(target: &Path, deps: D, comparator: &Comparator)
+    -> Result<bool, FcmpError>
+    where D: IntoIterator<Item=&'p Path>
+{
+    let target = match comparator.open(target) {
+        Ok(Some(target)) if target.is_found() => target,
+        Ok(_) | Err(FcmpError::Missing { .. }) => return Ok(false),
+        Err(e) => return Err(e),
     };
 
-    Ok(ordering)
+    for dep in deps {
+        comparator.check_cancelled()?;
+        let Some(dep) = comparator.open(dep)? else { continue };
+        let outcome = target.try_partial_cmp(&dep, comparator)
+            .map_err(|e| FcmpError::diff(
+                target.path.clone(),
+                dep.path.clone(),
+                e))?;
+        if outcome.ordering().is_none_or(Ordering::is_lt) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
 }
 
+
 ////////////////////////////////////////////////////////////////////////////////
 // compare_all
 ////////////////////////////////////////////////////////////////////////////////
-/// Takes an iterator of [`Path`]s and returns the index of the most recently
-/// modified file.
+/// Takes an iterator of [`Path`]s and returns the index of the greatest file
+/// according to the given [`Comparator`].
 /// 
 /// If the result would be ambiguous, the first occurring ambiguous item in the
 /// list will be returned.
@@ -271,61 +2022,425 @@ pub fn partial_cmp_paths(
 /// ### Parameters
 /// 
 /// + `reverse`: Whether to reverse to comparison order and return the least
-/// recently modified file.
-/// + `diff`: Whether to consider files with equivalent content to be equal.
-/// + `missing`: The [`MissingFileBehavior`] indicating how to handle missing
-/// files.
+///   file.
+/// + `comparator`: The [`Comparator`] used to order the files.
+/// 
+/// ### Errors
+///
+/// Returns an [`FcmpError`] if a provided file is missing, unreadable, or
+/// otherwise rejected by the `Comparator`'s policies, or if reading or
+/// comparing the files results in an unexpected IO error.
+///
+/// [`FcmpError`]: crate::FcmpError
+/// [`Path`]: std::path::Path
+/// [`Comparator`]: Comparator
+pub fn compare_all<'p, P>(paths: P, reverse: bool, comparator: &Comparator)
+    -> Result<usize, FcmpError>
+    where P: IntoIterator<Item=&'p Path>
+{
+    let ties = compare_all_ties(paths, reverse, comparator)?;
+    Ok(ties.first().copied().unwrap_or(0))
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// compare_all_ties
+////////////////////////////////////////////////////////////////////////////////
+/// Takes an iterator of [`Path`]s and returns the indices of every file which
+/// ties for the greatest file according to the given [`Comparator`].
+/// 
+/// The indices are returned in the order they occur in the list. If no files
+/// are considered, the result will be empty.
+///
+///
+/// ### Parameters
+/// 
+/// + `reverse`: Whether to reverse to comparison order and return the least
+///   files.
+/// + `comparator`: The [`Comparator`] used to order the files.
+/// 
+/// ### Errors
+///
+/// Returns an [`FcmpError`] if a provided file is missing, unreadable, or
+/// otherwise rejected by the `Comparator`'s policies, or if reading or
+/// comparing the files results in an unexpected IO error.
+///
+/// [`FcmpError`]: crate::FcmpError
+/// [`Path`]: std::path::Path
+/// [`Comparator`]: Comparator
+pub fn compare_all_ties<'p, P>(
+    paths: P,
+    reverse: bool,
+    comparator: &Comparator)
+    -> Result<Vec<usize>, FcmpError>
+    where P: IntoIterator<Item=&'p Path>
+{
+    compare_all_report(paths, reverse, comparator).map(|report| report.ties)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// compare_top
+////////////////////////////////////////////////////////////////////////////////
+/// Takes an iterator of [`Path`]s and returns the indices of the `n` greatest
+/// files according to the given [`Comparator`], in descending order.
+/// 
+/// Files which compare equal are returned in the order they occur in the list.
+/// If fewer than `n` files are considered, all of them are returned.
+///
+///
+/// ### Parameters
+/// 
+/// + `n`: The number of files to return.
+/// + `reverse`: Whether to reverse to comparison order and return the least
+///   files in ascending order.
+/// + `comparator`: The [`Comparator`] used to order the files.
+/// 
+/// ### Errors
+///
+/// Returns an [`FcmpError`] if a provided file is missing, unreadable, or
+/// otherwise rejected by the `Comparator`'s policies, or if reading or
+/// comparing the files results in an unexpected IO error.
+///
+/// [`FcmpError`]: crate::FcmpError
+/// [`Path`]: std::path::Path
+/// [`Comparator`]: Comparator
+pub fn compare_top<'p, P>(
+    paths: P,
+    n: usize,
+    reverse: bool,
+    comparator: &Comparator)
+    -> Result<Vec<usize>, FcmpError>
+    where P: IntoIterator<Item=&'p Path>
+{
+    rank(paths, Some(n), reverse, comparator)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// compare_nth
+////////////////////////////////////////////////////////////////////////////////
+/// Takes an iterator of [`Path`]s and returns the index of the `n`-th greatest
+/// file according to the given [`Comparator`], where `n = 0` is the greatest
+/// file.
+/// 
+/// Files which compare equal are ranked in the order they occur in the list.
+/// Returns `None` if `n` or fewer files are considered.
+///
+///
+/// ### Parameters
+/// 
+/// + `n`: The rank of the file to return.
+/// + `reverse`: Whether to reverse to comparison order and return the `n`-th
+///   least file.
+/// + `comparator`: The [`Comparator`] used to order the files.
+/// 
+/// ### Errors
+///
+/// Returns an [`FcmpError`] if a provided file is missing, unreadable, or
+/// otherwise rejected by the `Comparator`'s policies, or if reading or
+/// comparing the files results in an unexpected IO error.
+///
+/// [`FcmpError`]: crate::FcmpError
+/// [`Path`]: std::path::Path
+/// [`Comparator`]: Comparator
+pub fn compare_nth<'p, P>(
+    paths: P,
+    n: usize,
+    reverse: bool,
+    comparator: &Comparator)
+    -> Result<Option<usize>, FcmpError>
+    where P: IntoIterator<Item=&'p Path>
+{
+    let ranked = rank(paths, Some(n.saturating_add(1)), reverse, comparator)?;
+    Ok(ranked.get(n).copied())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// sort_paths
+////////////////////////////////////////////////////////////////////////////////
+/// Takes an iterator of [`Path`]s and returns the indices of the files sorted
+/// in descending order according to the given [`Comparator`].
+/// 
+/// Files which compare equal are returned in the order they occur in the list.
+/// Ignored files are not included in the result.
+///
+///
+/// ### Parameters
+/// 
+/// + `reverse`: Whether to reverse to comparison order and sort the files in
+///   ascending order.
+/// + `comparator`: The [`Comparator`] used to order the files.
+/// 
+/// ### Errors
+///
+/// Returns an [`FcmpError`] if a provided file is missing, unreadable, or
+/// otherwise rejected by the `Comparator`'s policies, or if reading or
+/// comparing the files results in an unexpected IO error.
+///
+/// [`FcmpError`]: crate::FcmpError
+/// [`Path`]: std::path::Path
+/// [`Comparator`]: Comparator
+pub fn sort_paths<'p, P>(paths: P, reverse: bool, comparator: &Comparator)
+    -> Result<Vec<usize>, FcmpError>
+    where P: IntoIterator<Item=&'p Path>
+{
+    rank(paths, None, reverse, comparator)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// rotate_paths
+////////////////////////////////////////////////////////////////////////////////
+/// Takes an iterator of [`Path`]s and returns the indices of the files beyond
+/// the `keep` greatest files according to the given [`Comparator`], in
+/// descending order.
+///
+/// This selects the files to remove when rotating backups. Files which compare
+/// equal to the least kept file are also kept, so that the result does not
+/// depend on the order of the list. Missing files are handled according to
+/// the `Comparator`'s [`MissingFileBehavior`], but are never kept or returned,
+/// as there is nothing to remove.
+///
+///
+/// ### Parameters
+/// 
+/// + `keep`: The number of files to keep.
+/// + `reverse`: Whether to reverse to comparison order and keep the least
+///   files instead.
+/// + `comparator`: The [`Comparator`] used to order the files.
 /// 
 /// ### Errors
 ///
-/// Returns an error if `MissingFileBehavior::Error` is used and a provided
-/// file is missing, or if reading the file results in an unexpected IO error.
+/// Returns an [`FcmpError`] if a provided file is missing, unreadable, or
+/// otherwise rejected by the `Comparator`'s policies, or if reading or
+/// comparing the files results in an unexpected IO error.
 ///
+/// [`FcmpError`]: crate::FcmpError
 /// [`Path`]: std::path::Path
+/// [`Comparator`]: Comparator
 /// [`MissingFileBehavior`]: MissingFileBehavior
-pub fn compare_all<'p, P>(
+pub fn rotate_paths<'p, P>(
     paths: P,
+    keep: usize,
     reverse: bool,
-    diff_op: &DiffOp,
-    missing: MissingFileBehavior)
-    -> Result<usize, anyhow::Error>
+    comparator: &Comparator)
+    -> Result<Vec<usize>, FcmpError>
     where P: IntoIterator<Item=&'p Path>
 {
-    let promote_newest = matches!(missing, MissingFileBehavior::Newest);
+    let mut ranked = rank_files(paths, None, reverse, comparator)?;
+    ranked.retain(|(_, file)| file.is_found());
+    let mut kept = keep.min(ranked.len());
+    if let Some((_, least_kept)) = kept.checked_sub(1).map(|i| &ranked[i]) {
+        for (_, file) in &ranked[kept..] {
+            let outcome = file.try_partial_cmp(least_kept, comparator)
+                .map_err(|e| FcmpError::diff(
+                    file.path.clone(),
+                    least_kept.path.clone(),
+                    e))?;
+            if outcome.ordering() != Some(Ordering::Equal) { break; }
+            kept += 1;
+        }
+    }
+    Ok(ranked.into_iter().skip(kept).map(|(idx, _)| idx).collect())
+}
 
-    let mut max_idx = 0;
-    let mut prev_file_cmp: Option<FileCmp> = None;
 
-    for (idx, p) in paths.into_iter().enumerate() {
-        let curr = match FileCmp::try_from(p.to_path_buf()) {
-            Ok(file_cmp) if !file_cmp.is_found() => match missing {
-                MissingFileBehavior::Error => return Err(
-                    anyhow!("file '{}' not found", p.display())
-                ),
+/// Returns the indices of the greatest files in descending order, keeping at
+/// most `limit` of them.
+fn rank<'p, P>(
+    paths: P,
+    limit: Option<usize>,
+    reverse: bool,
+    comparator: &Comparator)
+    -> Result<Vec<usize>, FcmpError>
+    where P: IntoIterator<Item=&'p Path>
+{
+    Ok(rank_files(paths, limit, reverse, comparator)?
+        .into_iter()
+        .map(|(idx, _)| idx)
+        .collect())
+}
 
-                MissingFileBehavior::Ignore => continue,
-                _ => Some(file_cmp),
-            },
-            Ok(file_cmp) => Some(file_cmp),
-            Err(e) => return Err(e.into()),
-        };
+/// Returns the indices and comparers of the greatest files in descending
+/// order, keeping at most `limit` of them.
+///
+/// The files are kept in a sorted buffer bounded by `limit`. A `FileCmp` only
+/// opens its file while comparing content, so the number of files held open
+/// does not grow with the number of paths.
+fn rank_files<'p, P>(
+    paths: P,
+    limit: Option<usize>,
+    reverse: bool,
+    comparator: &Comparator)
+    -> Result<Vec<(usize, FileCmp)>, FcmpError>
+    where P: IntoIterator<Item=&'p Path>
+{
+    let paths: Vec<&Path> = paths.into_iter().collect();
+    let unstable = comparator.find_unstable(&paths);
+    let files = comparator.open_all(&paths, &unstable)?;
 
-        match (prev_file_cmp.as_ref(), curr) {
-            (Some(prev), Some(curr)) => {
-                let cmp = prev.partial_cmp(&curr, diff_op, promote_newest)
-                    .map(|o| if reverse { o } else { o.reverse() });
-                if cmp == Some(Ordering::Greater) {
-                    prev_file_cmp = Some(curr);
-                    max_idx = idx;
-                }
-            },
-            (None, curr) => {
-                prev_file_cmp = curr;
-                max_idx = idx;
-            },
-            _ => (),
+    let mut ranked: Vec<(usize, FileCmp)> = Vec::new();
+
+    for (idx, curr) in files.into_iter().enumerate() {
+        comparator.check_cancelled()?;
+        let Some(curr) = curr else { continue };
+
+        // Insert after every file which is not less than the current one, so
+        // that equal files keep their input order.
+        let mut failure = None;
+        let pos = ranked.partition_point(|(_, r)| {
+            let cmp = curr.try_partial_cmp(r, comparator)
+                .map_err(|e| failure = Some((r.path.clone(), e)))
+                .ok()
+                .and_then(CompareOutcome::ordering)
+                .map(|o| if reverse { o.reverse() } else { o });
+            cmp != Some(Ordering::Greater)
+        });
+        if let Some((path, e)) = failure {
+            return Err(FcmpError::diff(curr.path, path, e));
+        }
+
+        if limit.is_none_or(|n| pos < n) {
+            ranked.insert(pos, (idx, curr));
+            if let Some(n) = limit { ranked.truncate(n); }
+        }
+    }
+
+    Ok(ranked)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// content_equal
+////////////////////////////////////////////////////////////////////////////////
+/// Takes an iterator of [`Path`]s and returns the indices of the files whose
+/// content does not differ from the file at index `idx`, including `idx`
+/// itself.
+///
+/// Files which cannot be compared are considered to differ.
+///
+/// ### Parameters
+/// 
+/// + `idx`: The index of the file to compare the others against.
+/// + `diff_op`: The [`DiffOp`] used to compare file contents.
+///
+/// [`Path`]: std::path::Path
+/// [`DiffOp`]: crate::DiffOp
+pub fn content_equal<'p, P>(paths: P, idx: usize, diff_op: &DiffOp)
+    -> Vec<usize>
+    where P: IntoIterator<Item=&'p Path>
+{
+    let paths: Vec<&Path> = paths.into_iter().collect();
+    let Some(target) = paths.get(idx) else { return Vec::new() };
+
+    paths.iter()
+        .enumerate()
+        .filter(|(i, p)| *i == idx
+            || matches!(diff_op.diff(target, p), Ok(false)))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// duplicate_groups
+////////////////////////////////////////////////////////////////////////////////
+/// Takes an iterator of [`Path`]s and returns the indices of the files with
+/// identical content, grouped by content. Only groups of two or more files
+/// are returned, in the order of their first file.
+///
+/// Files are grouped by size, and files of the same size by the SHA-256
+/// digest of their content, so that files with a unique size are not read
+/// and other files are read once. Missing files, directories, and files which
+/// can't be read are not grouped.
+///
+/// ### Parameters
+///
+/// + `verify`: Whether files with the same digest are also compared byte by
+///   byte, so that a digest collision can't group different files.
+///
+/// [`Path`]: std::path::Path
+pub fn duplicate_groups<'p, P>(paths: P, verify: bool) -> Vec<Vec<usize>>
+    where P: IntoIterator<Item=&'p Path>
+{
+    let files: Vec<Option<FileCmp>> = paths
+        .into_iter()
+        .map(|path| FileCmp::try_from(path).ok())
+        .collect();
+    let file = |idx: usize| files[idx].as_ref();
+
+    let same_sizes = group_by(0..files.len(), |idx| file(idx)
+        .filter(|f| f.is_found()
+            && !f.metadata.as_ref().is_some_and(Metadata::is_dir))
+        .and_then(FileCmp::size));
+
+    // Hardlinks to the same file share a digest, so each file is read once.
+    let mut digests = HashMap::new();
+    let mut digest = |f: &FileCmp| f.id().map_or_else(
+        || f.digest(),
+        |id| *digests.entry(id).or_insert_with(|| f.digest()));
+
+    let mut groups = Vec::new();
+    for same_size in same_sizes.into_iter().filter(|g| g.len() > 1) {
+        let same_digests = group_by(same_size, |idx| file(idx)
+            .and_then(&mut digest));
+        for same_digest in same_digests {
+            if verify {
+                let diff_op = DiffOp::internal();
+                groups.extend(group_by_eq(same_digest, |a, b| {
+                    let (a, b) = (file(a).map(|f| &f.path), file(b));
+                    a.zip(b).is_some_and(|(a, b)| {
+                        matches!(diff_op.diff(a, &b.path), Ok(false))
+                    })
+                }));
+            } else {
+                groups.push(same_digest);
+            }
         }
     }
 
-    Ok(max_idx)
+    groups.retain(|g| g.len() > 1);
+    groups.sort_unstable_by_key(|g| g[0]);
+    groups
+}
+
+/// Groups the given indices by the key returned for each by the given
+/// function, in the order each key is first returned. Indices without a key
+/// are not grouped.
+fn group_by<I, F, K>(indices: I, mut key: F) -> Vec<Vec<usize>>
+    where
+        I: IntoIterator<Item=usize>,
+        F: FnMut(usize) -> Option<K>,
+        K: Hash + Eq,
+{
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut positions = HashMap::new();
+    for idx in indices {
+        let Some(key) = key(idx) else { continue };
+        let pos = *positions.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[pos].push(idx);
+    }
+    groups
+}
+
+/// Groups the given indices by comparing each to the first index of each
+/// group with the given function, in the order each group is first found.
+fn group_by_eq<I, F>(indices: I, mut eq: F) -> Vec<Vec<usize>>
+    where
+        I: IntoIterator<Item=usize>,
+        F: FnMut(usize, usize) -> bool,
+{
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for idx in indices {
+        match groups.iter_mut().find(|g| eq(g[0], idx)) {
+            Some(group) => group.push(idx),
+            None        => groups.push(vec![idx]),
+        }
+    }
+    groups
 }
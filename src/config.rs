@@ -0,0 +1,198 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! User configuration.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::paths::expand_glob;
+use crate::paths::GlobError;
+
+// External library imports.
+use serde::Deserialize;
+use serde::Serialize;
+
+// Standard library imports.
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Config
+////////////////////////////////////////////////////////////////////////////////
+/// User configuration, loaded from a TOML file.
+///
+/// Named path sets are given as tables named `set`, such as:
+///
+/// ```toml
+/// [set.logs]
+/// paths = ["/var/log/app/*.log"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The named path sets.
+    #[serde(rename = "set", default)]
+    pub sets: BTreeMap<String, PathSet>,
+}
+
+impl Config {
+    /// Loads a `Config` from the TOML file at the given path.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`ConfigError`] if the file can't be read or is not a valid
+    /// config file.
+    ///
+    /// [`ConfigError`]: ConfigError
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|source| ConfigError::Read {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        toml::from_str(&text)
+            .map_err(|source| ConfigError::Parse {
+                path: path.to_path_buf(),
+                source,
+            })
+    }
+
+    /// Loads the `Config` from the default config file, or returns an empty
+    /// `Config` if the file does not exist.
+    ///
+    /// The default config file is `fcmp/config.toml` in the directory given
+    /// by `XDG_CONFIG_HOME`, or `~/.config` if it is not set. On Windows,
+    /// `APPDATA` is used instead.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`ConfigError`] if the file exists but can't be read or is
+    /// not a valid config file.
+    ///
+    /// [`ConfigError`]: ConfigError
+    pub fn load_default() -> Result<Self, ConfigError> {
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Returns the path of the default config file, if it can be determined.
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = if cfg!(windows) {
+            std::env::var_os("APPDATA").map(PathBuf::from)
+        } else {
+            std::env::var_os("XDG_CONFIG_HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| std::env::var_os("HOME")
+                    .map(|home| PathBuf::from(home).join(".config")))
+        };
+        dir.map(|dir| dir.join("fcmp").join("config.toml"))
+    }
+
+    /// Returns the paths of the named path set, with any glob patterns
+    /// expanded.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`ConfigError`] if there is no path set with the given name,
+    /// or if a pattern can't be expanded.
+    ///
+    /// [`ConfigError`]: ConfigError
+    pub fn expand_set(&self, name: &str) -> Result<Vec<PathBuf>, ConfigError> {
+        let set = self.sets
+            .get(name)
+            .ok_or_else(|| ConfigError::UnknownSet { name: name.to_owned() })?;
+        Ok(set.expand()?)
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PathSet
+////////////////////////////////////////////////////////////////////////////////
+/// A named set of paths defined in a [`Config`].
+///
+/// [`Config`]: Config
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PathSet {
+    /// The paths in the set. Paths containing `*`, `?`, or `[` are expanded as
+    /// glob patterns, and all others are used as given.
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+impl PathSet {
+    /// Returns the paths in the set, with any glob patterns expanded.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`GlobError`] if a pattern can't be expanded.
+    ///
+    /// [`GlobError`]: crate::GlobError
+    pub fn expand(&self) -> Result<Vec<PathBuf>, GlobError> {
+        let mut paths = Vec::new();
+        for path in &self.paths {
+            if path.contains(['*', '?', '[']) {
+                paths.extend(expand_glob(path)?);
+            } else {
+                paths.push(PathBuf::from(path));
+            }
+        }
+        Ok(paths)
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ConfigError
+////////////////////////////////////////////////////////////////////////////////
+/// An error which occurs while loading a [`Config`] or expanding its path
+/// sets.
+///
+/// [`Config`]: Config
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The config file can't be read.
+    #[error("failed to read config file '{}'", path.display())]
+    Read {
+        /// The path of the config file.
+        path: PathBuf,
+        /// The error which occurred when reading the file.
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The config file is not valid TOML or has unknown fields.
+    #[error("invalid config file '{}'", path.display())]
+    Parse {
+        /// The path of the config file.
+        path: PathBuf,
+        /// The error which occurred when parsing the file.
+        #[source]
+        source: toml::de::Error,
+    },
+
+    /// There is no path set with the given name.
+    #[error("unknown path set '@{name}'")]
+    UnknownSet {
+        /// The name of the path set.
+        name: String,
+    },
+
+    /// A pattern in a path set can't be expanded.
+    #[error(transparent)]
+    Glob(#[from] GlobError),
+}
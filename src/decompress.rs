@@ -0,0 +1,169 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Transparent decompression of file content.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+use flate2::bufread::MultiGzDecoder;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+// Standard library imports.
+use std::io::BufRead as _;
+use std::io::BufReader;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+
+/// The magic bytes at the start of a gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// The magic bytes at the start of a zstd frame.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+/// The magic bytes at the start of a bzip2 stream.
+const BZIP2_MAGIC: [u8; 3] = *b"BZh";
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Decompression
+////////////////////////////////////////////////////////////////////////////////
+/// Options for decompressing file content before it is compared.
+///
+/// A file is detected as compressed by its magic bytes or its extension.
+/// Files which are not detected as compressed are compared as they are stored,
+/// so a compressed file may be compared against an uncompressed one.
+///
+/// Gzip is always supported. Zstd and bzip2 are supported when the `zstd` and
+/// `bzip2` features are enabled, and decompressing them otherwise results in
+/// an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(clap::ArgEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Decompression {
+    /// Compare all files as they are stored.
+    #[default]
+    None,
+    /// Decompress files in any supported format.
+    Auto,
+    /// Decompress gzip files, detected by their magic bytes or a `.gz`
+    /// extension.
+    Gzip,
+    /// Decompress zstd files, detected by their magic bytes or a `.zst`
+    /// extension.
+    Zstd,
+    /// Decompress bzip2 files, detected by their magic bytes or a `.bz2`
+    /// extension.
+    Bzip2,
+}
+
+impl Decompression {
+    /// Returns a reader of the decompressed content of the given file, which
+    /// was opened from the given path.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the start of the file can't be read to
+    /// detect its format, or if the file must be decompressed from a format
+    /// whose feature is not enabled.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub(crate) fn reader<'r, R>(self, path: &Path, file: R)
+        -> Result<Box<dyn Read + 'r>, std::io::Error>
+        where R: Read + 'r
+    {
+        let mut reader = BufReader::new(file);
+        if self == Self::None { return Ok(Box::new(reader)); }
+
+        let detected = Self::detect(path, reader.fill_buf()?);
+        if !self.accepts(detected) { return Ok(Box::new(reader)); }
+
+        match detected {
+            Self::Gzip => Ok(Box::new(MultiGzDecoder::new(reader))),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => Ok(Box::new(
+                zstd::stream::read::Decoder::with_buffer(reader)?)),
+            #[cfg(feature = "bzip2")]
+            Self::Bzip2 => Ok(Box::new(
+                bzip2::bufread::MultiBzDecoder::new(reader))),
+            #[cfg(not(feature = "zstd"))]
+            Self::Zstd => Err(not_enabled(detected)),
+            #[cfg(not(feature = "bzip2"))]
+            Self::Bzip2 => Err(not_enabled(detected)),
+            Self::None | Self::Auto => Ok(Box::new(reader)),
+        }
+    }
+
+    /// Returns `true` if files detected in the given format are decompressed.
+    fn accepts(self, detected: Self) -> bool {
+        self == Self::Auto || self == detected
+    }
+
+    /// Returns the compression format of a file, given its path and the
+    /// start of its content.
+    fn detect(path: &Path, header: &[u8]) -> Self {
+        let extension = |ext: &str| path.extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case(ext));
+
+        if header.starts_with(&GZIP_MAGIC) || extension("gz") {
+            Self::Gzip
+        } else if header.starts_with(&ZSTD_MAGIC) || extension("zst") {
+            Self::Zstd
+        } else if header.starts_with(&BZIP2_MAGIC) || extension("bz2") {
+            Self::Bzip2
+        } else {
+            Self::None
+        }
+    }
+}
+
+/// Returns an error indicating that decompression from the given format is not
+/// enabled.
+#[cfg(not(all(feature = "zstd", feature = "bzip2")))]
+fn not_enabled(format: Decompression) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("{format:?} decompression is not enabled"))
+}
+
+impl FromStr for Decompression {
+    type Err = DecompressionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("none") {
+            Ok(Self::None)
+        } else if s.eq_ignore_ascii_case("auto") {
+            Ok(Self::Auto)
+        } else if s.eq_ignore_ascii_case("gzip") {
+            Ok(Self::Gzip)
+        } else if s.eq_ignore_ascii_case("zstd") {
+            Ok(Self::Zstd)
+        } else if s.eq_ignore_ascii_case("bzip2") {
+            Ok(Self::Bzip2)
+        } else {
+            Err(DecompressionParseError)
+        }
+    }
+}
+
+/// An error indicating a failure to parse a [`Decompression`].
+///
+/// [`Decompression`]: Decompression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecompressionParseError;
+
+impl std::error::Error for DecompressionParseError {}
+
+impl std::fmt::Display for DecompressionParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse Decompression")
+    }
+}
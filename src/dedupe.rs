@@ -0,0 +1,264 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Replacing duplicate files with links.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::compare::compare_all;
+use crate::compare::Comparator;
+use crate::error::FcmpError;
+use crate::ops::DiffOp;
+
+// Standard library imports.
+use std::ffi::OsString;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// DedupeAction
+////////////////////////////////////////////////////////////////////////////////
+/// The action taken on each duplicate file which is not kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(clap::ArgEnum)]
+pub enum DedupeAction {
+    /// Replace the file with a hardlink to the kept file.
+    Hardlink,
+    /// Replace the file with a symbolic link to the absolute path of the kept
+    /// file.
+    Symlink,
+    /// Remove the file.
+    #[clap(name = "rm-script")]
+    Remove,
+}
+
+impl FromStr for DedupeAction {
+    type Err = DedupeActionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("hardlink") {
+            Ok(Self::Hardlink)
+        } else if s.eq_ignore_ascii_case("symlink") {
+            Ok(Self::Symlink)
+        } else if s.eq_ignore_ascii_case("rm-script")
+            || s.eq_ignore_ascii_case("remove")
+        {
+            Ok(Self::Remove)
+        } else {
+            Err(DedupeActionParseError)
+        }
+    }
+}
+
+/// An error indicating a failure to parse a [`DedupeAction`].
+///
+/// [`DedupeAction`]: DedupeAction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DedupeActionParseError;
+
+impl std::error::Error for DedupeActionParseError {}
+
+impl std::fmt::Display for DedupeActionParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse DedupeAction")
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// DedupeStep
+////////////////////////////////////////////////////////////////////////////////
+/// A [`DedupeAction`] taken on one duplicate file.
+///
+/// [`DedupeAction`]: DedupeAction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DedupeStep {
+    /// The action taken on the duplicate file.
+    pub action: DedupeAction,
+    /// The path of the duplicate file.
+    pub path: PathBuf,
+    /// The path of the kept file with the same content.
+    pub kept: PathBuf,
+}
+
+impl DedupeStep {
+    /// Performs the step. Links replace the duplicate file by renaming, so
+    /// the file is never missing.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the link can't be created or the file
+    /// can't be replaced or removed.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn apply(&self) -> Result<(), std::io::Error> {
+        let link = match self.action {
+            DedupeAction::Remove => return std::fs::remove_file(&self.path),
+            DedupeAction::Hardlink => std::fs::hard_link,
+            DedupeAction::Symlink => symlink,
+        };
+
+        let mut temp_name = OsString::from(".");
+        temp_name.push(self.path.file_name().unwrap_or_default());
+        temp_name.push(".fcmp-dedupe");
+        let temp = self.path.with_file_name(temp_name);
+        link(&self.kept, &temp)?;
+        std::fs::rename(&temp, &self.path).inspect_err(|_| {
+            let _ = std::fs::remove_file(&temp);
+        })
+    }
+
+    /// Returns the POSIX shell command which performs the step, followed by a
+    /// newline.
+    ///
+    /// The paths are quoted byte for byte, so the command names the same files
+    /// even if their names contain newlines or aren't valid UTF-8.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if a path can't be given to the shell
+    /// exactly, which is only possible on platforms where paths aren't bytes.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn command(&self) -> Result<Vec<u8>, std::io::Error> {
+        let mut command = match self.action {
+            DedupeAction::Hardlink => b"ln -f -- ".to_vec(),
+            DedupeAction::Symlink  => b"ln -sf -- ".to_vec(),
+            DedupeAction::Remove   => b"rm -f -- ".to_vec(),
+        };
+        if self.action != DedupeAction::Remove {
+            shell_quote(&self.kept, &mut command)?;
+            command.push(b' ');
+        }
+        shell_quote(&self.path, &mut command)?;
+        command.push(b'\n');
+        Ok(command)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// dedupe_steps
+////////////////////////////////////////////////////////////////////////////////
+/// Takes an iterator of [`Path`]s of files with identical content and returns
+/// the steps taking the given [`DedupeAction`] on every file except the most
+/// recently modified, which is kept.
+///
+/// The paths are usually a group returned by [`duplicate_groups`]. If several
+/// files are the most recently modified, the first of them is kept.
+///
+/// ### Errors
+///
+/// Returns an [`FcmpError`] if a file is missing or its modification time
+/// can't be read, or if the current directory can't be read to make the path
+/// of the kept file absolute for a symbolic link.
+///
+/// [`Path`]: std::path::Path
+/// [`duplicate_groups`]: crate::duplicate_groups
+/// [`DedupeAction`]: DedupeAction
+/// [`FcmpError`]: crate::FcmpError
+pub fn dedupe_steps<'p, P>(paths: P, action: DedupeAction)
+    -> Result<Vec<DedupeStep>, FcmpError>
+    where P: IntoIterator<Item=&'p Path>
+{
+    let paths: Vec<&Path> = paths.into_iter().collect();
+    // The files are known to have the same content.
+    let comparator = Comparator {
+        diff_op: DiffOp::None,
+        ..Comparator::default()
+    };
+    let kept_idx = compare_all(paths.iter().copied(), false, &comparator)?;
+
+    let kept = match action {
+        DedupeAction::Symlink => std::path::absolute(paths[kept_idx])
+            .map_err(|source| FcmpError::Io {
+                path: paths[kept_idx].to_path_buf(),
+                source,
+            })?,
+        _ => paths[kept_idx].to_path_buf(),
+    };
+    Ok(paths
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| *idx != kept_idx)
+        .map(|(_, path)| DedupeStep {
+            action,
+            path: path.to_path_buf(),
+            kept: kept.clone(),
+        })
+        .collect())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helpers
+////////////////////////////////////////////////////////////////////////////////
+/// Appends the given path to the command, quoted for a POSIX shell.
+///
+/// Every byte other than a single quote is literal within single quotes, so
+/// no byte of the path can end the quoting.
+fn shell_quote(path: &Path, command: &mut Vec<u8>)
+    -> Result<(), std::io::Error>
+{
+    command.push(b'\'');
+    for &byte in path_bytes(path)? {
+        if byte == b'\'' {
+            command.extend_from_slice(b"'\\''");
+        } else {
+            command.push(byte);
+        }
+    }
+    command.push(b'\'');
+    Ok(())
+}
+
+/// Returns the bytes of the given path.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps)] // Not bytes on other platforms.
+fn path_bytes(path: &Path) -> Result<&[u8], std::io::Error> {
+    use std::os::unix::ffi::OsStrExt as _;
+    Ok(path.as_os_str().as_bytes())
+}
+
+/// Returns the bytes of the given path.
+///
+/// ### Errors
+///
+/// Returns an error if the path is not valid unicode, as it has no bytes
+/// which a shell would read as the same path.
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Result<&[u8], std::io::Error> {
+    path.to_str()
+        .map(str::as_bytes)
+        .ok_or_else(|| std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{}: path is not valid unicode", path.display())))
+}
+
+/// Creates a symbolic link at `link` pointing to `target`.
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> Result<(), std::io::Error> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+/// Creates a symbolic link at `link` pointing to `target`.
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> Result<(), std::io::Error> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+/// Creates a symbolic link at `link` pointing to `target`.
+///
+/// ### Errors
+///
+/// Symbolic links are not supported on this platform, so an error is always
+/// returned.
+#[cfg(not(any(unix, windows)))]
+fn symlink(_target: &Path, _link: &Path) -> Result<(), std::io::Error> {
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+}
@@ -0,0 +1,135 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Comparison errors.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::cancel::Cancelled;
+use crate::compare::CompareKey;
+
+// Standard library imports.
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FcmpError
+////////////////////////////////////////////////////////////////////////////////
+/// An error which occurs while comparing files.
+///
+/// Files which can't be ordered are not an error, and are instead reported
+/// as [`CompareOutcome::Incomparable`].
+///
+/// [`CompareOutcome::Incomparable`]: crate::CompareOutcome::Incomparable
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum FcmpError {
+    /// A file is missing and [`MissingFileBehavior::Error`] is used.
+    ///
+    /// [`MissingFileBehavior::Error`]: crate::MissingFileBehavior::Error
+    #[error("file '{}' not found", path.display())]
+    Missing {
+        /// The path of the missing file.
+        path: PathBuf,
+    },
+
+    /// A symbolic link's target is missing and
+    /// [`BrokenSymlinkBehavior::Error`] is used.
+    ///
+    /// [`BrokenSymlinkBehavior::Error`]: crate::BrokenSymlinkBehavior::Error
+    #[error("broken symbolic link '{}'", path.display())]
+    BrokenSymlink {
+        /// The path of the symbolic link.
+        path: PathBuf,
+    },
+
+    /// A file is a special file and [`SpecialFilePolicy::Error`] is used.
+    ///
+    /// [`SpecialFilePolicy::Error`]: crate::SpecialFilePolicy::Error
+    #[error("special file '{}'", path.display())]
+    SpecialFile {
+        /// The path of the special file.
+        path: PathBuf,
+    },
+
+    /// A file can't be read due to insufficient permissions and
+    /// [`UnreadableFilePolicy::Error`] is used.
+    ///
+    /// [`UnreadableFilePolicy::Error`]: crate::UnreadableFilePolicy::Error
+    #[error("file '{}' is unreadable", path.display())]
+    Unreadable {
+        /// The path of the unreadable file.
+        path: PathBuf,
+        /// The error which occurred when reading the file.
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The [`CompareKey`] of a file is unavailable and there is no fallback
+    /// key.
+    ///
+    /// [`CompareKey`]: crate::CompareKey
+    #[error("{key:?} time of '{}' is unavailable", path.display())]
+    KeyUnavailable {
+        /// The path of the file.
+        path: PathBuf,
+        /// The unavailable key.
+        key: CompareKey,
+        /// The error which occurred when reading the key.
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// Reading the metadata of a file failed.
+    #[error("failed to read metadata of '{}'", path.display())]
+    Io {
+        /// The path of the file.
+        path: PathBuf,
+        /// The error which occurred when reading the file.
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// Comparing the content of two files failed, e.g., because the read
+    /// limit was exceeded with [`ReadLimitBehavior::Error`] or a diff tool
+    /// failed.
+    ///
+    /// [`ReadLimitBehavior::Error`]: crate::ReadLimitBehavior::Error
+    #[error("failed to compare '{}' and '{}'", a.display(), b.display())]
+    Diff {
+        /// The path of the first file.
+        a: PathBuf,
+        /// The path of the second file.
+        b: PathBuf,
+        /// The error which occurred when comparing the files.
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The comparison was aborted by the [`Comparator`]'s [`CancelToken`].
+    ///
+    /// [`Comparator`]: crate::Comparator
+    /// [`CancelToken`]: crate::CancelToken
+    #[error("comparison cancelled")]
+    Cancelled,
+}
+
+impl FcmpError {
+    /// Returns an error for a failed comparison of the content of the given
+    /// paths, or [`FcmpError::Cancelled`] if the comparison was cancelled.
+    ///
+    /// [`FcmpError::Cancelled`]: FcmpError::Cancelled
+    pub(crate) fn diff(a: PathBuf, b: PathBuf, source: std::io::Error)
+        -> Self
+    {
+        if Cancelled::is(&source) {
+            Self::Cancelled
+        } else {
+            Self::Diff { a, b, source }
+        }
+    }
+}
@@ -0,0 +1,194 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Shared extent detection for copy-on-write filesystems.
+////////////////////////////////////////////////////////////////////////////////
+
+// Standard library imports.
+use std::fs::File;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// shares_extents
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the given files are reflinked copies of each other, and
+/// so are known to have the same content without reading either file.
+///
+/// This is the case when both files are on the same device and every extent
+/// of each file is a shared extent mapped to the same physical location in the
+/// other. Returns `false` if the extents can't be queried or differ in any way,
+/// in which case the file contents must be compared.
+#[cfg(all(feature = "reflink", target_os = "linux"))]
+pub(crate) fn shares_extents(a: &File, b: &File) -> bool {
+    use std::os::unix::fs::MetadataExt as _;
+
+    let same_device = match (a.metadata(), b.metadata()) {
+        (Ok(meta_a), Ok(meta_b)) => meta_a.dev() == meta_b.dev(),
+        _ => false,
+    };
+    if !same_device { return false; }
+
+    match (fiemap::extents(a), fiemap::extents(b)) {
+        (Some(extents_a), Some(extents_b)) => !extents_a.is_empty()
+            && extents_a == extents_b
+            && extents_a.iter().all(fiemap::Extent::is_shared),
+        _ => false,
+    }
+}
+
+/// Returns `true` if the given files are reflinked copies of each other.
+///
+/// Extents are only queried on Linux with the `reflink` feature, so `false`
+/// is always returned.
+#[cfg(not(all(feature = "reflink", target_os = "linux")))]
+pub(crate) fn shares_extents(_a: &File, _b: &File) -> bool {
+    false
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// fiemap
+////////////////////////////////////////////////////////////////////////////////
+/// Bindings for the Linux `FS_IOC_FIEMAP` ioctl.
+#[cfg(all(feature = "reflink", target_os = "linux"))]
+mod fiemap {
+    // Standard library imports.
+    use std::fs::File;
+    use std::os::unix::io::AsRawFd as _;
+
+    /// The `FS_IOC_FIEMAP` ioctl request code.
+    const FS_IOC_FIEMAP: u32 = 0xC020_660B;
+    /// Requests that dirty data is flushed before the extents are mapped.
+    const FIEMAP_FLAG_SYNC: u32 = 0x0001;
+    /// Marks the last extent of a file.
+    const FIEMAP_EXTENT_LAST: u32 = 0x0001;
+    /// Marks an extent which is shared with another file.
+    const FIEMAP_EXTENT_SHARED: u32 = 0x2000;
+    /// Extent flags indicating that the physical location does not identify
+    /// the data: unknown, delayed allocation, encoded, encrypted, unaligned,
+    /// inline, tail-packed, or unwritten extents.
+    const FIEMAP_EXTENT_OPAQUE: u32 = 0x0002 | 0x0004 | 0x0008 | 0x0080
+        | 0x0100 | 0x0200 | 0x0400 | 0x0800;
+    /// The number of extents requested by each ioctl call.
+    const EXTENT_BATCH: usize = 64;
+
+    /// A `struct fiemap_extent` as defined in `linux/fiemap.h`.
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    #[allow(clippy::struct_field_names)]
+    struct FiemapExtent {
+        /// The logical offset of the extent within the file.
+        fe_logical: u64,
+        /// The physical offset of the extent on the device.
+        fe_physical: u64,
+        /// The length of the extent in bytes.
+        fe_length: u64,
+        /// Reserved.
+        fe_reserved64: [u64; 2],
+        /// The extent flags.
+        fe_flags: u32,
+        /// Reserved.
+        fe_reserved: [u32; 3],
+    }
+
+    /// A `struct fiemap` as defined in `linux/fiemap.h`, with room for
+    /// `EXTENT_BATCH` extents.
+    #[repr(C)]
+    #[allow(clippy::struct_field_names)]
+    struct Fiemap {
+        /// The logical offset to start mapping from.
+        fm_start: u64,
+        /// The number of bytes to map.
+        fm_length: u64,
+        /// The request flags.
+        fm_flags: u32,
+        /// The number of extents which were mapped.
+        fm_mapped_extents: u32,
+        /// The number of extents which fit in `fm_extents`.
+        fm_extent_count: u32,
+        /// Reserved.
+        fm_reserved: u32,
+        /// The mapped extents.
+        fm_extents: [FiemapExtent; EXTENT_BATCH],
+    }
+
+    /// A mapped file extent.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub(super) struct Extent {
+        /// The logical offset of the extent within the file.
+        logical: u64,
+        /// The physical offset of the extent on the device.
+        physical: u64,
+        /// The length of the extent in bytes.
+        length: u64,
+        /// Whether the extent is shared with another file.
+        shared: bool,
+    }
+
+    impl Extent {
+        /// Returns `true` if the extent is shared with another file.
+        pub(super) const fn is_shared(&self) -> bool {
+            self.shared
+        }
+    }
+
+    /// Returns the extents of the given file, or `None` if they can't be
+    /// mapped or any extent has no meaningful physical location.
+    pub(super) fn extents(file: &File) -> Option<Vec<Extent>> {
+        let mut extents = Vec::new();
+        let mut map = Box::new(Fiemap {
+            fm_start: 0,
+            fm_length: u64::MAX,
+            fm_flags: FIEMAP_FLAG_SYNC,
+            fm_mapped_extents: 0,
+            fm_extent_count: 0,
+            fm_reserved: 0,
+            fm_extents: [FiemapExtent::default(); EXTENT_BATCH],
+        });
+
+        loop {
+            map.fm_length = u64::MAX - map.fm_start;
+            map.fm_mapped_extents = 0;
+            map.fm_extent_count = u32::try_from(EXTENT_BATCH).ok()?;
+
+            #[allow(clippy::cast_lossless, clippy::cast_possible_wrap)]
+            let request = FS_IOC_FIEMAP as libc::Ioctl;
+            // SAFETY: `map` points to a valid, exclusively borrowed `Fiemap`
+            // whose `fm_extent_count` matches the length of `fm_extents`, so
+            // the kernel writes only within it.
+            #[allow(unsafe_code)]
+            let res = unsafe {
+                libc::ioctl(
+                    file.as_raw_fd(),
+                    request,
+                    std::ptr::addr_of_mut!(*map))
+            };
+            if res != 0 { return None; }
+
+            let mapped = usize::try_from(map.fm_mapped_extents).ok()?;
+            if mapped == 0 { return Some(extents); }
+
+            for extent in map.fm_extents.iter().take(mapped) {
+                if extent.fe_flags & FIEMAP_EXTENT_OPAQUE != 0 {
+                    return None;
+                }
+                extents.push(Extent {
+                    logical: extent.fe_logical,
+                    physical: extent.fe_physical,
+                    length: extent.fe_length,
+                    shared: extent.fe_flags & FIEMAP_EXTENT_SHARED != 0,
+                });
+                if extent.fe_flags & FIEMAP_EXTENT_LAST != 0 {
+                    return Some(extents);
+                }
+            }
+
+            let last = map.fm_extents[mapped - 1];
+            map.fm_start = last.fe_logical.checked_add(last.fe_length)?;
+        }
+    }
+}
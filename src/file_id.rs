@@ -0,0 +1,118 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Platform file identity.
+////////////////////////////////////////////////////////////////////////////////
+
+// Standard library imports.
+use std::fs::File;
+#[cfg(unix)]
+use std::fs::Metadata;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FileId
+////////////////////////////////////////////////////////////////////////////////
+/// A platform-specific identifier for an underlying file.
+///
+/// Two paths with the same `FileId` refer to the same file, e.g., because they
+/// are hardlinks or aliases of each other. On unix platforms, this is the
+/// device and inode number of the file. On Windows, this is the volume serial
+/// number and file index reported by `GetFileInformationByHandle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId {
+    /// The device or volume containing the file.
+    device: u64,
+    /// The index of the file within its device or volume.
+    index: u64,
+}
+
+impl FileId {
+    /// Returns the `FileId` of the given open file.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the file's identity can't be
+    /// determined.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    #[cfg(unix)]
+    pub fn from_file(file: &File) -> Result<Self, std::io::Error> {
+        Ok(Self::from_metadata(&file.metadata()?))
+    }
+
+    /// Returns the `FileId` of the given open file.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the file's identity can't be
+    /// determined.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    #[cfg(windows)]
+    pub fn from_file(file: &File) -> Result<Self, std::io::Error> {
+        let info = winapi_util::file::information(file)?;
+        Ok(Self {
+            device: info.volume_serial_number(),
+            index: info.file_index(),
+        })
+    }
+
+    /// Returns the `FileId` of the given open file.
+    ///
+    /// ### Errors
+    ///
+    /// File identities are not supported on this platform, so an error is
+    /// always returned.
+    #[cfg(not(any(unix, windows)))]
+    pub fn from_file(_file: &File) -> Result<Self, std::io::Error> {
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+    }
+
+    /// Returns the `FileId` of the file with the given metadata.
+    #[cfg(unix)]
+    #[must_use]
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt as _;
+
+        Self {
+            device: metadata.dev(),
+            index: metadata.ino(),
+        }
+    }
+
+    /// Returns the `FileId` of the file at the given path.
+    ///
+    /// On unix platforms, the file is not opened.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the file can't be opened or its
+    /// identity can't be determined.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    #[cfg(unix)]
+    pub fn from_path(path: &Path) -> Result<Self, std::io::Error> {
+        Ok(Self::from_metadata(&std::fs::metadata(path)?))
+    }
+
+    /// Returns the `FileId` of the file at the given path.
+    ///
+    /// On unix platforms, the file is not opened.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the file can't be opened or its
+    /// identity can't be determined.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    #[cfg(not(unix))]
+    pub fn from_path(path: &Path) -> Result<Self, std::io::Error> {
+        Self::from_file(&File::open(path)?)
+    }
+}
@@ -0,0 +1,564 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Candidate filter expressions.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::format::FileResult;
+use crate::walk::EntryKind;
+
+// External library imports.
+use glob::Pattern;
+
+// Standard library imports.
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::vec::IntoIter;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FilterExpr
+////////////////////////////////////////////////////////////////////////////////
+/// An expression used to select which files are considered for comparison,
+/// such as `size > 1M && age < 7d`.
+///
+/// An expression is made of conditions combined with `&&`, `||`, `!`, and
+/// parentheses. The available conditions are:
+///
+/// + `size <op> <size>`: The file size, with an optional `K`, `M`, `G`, or
+///   `T` suffix giving a power of 1024.
+/// + `age <op> <duration>`: The time since the file was modified, e.g.,
+///   `7d` or `2h 30m`.
+/// + `mtime <op> <timestamp>`: The file modification time, as an RFC 3339
+///   timestamp, e.g., `2022-01-01T00:00:00Z`.
+/// + `name == <glob>`, `name != <glob>`: Whether the file name matches a glob
+///   pattern.
+/// + `path == <glob>`, `path != <glob>`: Whether the path matches a glob
+///   pattern.
+///
+/// The comparison operators are `<`, `<=`, `>`, `>=`, `==` (or `=`), and
+/// `!=`. Values containing spaces or operator characters may be quoted with
+/// `'` or `"`. Conditions on the size or times of missing files are never
+/// satisfied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterExpr {
+    /// The root of the parsed expression.
+    root: Expr,
+}
+
+impl FilterExpr {
+    /// Returns `true` if the given [`FileResult`] satisfies the expression.
+    ///
+    /// ### Parameters
+    ///
+    /// + `now`: The current time, used to determine file ages.
+    ///
+    /// [`FileResult`]: crate::FileResult
+    #[must_use]
+    pub fn matches(&self, result: &FileResult, now: SystemTime) -> bool {
+        self.root.eval(result, now)
+    }
+}
+
+impl FromStr for FilterExpr {
+    type Err = FilterExprParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?.into_iter().peekable(),
+            end: s.len(),
+        };
+        let root = parser.parse_or()?;
+        if let Some((offset, token)) = parser.tokens.next() {
+            return Err(FilterExprParseError {
+                offset,
+                kind: FilterExprErrorKind::UnexpectedToken(token.to_string()),
+            });
+        }
+        Ok(Self { root })
+    }
+}
+
+/// An error indicating a failure to parse a [`FilterExpr`].
+///
+/// [`FilterExpr`]: FilterExpr
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("at offset {offset}: {kind}")]
+pub struct FilterExprParseError {
+    /// The byte offset in the expression of the token at which the error
+    /// occurred, or the length of the expression if it ended early.
+    pub offset: usize,
+    /// The kind of error.
+    pub kind: FilterExprErrorKind,
+}
+
+/// The kind of a [`FilterExprParseError`].
+///
+/// [`FilterExprParseError`]: FilterExprParseError
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum FilterExprErrorKind {
+    /// The expression ended where a condition or `)` was expected.
+    #[error("unexpected end of expression")]
+    UnexpectedEnd,
+
+    /// A token appeared where it is not allowed.
+    #[error("unexpected '{0}'")]
+    UnexpectedToken(String),
+
+    /// A quoted value is not terminated.
+    #[error("unterminated quote")]
+    UnterminatedQuote,
+
+    /// A condition names an unknown field.
+    #[error("unknown field '{0}'")]
+    UnknownField(String),
+
+    /// A condition uses an operator which the field doesn't support.
+    #[error("'{field}' can't be compared with '{op}'")]
+    UnsupportedOperator {
+        /// The name of the field.
+        field: String,
+        /// The operator.
+        op: String,
+    },
+
+    /// A condition's value can't be parsed for its field.
+    #[error("invalid {field} '{value}': {reason}")]
+    InvalidValue {
+        /// The name of the field.
+        field: String,
+        /// The value.
+        value: String,
+        /// The reason the value is invalid.
+        reason: String,
+    },
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Expr
+////////////////////////////////////////////////////////////////////////////////
+/// A node of a parsed [`FilterExpr`].
+///
+/// [`FilterExpr`]: FilterExpr
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    /// Both expressions are satisfied.
+    And(Box<Self>, Box<Self>),
+    /// Either expression is satisfied.
+    Or(Box<Self>, Box<Self>),
+    /// The expression is not satisfied.
+    Not(Box<Self>),
+    /// The file size compares to the given size.
+    Size(CmpOp, u64),
+    /// The time since modification compares to the given duration.
+    Age(CmpOp, Duration),
+    /// The modification time compares to the given time.
+    Modified(CmpOp, SystemTime),
+    /// The file name matches the pattern, unless negated.
+    Name(bool, Pattern),
+    /// The path matches the pattern, unless negated.
+    Path(bool, Pattern),
+}
+
+impl Expr {
+    /// Returns `true` if the given [`FileResult`] satisfies the expression.
+    ///
+    /// [`FileResult`]: crate::FileResult
+    fn eval(&self, result: &FileResult, now: SystemTime) -> bool {
+        match self {
+            Self::And(a, b) => a.eval(result, now) && b.eval(result, now),
+            Self::Or(a, b)  => a.eval(result, now) || b.eval(result, now),
+            Self::Not(a)    => !a.eval(result, now),
+            Self::Size(op, size) => result.size
+                .is_some_and(|s| op.test(s.cmp(size))),
+            Self::Age(op, age) => result.modified
+                .map(|t| now.duration_since(t).unwrap_or_default())
+                .is_some_and(|a| op.test(a.cmp(age))),
+            Self::Modified(op, time) => result.modified
+                .is_some_and(|t| op.test(t.cmp(time))),
+            Self::Name(negate, pattern) => result.path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| pattern.matches(n) != *negate),
+            Self::Path(negate, pattern) => pattern
+                .matches_path(&result.path) != *negate,
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// CmpOp
+////////////////////////////////////////////////////////////////////////////////
+/// A comparison operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp {
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+}
+
+impl std::fmt::Display for CmpOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+        })
+    }
+}
+
+impl CmpOp {
+    /// Returns `true` if the given ordering satisfies the operator.
+    fn test(self, ordering: Ordering) -> bool {
+        match self {
+            Self::Lt => ordering == Ordering::Less,
+            Self::Le => ordering != Ordering::Greater,
+            Self::Gt => ordering == Ordering::Greater,
+            Self::Ge => ordering != Ordering::Less,
+            Self::Eq => ordering == Ordering::Equal,
+            Self::Ne => ordering != Ordering::Equal,
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Parsing
+////////////////////////////////////////////////////////////////////////////////
+/// A token of a filter expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// `!`
+    Not,
+    /// `(`
+    Open,
+    /// `)`
+    Close,
+    /// A comparison operator.
+    Op(CmpOp),
+    /// A field name or value.
+    Word(String),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::And     => f.write_str("&&"),
+            Self::Or      => f.write_str("||"),
+            Self::Not     => f.write_str("!"),
+            Self::Open    => f.write_str("("),
+            Self::Close   => f.write_str(")"),
+            Self::Op(op)  => write!(f, "{op}"),
+            Self::Word(w) => f.write_str(w),
+        }
+    }
+}
+
+/// Splits a filter expression into tokens, each with its byte offset in the
+/// expression.
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, FilterExprParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    let error = |offset, kind| FilterExprParseError { offset, kind };
+
+    while let Some((offset, c)) = chars.next() {
+        let next_eq = chars.peek().map(|(_, c)| *c) == Some('=');
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' if chars.next_if(|(_, c)| *c == '&').is_some() => Token::And,
+            '|' if chars.next_if(|(_, c)| *c == '|').is_some() => Token::Or,
+            '!' if !next_eq => Token::Not,
+            '<' | '>' | '=' | '!' => {
+                if next_eq { let _ = chars.next(); }
+                Token::Op(match (c, next_eq) {
+                    ('<', false) => CmpOp::Lt,
+                    ('<', true)  => CmpOp::Le,
+                    ('>', false) => CmpOp::Gt,
+                    ('>', true)  => CmpOp::Ge,
+                    ('=', _)     => CmpOp::Eq,
+                    _            => CmpOp::Ne,
+                })
+            },
+            '\'' | '"' => {
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => break,
+                        Some((_, w)) => word.push(w),
+                        None => return Err(error(
+                            offset,
+                            FilterExprErrorKind::UnterminatedQuote)),
+                    }
+                }
+                Token::Word(word)
+            },
+            '&' | '|' => return Err(error(
+                offset,
+                FilterExprErrorKind::UnexpectedToken(c.to_string()))),
+            _ => {
+                let mut word = c.to_string();
+                while let Some((_, w)) = chars
+                    .next_if(|(_, w)| is_word_char(*w))
+                {
+                    word.push(w);
+                }
+                Token::Word(word)
+            },
+        };
+        tokens.push((offset, token));
+    }
+    Ok(tokens)
+}
+
+/// Returns `true` if the given character may appear in an unquoted word.
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace() && !"()&|!<>=\"'".contains(c)
+}
+
+/// A recursive descent parser over the tokens of a filter expression. `&&`
+/// binds more tightly than `||`, and `!` more tightly than both.
+struct Parser {
+    /// The remaining tokens, with their byte offsets in the expression.
+    tokens: Peekable<IntoIter<(usize, Token)>>,
+    /// The length of the expression, reported for errors at its end.
+    end: usize,
+}
+
+impl Parser {
+    /// Returns the next token, or an error if the expression has ended.
+    fn next(&mut self) -> Result<(usize, Token), FilterExprParseError> {
+        self.tokens.next().ok_or(FilterExprParseError {
+            offset: self.end,
+            kind: FilterExprErrorKind::UnexpectedEnd,
+        })
+    }
+
+    /// Consumes the next token if it is the given token.
+    fn next_if_eq(&mut self, token: &Token) -> bool {
+        self.tokens.next_if(|(_, t)| t == token).is_some()
+    }
+
+    /// Parses a disjunction of conjunctions.
+    fn parse_or(&mut self) -> Result<Expr, FilterExprParseError> {
+        let mut expr = self.parse_and()?;
+        while self.next_if_eq(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    /// Parses a conjunction of unary expressions.
+    fn parse_and(&mut self) -> Result<Expr, FilterExprParseError> {
+        let mut expr = self.parse_unary()?;
+        while self.next_if_eq(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    /// Parses a negation, parenthesized expression, or condition.
+    fn parse_unary(&mut self) -> Result<Expr, FilterExprParseError> {
+        match self.next()? {
+            (_, Token::Not) => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            (_, Token::Open) => {
+                let expr = self.parse_or()?;
+                match self.next()? {
+                    (_, Token::Close) => Ok(expr),
+                    (offset, token) => Err(unexpected(offset, &token)),
+                }
+            },
+            (field_offset, Token::Word(field)) => {
+                let (op_offset, op) = match self.next()? {
+                    (offset, Token::Op(op)) => (offset, op),
+                    (offset, token) => return Err(unexpected(offset, &token)),
+                };
+                let (value_offset, value) = match self.next()? {
+                    (offset, Token::Word(value)) => (offset, value),
+                    (offset, token) => return Err(unexpected(offset, &token)),
+                };
+                parse_condition(&field, op, &value).map_err(|kind| {
+                    // Report the error at the part of the condition at fault.
+                    let offset = match kind {
+                        FilterExprErrorKind::UnknownField(_) => field_offset,
+                        FilterExprErrorKind::UnsupportedOperator { .. }
+                            => op_offset,
+                        _ => value_offset,
+                    };
+                    FilterExprParseError { offset, kind }
+                })
+            },
+            (offset, token) => Err(unexpected(offset, &token)),
+        }
+    }
+}
+
+/// Returns an error for the given unexpected token at the given offset.
+fn unexpected(offset: usize, token: &Token) -> FilterExprParseError {
+    FilterExprParseError {
+        offset,
+        kind: FilterExprErrorKind::UnexpectedToken(token.to_string()),
+    }
+}
+
+/// Parses a condition comparing the given field to the given value.
+fn parse_condition(field: &str, op: CmpOp, value: &str)
+    -> Result<Expr, FilterExprErrorKind>
+{
+    let invalid = |reason: &dyn std::fmt::Display| {
+        FilterExprErrorKind::InvalidValue {
+            field: field.to_string(),
+            value: value.to_string(),
+            reason: reason.to_string(),
+        }
+    };
+    let pattern = |value: &str| match op {
+        CmpOp::Eq | CmpOp::Ne => Pattern::new(value)
+            .map(|p| (op == CmpOp::Ne, p))
+            .map_err(|e| invalid(&e)),
+        _ => Err(FilterExprErrorKind::UnsupportedOperator {
+            field: field.to_string(),
+            op: op.to_string(),
+        }),
+    };
+
+    if field.eq_ignore_ascii_case("size") {
+        parse_size(value)
+            .map(|size| Expr::Size(op, size))
+            .map_err(|e| invalid(&e))
+    } else if field.eq_ignore_ascii_case("age") {
+        humantime::parse_duration(value)
+            .map(|age| Expr::Age(op, age))
+            .map_err(|e| invalid(&e))
+    } else if field.eq_ignore_ascii_case("mtime") {
+        humantime::parse_rfc3339_weak(value)
+            .map(|time| Expr::Modified(op, time))
+            .map_err(|e| invalid(&e))
+    } else if field.eq_ignore_ascii_case("name") {
+        pattern(value).map(|(negate, p)| Expr::Name(negate, p))
+    } else if field.eq_ignore_ascii_case("path") {
+        pattern(value).map(|(negate, p)| Expr::Path(negate, p))
+    } else {
+        Err(FilterExprErrorKind::UnknownField(field.to_string()))
+    }
+}
+
+/// Parses a size in bytes, with an optional `K`, `M`, `G`, or `T` suffix
+/// giving a power of 1024.
+///
+/// ### Errors
+///
+/// Returns a [`SizeParseError`] if the size is not a number with an optional
+/// suffix, or is too large.
+///
+/// [`SizeParseError`]: SizeParseError
+pub fn parse_size(value: &str) -> Result<u64, SizeParseError> {
+    let digits = value.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let scale: u64 = match &value[digits.len()..] {
+        "" | "B" | "b" => 1,
+        s if s.eq_ignore_ascii_case("k") => 1 << 10,
+        s if s.eq_ignore_ascii_case("m") => 1 << 20,
+        s if s.eq_ignore_ascii_case("g") => 1 << 30,
+        s if s.eq_ignore_ascii_case("t") => 1 << 40,
+        _ => return Err(SizeParseError),
+    };
+    digits.parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(scale))
+        .ok_or(SizeParseError)
+}
+
+/// An error indicating a failure to parse a size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeParseError;
+
+impl std::error::Error for SizeParseError {}
+
+impl std::fmt::Display for SizeParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected a number of bytes with an optional K, M, G, or T \
+            suffix, below 16 EiB")
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PathFilter
+////////////////////////////////////////////////////////////////////////////////
+/// A predicate used to select which paths are considered for comparison.
+///
+/// Paths which are not accepted are excluded as if they were ignored.
+pub trait PathFilter: std::fmt::Debug + Send + Sync {
+    /// Returns `true` if the given path should be considered for comparison.
+    fn accepts(&self, path: &Path) -> bool;
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FileTypeFilter
+////////////////////////////////////////////////////////////////////////////////
+/// A [`PathFilter`] which accepts only files of the given [`EntryKind`]s.
+///
+/// Symbolic links are accepted if `EntryKind::Symlink` is given or if the
+/// kind of their target is given. Missing files are always accepted, so that
+/// the missing file behavior applies to them.
+///
+/// [`PathFilter`]: PathFilter
+/// [`EntryKind`]: crate::EntryKind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileTypeFilter {
+    /// The accepted kinds of file.
+    kinds: Vec<EntryKind>,
+}
+
+impl FileTypeFilter {
+    /// Returns a `FileTypeFilter` accepting the given kinds of file.
+    #[must_use]
+    pub fn new<K>(kinds: K) -> Self
+        where K: IntoIterator<Item=EntryKind>
+    {
+        Self {
+            kinds: kinds.into_iter().collect(),
+        }
+    }
+}
+
+impl PathFilter for FileTypeFilter {
+    fn accepts(&self, path: &Path) -> bool {
+        let Ok(link) = std::fs::symlink_metadata(path) else { return true };
+        if self.kinds.contains(&EntryKind::from(link.file_type())) {
+            return true;
+        }
+        link.file_type().is_symlink() && std::fs::metadata(path)
+            .is_ok_and(|m| self.kinds.contains(&m.file_type().into()))
+    }
+}
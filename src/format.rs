@@ -0,0 +1,534 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Result output formatting.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::compare::FileCmp;
+
+// External library imports.
+use jiff::Timestamp;
+use jiff::tz::TimeZone;
+
+// Standard library imports.
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FileResult
+////////////////////////////////////////////////////////////////////////////////
+/// A file selected by a comparison, along with the field values available for
+/// output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileResult {
+    /// The index of the file within the compared paths.
+    pub index: usize,
+    /// The path of the file.
+    pub path: PathBuf,
+    /// Whether the file was found.
+    pub found: bool,
+    /// The modification time of the file, if it exists.
+    pub modified: Option<SystemTime>,
+    /// The last access time of the file, if it exists.
+    pub accessed: Option<SystemTime>,
+    /// The status change time of the file, if it exists and the platform
+    /// supports status change times.
+    pub changed: Option<SystemTime>,
+    /// The size of the file in bytes, if it exists.
+    pub size: Option<u64>,
+}
+
+impl FileResult {
+    /// Returns the `FileResult` for the file at the given path and index. The
+    /// time and size fields are `None` if the file can't be read.
+    #[must_use]
+    pub fn new(index: usize, path: &Path) -> Self {
+        let file = FileCmp::try_from(path).ok();
+        Self {
+            index,
+            path: path.to_path_buf(),
+            found: file.as_ref().is_some_and(FileCmp::is_found),
+            modified: file.as_ref().and_then(FileCmp::modified),
+            accessed: file.as_ref().and_then(FileCmp::accessed),
+            changed: file.as_ref().and_then(FileCmp::changed),
+            size: file.as_ref().and_then(FileCmp::size),
+        }
+    }
+
+    /// Returns the value of the given field, or `None` if it is not
+    /// available. Times are written in the given [`TimeStyle`], relative to
+    /// the given current time.
+    ///
+    /// [`TimeStyle`]: TimeStyle
+    #[must_use]
+    pub fn field(&self, field: Field, style: TimeStyle, now: SystemTime)
+        -> Option<String>
+    {
+        let time = |t: Option<SystemTime>| t.map(|t| style.format(t, now));
+
+        match field {
+            Field::Index    => Some(self.index.to_string()),
+            Field::Path     => Some(self.path.display().to_string()),
+            Field::Name     => self.path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned()),
+            Field::Modified => time(self.modified),
+            Field::Accessed => time(self.accessed),
+            Field::Changed  => time(self.changed),
+            Field::Size     => self.size.map(|s| s.to_string()),
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Field
+////////////////////////////////////////////////////////////////////////////////
+/// A field of a [`FileResult`] which can be included in formatted output.
+///
+/// [`FileResult`]: FileResult
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    /// The index of the file within the compared paths.
+    Index,
+    /// The path of the file.
+    Path,
+    /// The file name of the file.
+    Name,
+    /// The modification time of the file.
+    Modified,
+    /// The last access time of the file.
+    Accessed,
+    /// The status change time of the file.
+    Changed,
+    /// The size of the file in bytes.
+    Size,
+}
+
+impl FromStr for Field {
+    type Err = FieldParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("index") {
+            Ok(Self::Index)
+        } else if s.eq_ignore_ascii_case("path") {
+            Ok(Self::Path)
+        } else if s.eq_ignore_ascii_case("name") {
+            Ok(Self::Name)
+        } else if s.eq_ignore_ascii_case("mtime") {
+            Ok(Self::Modified)
+        } else if s.eq_ignore_ascii_case("atime") {
+            Ok(Self::Accessed)
+        } else if s.eq_ignore_ascii_case("ctime") {
+            Ok(Self::Changed)
+        } else if s.eq_ignore_ascii_case("size") {
+            Ok(Self::Size)
+        } else {
+            Err(FieldParseError)
+        }
+    }
+}
+
+/// An error indicating a failure to parse a [`Field`].
+///
+/// [`Field`]: Field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldParseError;
+
+impl std::error::Error for FieldParseError {}
+
+impl std::fmt::Display for FieldParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse Field")
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// OutputFormat
+////////////////////////////////////////////////////////////////////////////////
+/// A template for formatting a [`FileResult`].
+///
+/// Templates contain field names in braces, such as `{path}\t{size}`. The
+/// available fields are `index`, `path`, `name`, `mtime`, `atime`, `ctime`,
+/// and `size`. Unavailable values are written as `-`. Literal braces are
+/// written as `{{` and `}}`, and the escapes `\t`, `\n`, `\0`, and `\\` are
+/// recognized.
+///
+/// [`FileResult`]: FileResult
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputFormat {
+    /// The parsed template segments.
+    segments: Vec<Segment>,
+}
+
+/// A segment of an [`OutputFormat`] template.
+///
+/// [`OutputFormat`]: OutputFormat
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// Literal text.
+    Literal(String),
+    /// A field value.
+    Field(Field),
+}
+
+impl OutputFormat {
+    /// Returns an `OutputFormat` which writes the given fields separated by
+    /// tabs.
+    #[must_use]
+    pub fn from_fields<I>(fields: I) -> Self
+        where I: IntoIterator<Item=Field>
+    {
+        let mut segments = Vec::new();
+        for field in fields {
+            if !segments.is_empty() {
+                segments.push(Segment::Literal("\t".to_string()));
+            }
+            segments.push(Segment::Field(field));
+        }
+        Self { segments }
+    }
+
+    /// Returns the formatted output for the given [`FileResult`], writing
+    /// times in the given [`TimeStyle`] relative to the given current time.
+    ///
+    /// [`FileResult`]: FileResult
+    /// [`TimeStyle`]: TimeStyle
+    #[must_use]
+    pub fn format(&self, result: &FileResult, style: TimeStyle, now: SystemTime)
+        -> String
+    {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(s) => out.push_str(s),
+                Segment::Field(field) => out.push_str(result
+                    .field(*field, style, now)
+                    .as_deref()
+                    .unwrap_or("-")),
+            }
+        }
+        out
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = OutputFormatParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    let _ = chars.next();
+                    literal.push('{');
+                },
+                '}' if chars.as_str().starts_with('}') => {
+                    let _ = chars.next();
+                    literal.push('}');
+                },
+                '{' => {
+                    let (name, rest) = chars.as_str()
+                        .split_once('}')
+                        .ok_or(OutputFormatParseError)?;
+                    let field = name.parse()
+                        .map_err(|_e| OutputFormatParseError)?;
+                    chars = rest.chars();
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(
+                            std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(field));
+                },
+                '}' => return Err(OutputFormatParseError),
+                '\\' => match chars.next() {
+                    Some('t')  => literal.push('\t'),
+                    Some('n')  => literal.push('\n'),
+                    Some('0')  => literal.push('\0'),
+                    Some('\\') => literal.push('\\'),
+                    _          => return Err(OutputFormatParseError),
+                },
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() { segments.push(Segment::Literal(literal)); }
+
+        Ok(Self { segments })
+    }
+}
+
+/// An error indicating a failure to parse an [`OutputFormat`].
+///
+/// [`OutputFormat`]: OutputFormat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputFormatParseError;
+
+impl std::error::Error for OutputFormatParseError {}
+
+impl std::fmt::Display for OutputFormatParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse OutputFormat")
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// TableFormat
+////////////////////////////////////////////////////////////////////////////////
+/// A delimited table format for writing ranked [`FileResult`]s, with one row
+/// per file.
+///
+/// Each row contains the path, modification time, size, and (1-based) rank of
+/// a file.
+///
+/// [`FileResult`]: FileResult
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(clap::ArgEnum)]
+pub enum TableFormat {
+    /// Comma-separated values, quoted as described in RFC 4180.
+    Csv,
+    /// Tab-separated values. Tabs, newlines, and backslashes within values
+    /// are escaped with a backslash.
+    Tsv,
+}
+
+impl TableFormat {
+    /// The names of the table columns.
+    const COLUMNS: [&'static str; 4] = ["path", "mtime", "size", "rank"];
+
+    /// Returns the header row of the table.
+    #[must_use]
+    pub fn header(self) -> String {
+        self.row_from(Self::COLUMNS.iter().map(|c| (*c).to_string()))
+    }
+
+    /// Returns the table row for the given [`FileResult`] and rank, writing
+    /// times in the given [`TimeStyle`] relative to the given current time.
+    ///
+    /// [`FileResult`]: FileResult
+    /// [`TimeStyle`]: TimeStyle
+    #[must_use]
+    pub fn row(
+        self,
+        result: &FileResult,
+        rank: usize,
+        style: TimeStyle,
+        now: SystemTime)
+        -> String
+    {
+        let field = |field| result.field(field, style, now);
+        self.row_from([
+            field(Field::Path),
+            field(Field::Modified),
+            field(Field::Size),
+            Some(rank.to_string()),
+        ].into_iter().map(Option::unwrap_or_default))
+    }
+
+    /// Returns a table row containing the given values.
+    fn row_from<I>(self, values: I) -> String
+        where I: IntoIterator<Item=String>
+    {
+        let (separator, escape): (&str, fn(&str) -> String) = match self {
+            Self::Csv => (",", escape_csv),
+            Self::Tsv => ("\t", escape_tsv),
+        };
+        values.into_iter()
+            .map(|v| escape(&v))
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+}
+
+impl FromStr for TableFormat {
+    type Err = TableFormatParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("csv") {
+            Ok(Self::Csv)
+        } else if s.eq_ignore_ascii_case("tsv") {
+            Ok(Self::Tsv)
+        } else {
+            Err(TableFormatParseError)
+        }
+    }
+}
+
+/// An error indicating a failure to parse a [`TableFormat`].
+///
+/// [`TableFormat`]: TableFormat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableFormatParseError;
+
+impl std::error::Error for TableFormatParseError {}
+
+impl std::fmt::Display for TableFormatParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse TableFormat")
+    }
+}
+
+/// Returns the given value quoted for a CSV field, if necessary.
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Returns the given value escaped for a TSV field.
+fn escape_tsv(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// TimeFormat
+////////////////////////////////////////////////////////////////////////////////
+/// The format used to write times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(clap::ArgEnum)]
+pub enum TimeFormat {
+    /// An ISO 8601 timestamp, e.g., `2024-01-05T12:30:00+01:00`.
+    #[default]
+    Iso8601,
+    /// The number of seconds since the Unix epoch.
+    Epoch,
+    /// The time relative to the current time, e.g., `3 minutes ago`.
+    Relative,
+}
+
+impl FromStr for TimeFormat {
+    type Err = TimeFormatParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("iso8601") {
+            Ok(Self::Iso8601)
+        } else if s.eq_ignore_ascii_case("epoch") {
+            Ok(Self::Epoch)
+        } else if s.eq_ignore_ascii_case("relative") {
+            Ok(Self::Relative)
+        } else {
+            Err(TimeFormatParseError)
+        }
+    }
+}
+
+/// An error indicating a failure to parse a [`TimeFormat`].
+///
+/// [`TimeFormat`]: TimeFormat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeFormatParseError;
+
+impl std::error::Error for TimeFormatParseError {}
+
+impl std::fmt::Display for TimeFormatParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse TimeFormat")
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// TimeStyle
+////////////////////////////////////////////////////////////////////////////////
+/// How times are written in output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TimeStyle {
+    /// The [`TimeFormat`] used to write times.
+    ///
+    /// [`TimeFormat`]: TimeFormat
+    pub format: TimeFormat,
+    /// Whether ISO 8601 timestamps are written in UTC instead of the local
+    /// time zone.
+    pub utc: bool,
+}
+
+impl TimeStyle {
+    /// Returns the given time written in this style. Relative times are
+    /// relative to the given current time.
+    #[must_use]
+    pub fn format(self, time: SystemTime, now: SystemTime) -> String {
+        // Times outside of the supported range are written as RFC 3339.
+        let Ok(timestamp) = Timestamp::try_from(time) else {
+            return humantime::format_rfc3339(time).to_string();
+        };
+        match self.format {
+            TimeFormat::Iso8601 => {
+                let offset = if self.utc {
+                    jiff::tz::Offset::UTC
+                } else {
+                    TimeZone::system().to_offset(timestamp)
+                };
+                // UTC times are written with a `Z` suffix.
+                if offset == jiff::tz::Offset::UTC {
+                    timestamp.to_string()
+                } else {
+                    timestamp.display_with_offset(offset).to_string()
+                }
+            },
+            TimeFormat::Epoch => timestamp.as_second().to_string(),
+            TimeFormat::Relative => relative(time, now),
+        }
+    }
+
+    /// Returns the given time written in this style without its fractional
+    /// seconds, for output which isn't cluttered with sub-second precision.
+    #[must_use]
+    pub fn format_seconds(self, time: SystemTime, now: SystemTime) -> String {
+        let time = time.duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(time, |since| SystemTime::UNIX_EPOCH
+                + Duration::from_secs(since.as_secs()));
+        self.format(time, now)
+    }
+}
+
+/// Returns the given time written relative to the given current time in its
+/// largest whole unit, e.g., `3 minutes ago` or `in 2 days`.
+fn relative(time: SystemTime, now: SystemTime) -> String {
+    const UNITS: [(&str, u64); 6] = [
+        ("year",   365 * 24 * 60 * 60),
+        ("month",  30 * 24 * 60 * 60),
+        ("day",    24 * 60 * 60),
+        ("hour",   60 * 60),
+        ("minute", 60),
+        ("second", 1),
+    ];
+
+    let (secs, future) = match now.duration_since(time) {
+        Ok(elapsed) => (elapsed.as_secs(), false),
+        Err(e) => (e.duration().as_secs(), true),
+    };
+    let Some((unit, count)) = UNITS
+        .iter()
+        .find(|(_, len)| secs >= *len)
+        .map(|(unit, len)| (unit, secs / len))
+    else {
+        return "just now".to_string();
+    };
+
+    let plural = if count == 1 { "" } else { "s" };
+    if future {
+        format!("in {count} {unit}{plural}")
+    } else {
+        format!("{count} {unit}{plural} ago")
+    }
+}
@@ -0,0 +1,274 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! File system abstraction.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::walk::EntryKind;
+
+// Standard library imports.
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::Cursor;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::PoisonError;
+use std::sync::RwLock;
+use std::sync::RwLockReadGuard;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FsMetadata
+////////////////////////////////////////////////////////////////////////////////
+/// The metadata of a file provided by a [`FileSystem`].
+///
+/// [`FileSystem`]: FileSystem
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsMetadata {
+    /// The kind of the file.
+    pub kind: EntryKind,
+    /// The size of the file in bytes.
+    pub len: u64,
+    /// The modification time of the file, if it is recorded.
+    pub modified: Option<SystemTime>,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FileSystem
+////////////////////////////////////////////////////////////////////////////////
+/// A source of files to compare.
+///
+/// A `FileSystem` given to a [`Comparator`] replaces the local file system
+/// for reading the metadata and content of the compared files, and may be
+/// traversed with [`walk_fs`].
+///
+/// [`Comparator`]: crate::Comparator
+/// [`walk_fs`]: crate::walk_fs
+pub trait FileSystem: std::fmt::Debug + Send + Sync {
+    /// Returns the metadata of the file at the given path, following
+    /// symbolic links, or `None` if there is no such file.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the metadata can't be read.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    fn metadata(&self, path: &Path)
+        -> Result<Option<FsMetadata>, std::io::Error>;
+
+    /// Opens the file at the given path for reading, or returns `None` if
+    /// there is no such file.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the file can't be opened.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    fn open(&self, path: &Path)
+        -> Result<Option<Box<dyn Read + Send>>, std::io::Error>;
+
+    /// Returns the names and kinds of the entries of the directory at the
+    /// given path, sorted by name.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the directory can't be read.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    fn read_dir(&self, path: &Path)
+        -> Result<Vec<(OsString, EntryKind)>, std::io::Error>;
+
+    /// Returns the content of the file at the given path, or `None` if there
+    /// is no such file.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the file can't be read.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    fn read(&self, path: &Path) -> Result<Option<Vec<u8>>, std::io::Error> {
+        let Some(mut reader) = self.open(path)? else { return Ok(None) };
+        let mut content = Vec::new();
+        let _ = reader.read_to_end(&mut content)?;
+        Ok(Some(content))
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// StdFileSystem
+////////////////////////////////////////////////////////////////////////////////
+/// The local file system, accessed through `std::fs`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdFileSystem;
+
+impl FileSystem for StdFileSystem {
+    fn metadata(&self, path: &Path)
+        -> Result<Option<FsMetadata>, std::io::Error>
+    {
+        match std::fs::metadata(path) {
+            Ok(metadata) => Ok(Some(FsMetadata {
+                kind: metadata.file_type().into(),
+                len: metadata.len(),
+                modified: metadata.modified().ok(),
+            })),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn open(&self, path: &Path)
+        -> Result<Option<Box<dyn Read + Send>>, std::io::Error>
+    {
+        match std::fs::File::open(path) {
+            Ok(file) => Ok(Some(Box::new(file))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn read_dir(&self, path: &Path)
+        -> Result<Vec<(OsString, EntryKind)>, std::io::Error>
+    {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            entries.push((entry.file_name(), entry.file_type()?.into()));
+        }
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(entries)
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// MemoryFileSystem
+////////////////////////////////////////////////////////////////////////////////
+/// A [`FileSystem`] holding files in memory, for testing comparisons without
+/// touching the disk.
+///
+/// Directories are not stored, and exist whenever they contain a file.
+///
+/// [`FileSystem`]: FileSystem
+#[derive(Debug, Default)]
+pub struct MemoryFileSystem {
+    /// The files, by path.
+    files: RwLock<BTreeMap<PathBuf, MemoryFile>>,
+}
+
+/// A file held by a [`MemoryFileSystem`].
+///
+/// [`MemoryFileSystem`]: MemoryFileSystem
+#[derive(Debug, Clone)]
+struct MemoryFile {
+    /// The content of the file.
+    content: Arc<[u8]>,
+    /// The modification time of the file.
+    modified: SystemTime,
+}
+
+impl MemoryFileSystem {
+    /// Returns an empty `MemoryFileSystem`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file with the given content and modification time, replacing
+    /// any file at the same path.
+    pub fn insert<P, C>(&self, path: P, content: C, modified: SystemTime)
+        where
+            P: Into<PathBuf>,
+            C: Into<Vec<u8>>,
+    {
+        let file = MemoryFile { content: content.into().into(), modified };
+        let _ = self.files
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(path.into(), file);
+    }
+
+    /// Removes the file at the given path, returning `true` if it existed.
+    pub fn remove(&self, path: &Path) -> bool {
+        self.files
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(path)
+            .is_some()
+    }
+
+    /// Locks the files for reading. A lock poisoned by a panicking thread is
+    /// still used, as no update leaves the files inconsistent.
+    fn files(&self) -> RwLockReadGuard<'_, BTreeMap<PathBuf, MemoryFile>> {
+        self.files.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns `true` if any file is inside the directory at the given path.
+    fn is_dir(&self, path: &Path) -> bool {
+        self.files().keys().any(|p| p != path && p.starts_with(path))
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn metadata(&self, path: &Path)
+        -> Result<Option<FsMetadata>, std::io::Error>
+    {
+        if let Some(file) = self.files().get(path) {
+            return Ok(Some(FsMetadata {
+                kind: EntryKind::File,
+                len: file.content.len() as u64,
+                modified: Some(file.modified),
+            }));
+        }
+        Ok(self.is_dir(path).then_some(FsMetadata {
+            kind: EntryKind::Dir,
+            len: 0,
+            modified: None,
+        }))
+    }
+
+    fn open(&self, path: &Path)
+        -> Result<Option<Box<dyn Read + Send>>, std::io::Error>
+    {
+        Ok(self.files()
+            .get(path)
+            .map(|file| -> Box<dyn Read + Send> {
+                Box::new(Cursor::new(Arc::clone(&file.content)))
+            }))
+    }
+
+    fn read_dir(&self, path: &Path)
+        -> Result<Vec<(OsString, EntryKind)>, std::io::Error>
+    {
+        if !self.is_dir(path) {
+            return Err(ErrorKind::NotFound.into());
+        }
+
+        let mut entries: BTreeMap<OsString, EntryKind> = BTreeMap::new();
+        for file in self.files().keys() {
+            let Ok(rest) = file.strip_prefix(path) else { continue };
+            let mut components = rest.components();
+            let Some(Component::Normal(name)) = components.next() else {
+                continue;
+            };
+            let kind = if components.next().is_some() {
+                EntryKind::Dir
+            } else {
+                EntryKind::File
+            };
+            let _ = entries.insert(name.to_os_string(), kind);
+        }
+        Ok(entries.into_iter().collect())
+    }
+}
@@ -0,0 +1,205 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Files read from a git repository's object database.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+use git2::ObjectType;
+use git2::Repository;
+
+// Standard library imports.
+use std::io::ErrorKind;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The separator between the revision and the path of a blob, as in
+/// `HEAD:src/config.rs`.
+pub const REVISION_SEPARATOR: char = ':';
+
+
+////////////////////////////////////////////////////////////////////////////////
+// BlobMetadata
+////////////////////////////////////////////////////////////////////////////////
+/// The metadata of a file in a git repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobMetadata {
+    /// The size of the blob in bytes.
+    pub len: u64,
+    /// The commit time of the revision the blob was read from, if the
+    /// revision is a commit.
+    pub modified: Option<SystemTime>,
+    /// The object ID of the blob.
+    pub id: String,
+}
+
+impl BlobMetadata {
+    /// Returns `true` if both blobs have the same object ID, which means the
+    /// files have identical content.
+    #[must_use]
+    pub fn same_id(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// GitBlob
+////////////////////////////////////////////////////////////////////////////////
+/// A file in a git repository, written as a revision and a path separated by
+/// `:`, as in `HEAD:src/config.rs`.
+///
+/// As with `git show`, the path is relative to the root of the repository
+/// containing the current directory, unless it starts with `./` or `../`, in
+/// which case it is relative to the current directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitBlob {
+    /// The revision the file is read from, such as `HEAD` or `v1.0~2`.
+    pub rev: String,
+    /// The path of the file inside the revision.
+    pub path: String,
+}
+
+impl GitBlob {
+    /// Parses a `GitBlob` from the given path. Returns `None` if the path
+    /// does not contain a revision separator following a revision, is a
+    /// URL or Windows drive path, or names an existing local file.
+    #[must_use]
+    pub fn parse(path: &Path) -> Option<Self> {
+        let text = path.to_str()?;
+        if text.contains("://") { return None; }
+        let (rev, blob_path) = text.split_once(REVISION_SEPARATOR)?;
+        let is_drive = rev.len() == 1 && rev.starts_with(char::is_alphabetic);
+        if rev.is_empty() || blob_path.is_empty() || is_drive {
+            return None;
+        }
+        if path.exists() { return None; }
+
+        Some(Self {
+            rev: rev.to_string(),
+            path: blob_path.to_string(),
+        })
+    }
+
+    /// Returns the metadata of the file, or `None` if the revision has no
+    /// such file.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the current directory is not in a git
+    /// repository or the revision can't be resolved.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn metadata(&self) -> Result<Option<BlobMetadata>, std::io::Error> {
+        self.with_blob(|blob, modified| BlobMetadata {
+            len: blob.size() as u64,
+            modified,
+            id: blob.id().to_string(),
+        })
+    }
+
+    /// Returns the content of the file, or `None` if the revision has no
+    /// such file.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the current directory is not in a git
+    /// repository or the revision can't be resolved.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn read(&self) -> Result<Option<Vec<u8>>, std::io::Error> {
+        self.with_blob(|blob, _| blob.content().to_vec())
+    }
+
+    /// Calls the given function with the blob and the commit time of the
+    /// revision, returning its result, or `None` if the revision has no such
+    /// file.
+    fn with_blob<F, T>(&self, f: F) -> Result<Option<T>, std::io::Error>
+        where F: FnOnce(&git2::Blob<'_>, Option<SystemTime>) -> T
+    {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(rev = %self.rev, path = %self.path, "reading git blob");
+        let repo = Repository::discover(".").map_err(|e| git_error(&e))?;
+        let object = repo.revparse_single(&self.rev)
+            .map_err(|e| git_error(&e))?;
+        let modified = object.peel_to_commit().ok().and_then(|commit| {
+            let secs = u64::try_from(commit.time().seconds()).ok()?;
+            SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+        });
+        let tree = object.peel_to_tree().map_err(|e| git_error(&e))?;
+
+        let Some(path) = self.repo_path(&repo)? else { return Ok(None) };
+        let entry = match tree.get_path(&path) {
+            Ok(entry) => entry,
+            Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+            Err(e) => return Err(git_error(&e)),
+        };
+        if entry.kind() != Some(ObjectType::Blob) { return Ok(None); }
+        let blob = repo.find_blob(entry.id()).map_err(|e| git_error(&e))?;
+        Ok(Some(f(&blob, modified)))
+    }
+
+    /// Returns the path of the file relative to the root of the repository,
+    /// or `None` if it is outside of the repository.
+    fn repo_path(&self, repo: &Repository)
+        -> Result<Option<PathBuf>, std::io::Error>
+    {
+        let path = Path::new(&self.path);
+        let relative_to_cwd = matches!(
+            path.components().next(),
+            Some(Component::CurDir | Component::ParentDir));
+        if !relative_to_cwd {
+            return Ok(normalize(Path::new(""), path));
+        }
+
+        let Some(workdir) = repo.workdir() else {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("{}: repository has no working tree", self.path)));
+        };
+        let cwd = std::env::current_dir()?.canonicalize()?;
+        let workdir = workdir.canonicalize()?;
+        Ok(cwd.strip_prefix(&workdir)
+            .ok()
+            .and_then(|base| normalize(base, path)))
+    }
+}
+
+/// Joins the given path to the given base, resolving `.` and `..`
+/// components, or returns `None` if the result would leave the base's root.
+fn normalize(base: &Path, path: &Path) -> Option<PathBuf> {
+    let mut normalized = base.to_path_buf();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => if !normalized.pop() { return None; },
+            Component::Normal(name) => normalized.push(name),
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(normalized)
+}
+
+/// Converts the given git error into a [`std::io::Error`].
+///
+/// [`std::io::Error`]: std::io::Error
+fn git_error(error: &git2::Error) -> std::io::Error {
+    let kind = match error.code() {
+        git2::ErrorCode::NotFound => ErrorKind::NotFound,
+        git2::ErrorCode::Ambiguous
+            | git2::ErrorCode::InvalidSpec => ErrorKind::InvalidInput,
+        _ => ErrorKind::Other,
+    };
+    std::io::Error::new(kind, error.message().to_string())
+}
@@ -0,0 +1,258 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Batch comparison jobs.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::compare::compare_all_ties;
+use crate::compare::Comparator;
+use crate::compare::CompareKey;
+use crate::compare::MissingFileBehavior;
+use crate::compare::TieBreak;
+use crate::error::FcmpError;
+use crate::ops::DiffOp;
+use crate::sample::SampleOptions;
+
+// External library imports.
+use serde::Deserialize;
+use serde::Serialize;
+
+// Standard library imports.
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// JobFile
+////////////////////////////////////////////////////////////////////////////////
+/// A collection of comparison jobs, loaded from a TOML or JSON file.
+///
+/// Jobs are given as an array named `job`. In TOML, each job is written as a
+/// `[[job]]` table.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobFile {
+    /// The jobs to run, in order.
+    #[serde(rename = "job", default)]
+    pub jobs: Vec<Job>,
+}
+
+impl JobFile {
+    /// Loads a `JobFile` from the given path. Files with a `.json` extension
+    /// are parsed as JSON, and all others as TOML.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`JobError`] if the file can't be read or is not a valid job
+    /// file.
+    ///
+    /// [`JobError`]: JobError
+    pub fn load(path: &Path) -> Result<Self, JobError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|source| JobError::Read {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        let is_json = path.extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+
+        let parsed = if is_json {
+            serde_json::from_str(&text).map_err(JobParseError::Json)
+        } else {
+            toml::from_str(&text).map_err(JobParseError::Toml)
+        };
+        parsed.map_err(|source| JobError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Job
+////////////////////////////////////////////////////////////////////////////////
+/// A single comparison job.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    /// The name of the job, used in reports.
+    pub name: String,
+    /// The file paths to compare.
+    pub paths: Vec<PathBuf>,
+    /// The [`CompareKey`] used to order the files.
+    #[serde(default)]
+    pub key: CompareKey,
+    /// Additional keys used to order files with equal keys.
+    #[serde(default)]
+    pub tie_break: Vec<CompareKey>,
+    /// The diff operation used to compare file contents.
+    #[serde(default)]
+    pub diff: JobDiff,
+    /// The [`MissingFileBehavior`] indicating how to handle missing files.
+    #[serde(default)]
+    pub missing: MissingFileBehavior,
+    /// Whether to select the oldest file instead of the newest.
+    #[serde(default)]
+    pub reverse: bool,
+    /// A file to write the result path to, in addition to the report.
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+}
+
+impl Job {
+    /// Returns the [`Comparator`] described by the job.
+    #[must_use]
+    pub fn comparator(&self) -> Comparator {
+        Comparator {
+            key: self.key,
+            tie_break: TieBreak::new(self.tie_break.iter().copied()),
+            diff_op: self.diff.into(),
+            missing: self.missing,
+            ..Comparator::default()
+        }
+    }
+
+    /// Runs the job and returns the path of the selected file, writing it to
+    /// the job's output file if one is given.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`JobError`] if no file is selected, if the comparison
+    /// fails, or if the output file can't be written.
+    ///
+    /// [`JobError`]: JobError
+    pub fn run(&self) -> Result<PathBuf, JobError> {
+        let idx = compare_all_ties(
+                self.paths.iter().map(PathBuf::as_path),
+                self.reverse,
+                &self.comparator())?
+            .first()
+            .copied()
+            .ok_or(JobError::NoneSelected)?;
+        let result = self.paths[idx].clone();
+
+        if let Some(output) = &self.output {
+            std::fs::write(output, format!("{}\n", result.display()))
+                .map_err(|source| JobError::Output {
+                    path: output.clone(),
+                    source,
+                })?;
+        }
+        Ok(result)
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// JobDiff
+////////////////////////////////////////////////////////////////////////////////
+/// The diff operations available to jobs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobDiff {
+    /// No diff will be performed.
+    #[default]
+    None,
+    /// An internal diff will be used.
+    Internal,
+    /// A sampled diff of the first, last, and some interior blocks will be
+    /// used.
+    Sampled,
+    /// A POSIX cmp subprocess will be used.
+    Cmp,
+    /// A POSIX diff subprocess will be used.
+    Diff,
+    /// The files will be compared as JSON values.
+    Json,
+    /// The files will be compared as YAML values.
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+impl From<JobDiff> for DiffOp {
+    fn from(diff: JobDiff) -> Self {
+        match diff {
+            JobDiff::None     => Self::None,
+            JobDiff::Internal => Self::internal(),
+            JobDiff::Sampled  => Self::Sampled(SampleOptions::default()),
+            JobDiff::Cmp      => Self::posix_cmp(),
+            JobDiff::Diff     => Self::posix_diff(),
+            JobDiff::Json     => Self::Json,
+            #[cfg(feature = "yaml")]
+            JobDiff::Yaml     => Self::Yaml,
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// JobError
+////////////////////////////////////////////////////////////////////////////////
+/// An error which occurs while loading a [`JobFile`] or running a [`Job`].
+///
+/// [`JobFile`]: JobFile
+/// [`Job`]: Job
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum JobError {
+    /// The job file can't be read.
+    #[error("failed to read job file '{}'", path.display())]
+    Read {
+        /// The path of the job file.
+        path: PathBuf,
+        /// The error which occurred when reading the file.
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The job file is not a valid job file.
+    #[error("invalid job file '{}'", path.display())]
+    Parse {
+        /// The path of the job file.
+        path: PathBuf,
+        /// The error which occurred when parsing the file.
+        #[source]
+        source: JobParseError,
+    },
+
+    /// Comparing the job's files failed.
+    #[error(transparent)]
+    Compare(#[from] FcmpError),
+
+    /// None of the job's files were selected, e.g., because they are all
+    /// missing and ignored.
+    #[error("no files selected")]
+    NoneSelected,
+
+    /// The job's output file can't be written.
+    #[error("failed to write output file '{}'", path.display())]
+    Output {
+        /// The path of the output file.
+        path: PathBuf,
+        /// The error which occurred when writing the file.
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+/// An error which occurs while parsing a job file as JSON or TOML.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum JobParseError {
+    /// The file is not a valid JSON job file.
+    #[error(transparent)]
+    Json(serde_json::Error),
+
+    /// The file is not a valid TOML job file.
+    #[error(transparent)]
+    Toml(toml::de::Error),
+}
@@ -8,11 +8,16 @@
 //! Fcmp CLI library modules.
 ////////////////////////////////////////////////////////////////////////////////
 #![forbid(non_ascii_idents)]
-#![forbid(unsafe_code)]
 #![deny(keyword_idents)]
 #![deny(macro_use_extern_crate)]
 #![deny(missing_abi)]
 #![deny(pointer_structural_match)]
+// Unsafe code is only needed for the system calls made by the optional
+// `reflink` and `io-uring` features, each allowed in a single block.
+#![cfg_attr(
+    not(any(feature = "reflink", feature = "io-uring")),
+    forbid(unsafe_code))]
+#![cfg_attr(any(feature = "reflink", feature = "io-uring"), deny(unsafe_code))]
 #![deny(unsafe_op_in_unsafe_fn)]
 #![warn(absolute_paths_not_starting_with_crate)]
 #![warn(anonymous_parameters)]
@@ -89,6 +94,7 @@
 #![allow(clippy::default_trait_access)]
 #![allow(clippy::enum_glob_use)]
 #![allow(clippy::match_bool)]
+#![allow(clippy::multiple_crate_versions)]
 #![allow(clippy::single_match_else)]
 #![allow(clippy::unseparated_literal_suffix)]
 
@@ -97,6 +103,7 @@
 #![allow(clippy::module_name_repetitions)]
 #![allow(clippy::wildcard_imports)]
 #![allow(clippy::missing_const_for_fn)]
+#![allow(clippy::redundant_pub_crate)] // Conflicts with unreachable_pub.
 #![allow(clippy::shadow_unrelated)] // Does not work correctly.
 
 // TODO: Remove these when error handling is more mature:
@@ -106,14 +113,121 @@
 
 
 // Internal modules.
+#[cfg(feature = "archive")]
+mod archive;
+mod backend;
+mod cancel;
+#[cfg(any(unix, windows))]
+mod chunked;
+mod clock;
 mod compare;
+mod decompress;
+mod dedupe;
+mod error;
+#[cfg(feature = "serde")]
+mod config;
+mod extents;
+mod file_id;
+mod filter;
+mod format;
+mod fs;
+#[cfg(feature = "git")]
+mod git;
+#[cfg(feature = "serde")]
+mod job;
+#[cfg(feature = "tokio")]
+mod nonblocking;
 mod ops;
+mod paths;
+mod plan;
+mod progress;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "http")]
+mod remote;
+mod report;
+#[cfg(feature = "serde")]
+mod resume;
+mod retry;
+mod sample;
+#[cfg(feature = "serde")]
+mod server;
+#[cfg(feature = "serde")]
+mod snapshot;
+mod source;
+mod stats;
+mod stdin;
+mod text;
+mod timing;
+mod unified;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
+mod walk;
+#[cfg(feature = "watch")]
+mod watch;
 
 // Public modules.
+pub mod build_support;
 pub mod command;
 
 // Exports.
+#[cfg(feature = "archive")]
+pub use archive::*;
+pub use cancel::*;
+pub use clock::*;
 pub use compare::*;
+pub use decompress::*;
+pub use dedupe::*;
+pub use error::*;
+#[cfg(feature = "serde")]
+pub use config::*;
+pub use file_id::*;
+pub use filter::*;
+pub use format::*;
+pub use fs::*;
+#[cfg(feature = "git")]
+pub use git::*;
+#[cfg(feature = "serde")]
+pub use job::*;
+#[cfg(feature = "tokio")]
+pub use nonblocking::*;
 pub use ops::*;
+pub use paths::*;
+pub use plan::*;
+pub use progress::*;
+#[cfg(feature = "http")]
+pub use remote::*;
+pub use report::*;
+#[cfg(feature = "serde")]
+pub use resume::*;
+pub use retry::*;
+pub use sample::*;
+#[cfg(feature = "serde")]
+pub use server::*;
+#[cfg(feature = "serde")]
+pub use snapshot::*;
+pub use source::*;
+pub use stats::*;
+pub use stdin::*;
+pub use text::*;
+pub use timing::*;
+pub use unified::*;
+pub use walk::*;
+#[cfg(feature = "watch")]
+pub use watch::*;
 
 
+
+
+// Thread safety guarantees. Configured comparators, diff operations, and
+// caches may be shared between threads.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<Comparator>();
+    assert_send_sync::<DiffOp>();
+    assert_send_sync::<FileCmp>();
+    assert_send_sync::<Manifest>();
+    assert_send_sync::<TieBreak>();
+    assert_send_sync::<TimingRecorder>();
+};
@@ -0,0 +1,218 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Async file compare functions using `tokio`.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::compare::compare_all;
+use crate::compare::Comparator;
+use crate::compare::CompareOutcome;
+use crate::compare::partial_cmp_paths;
+use crate::error::FcmpError;
+use crate::ops::InternalOptions;
+
+// External library imports.
+use tokio::io::AsyncBufReadExt as _;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt as _;
+use tokio::io::BufReader;
+use tokio::task::JoinHandle;
+
+// Standard library imports.
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// partial_cmp_paths_async
+////////////////////////////////////////////////////////////////////////////////
+/// Compares the files at the given paths as [`partial_cmp_paths`] does,
+/// without blocking the async runtime.
+///
+/// The comparison runs on the runtime's blocking thread pool, so it must be
+/// called within a `tokio` runtime.
+///
+/// ### Errors
+///
+/// Returns the same errors as [`partial_cmp_paths`].
+///
+/// [`partial_cmp_paths`]: crate::partial_cmp_paths
+pub async fn partial_cmp_paths_async<A, B>(
+    a: A,
+    b: B,
+    comparator: &Comparator)
+    -> Result<CompareOutcome, FcmpError>
+    where
+        A: AsRef<Path>,
+        B: AsRef<Path>,
+{
+    let a = a.as_ref().to_path_buf();
+    let b = b.as_ref().to_path_buf();
+    let comparator = comparator.clone();
+    join(tokio::task::spawn_blocking(move || {
+        partial_cmp_paths(&a, &b, &comparator)
+    })).await
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// compare_all_async
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the index of the greatest of the files at the given paths as
+/// [`compare_all`] does, without blocking the async runtime.
+///
+/// The comparison runs on the runtime's blocking thread pool, so it must be
+/// called within a `tokio` runtime.
+///
+/// ### Errors
+///
+/// Returns the same errors as [`compare_all`].
+///
+/// [`compare_all`]: crate::compare_all
+pub async fn compare_all_async<P, I>(
+    paths: I,
+    reverse: bool,
+    comparator: &Comparator)
+    -> Result<usize, FcmpError>
+    where
+        I: IntoIterator<Item=P>,
+        P: AsRef<Path>,
+{
+    let paths: Vec<PathBuf> = paths
+        .into_iter()
+        .map(|p| p.as_ref().to_path_buf())
+        .collect();
+    let comparator = comparator.clone();
+    join(tokio::task::spawn_blocking(move || {
+        compare_all(paths.iter().map(PathBuf::as_path), reverse, &comparator)
+    })).await
+}
+
+/// Returns the result of the given blocking comparison task, resuming its
+/// panic if it panicked.
+async fn join<T>(handle: JoinHandle<Result<T, FcmpError>>)
+    -> Result<T, FcmpError>
+{
+    match handle.await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        // The task was cancelled by the runtime shutting down.
+        Err(_) => Err(FcmpError::Cancelled),
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// diff_async
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the files at the given paths are different, using an
+/// internal diff with the given [`InternalOptions`] which reads the files
+/// asynchronously.
+///
+/// Files of different sizes are different without being read. A missing file
+/// is different from an existing file, and two missing files are the same.
+/// The `threads` of the options are not used.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if either file fails to read, or wrapping a
+/// [`ReadLimitExceeded`] error if the files are the same up to the read limit
+/// and [`ReadLimitBehavior::Error`] is used.
+///
+/// [`InternalOptions`]: crate::InternalOptions
+/// [`std::io::Error`]: std::io::Error
+/// [`ReadLimitExceeded`]: crate::ReadLimitExceeded
+/// [`ReadLimitBehavior::Error`]: crate::ReadLimitBehavior::Error
+pub async fn diff_async(a: &Path, b: &Path, options: InternalOptions)
+    -> Result<bool, std::io::Error>
+{
+    let (file_a, file_b) = match (open(a).await?, open(b).await?) {
+        (Some(file_a), Some(file_b)) => (file_a, file_b),
+        (None, None) => return Ok(false),
+        _            => return Ok(true),
+    };
+
+    let meta_a = file_a.metadata().await?;
+    let meta_b = file_b.metadata().await?;
+    if meta_a.len() != meta_b.len()
+        || meta_a.file_type() != meta_b.file_type()
+    {
+        return Ok(true);
+    }
+
+    let len = meta_a.len();
+    let limit = options.max_read_bytes.map_or(len, |max| max.min(len));
+    let (eq, read) = content_eq_async(
+            file_a.take(limit),
+            file_b.take(limit),
+            options.buffer_size)
+        .await?;
+    if eq && limit < len {
+        return options.read_limit
+            .exceeded(limit, read)
+            .map(|(eq, _)| !eq);
+    }
+    Ok(!eq)
+}
+
+/// Opens the file at the given path for reading, returning `None` if it does
+/// not exist.
+async fn open(path: &Path)
+    -> Result<Option<tokio::fs::File>, std::io::Error>
+{
+    match tokio::fs::File::open(path).await {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// content_eq_async
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the given async readers have the same content, along
+/// with the number of bytes read from each reader. Each reader is read
+/// through a buffer of the given size.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if either reader fails to read.
+///
+/// [`std::io::Error`]: std::io::Error
+pub async fn content_eq_async<A, B>(a: A, b: B, buffer_size: usize)
+    -> Result<(bool, u64), std::io::Error>
+    where
+        A: AsyncRead + Unpin,
+        B: AsyncRead + Unpin,
+{
+    // An empty buffer would be indistinguishable from the end of a stream.
+    let buffer_size = buffer_size.max(1);
+    let mut reader_a = BufReader::with_capacity(buffer_size, a);
+    let mut reader_b = BufReader::with_capacity(buffer_size, b);
+    let mut read = 0;
+
+    loop {
+        let buf_a = reader_a.fill_buf().await?;
+        let buf_b = reader_b.fill_buf().await?;
+
+        if buf_a.is_empty() && buf_b.is_empty() {
+            return Ok((true, read));
+        }
+
+        let read_len = buf_a.len().min(buf_b.len());
+        if read_len == 0 || buf_a[..read_len] != buf_b[..read_len] {
+            return Ok((false, read + read_len as u64));
+        }
+
+        reader_a.consume(read_len);
+        reader_b.consume(read_len);
+        read += read_len as u64;
+    }
+}
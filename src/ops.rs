@@ -9,33 +9,268 @@
 ////////////////////////////////////////////////////////////////////////////////
 
 
+// Internal library imports.
+#[cfg(feature = "archive")]
+use crate::archive::ArchiveFormat;
+#[cfg(feature = "archive")]
+use crate::archive::ArchivePath;
+#[cfg(feature = "archive")]
+use crate::archive::member_digests;
+use crate::backend::spawn;
+use crate::backend::SUBPROCESSES_SUPPORTED;
+use crate::cancel::CancelToken;
+#[cfg(any(unix, windows))]
+use crate::chunked::chunked_eq;
+use crate::decompress::Decompression;
+use crate::extents::shares_extents;
+use crate::file_id::FileId;
+use crate::fs::FileSystem;
+#[cfg(feature = "git")]
+use crate::git::GitBlob;
+#[cfg(feature = "http")]
+use crate::remote::RemoteUrl;
+use crate::sample::sampled_eq;
+use crate::sample::SampleOptions;
+use crate::text::text_eq;
+use crate::text::TextOptions;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+use crate::uring::uring_eq;
+use crate::walk::EntryKind;
+use crate::walk::is_special;
+
 // External library imports.
+use glob::Pattern;
+use memchr::memchr_iter;
 #[cfg(feature = "serde")]
 use serde::Serialize;
 #[cfg(feature = "serde")]
 use serde::Deserialize;
 
 // Standard library imports.
+use std::cell::Cell;
+use std::cell::RefCell;
 use std::path::Path;
+use std::str::FromStr;
+#[cfg(feature = "serde")]
+use std::path::PathBuf;
 use std::io::BufRead as _;
 use std::io::BufReader;
+#[cfg(feature = "git")]
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Seek as _;
 use std::io::ErrorKind;
+use std::process::Child;
 use std::process::Command;
-use std::ops::Not;
+use std::process::ExitStatus;
 use std::fs::File;
+use std::sync::Arc;
+use std::time::Duration;
+
+
+/// A function used by [`DiffOp::Custom`] to determine whether two files are
+/// different.
+///
+/// [`DiffOp::Custom`]: DiffOp::Custom
+pub type DiffFn = dyn Fn(&Path, &Path) -> Result<bool, std::io::Error>
+	+ Send
+	+ Sync;
+
+
+/// The default size of the read buffer used for each file by internal diffs,
+/// in bytes.
+pub const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
+
+thread_local! {
+	/// The number of diff subprocesses spawned on the current thread.
+	static SUBPROCESSES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Returns the number of diff subprocesses spawned on the current thread.
+///
+/// Subprocesses may be chosen by a routed or custom `DiffOp`, so they are
+/// counted where they are spawned.
+pub(crate) fn subprocesses_spawned() -> u64 {
+	SUBPROCESSES.with(Cell::get)
+}
+
+thread_local! {
+	/// The [`CancelToken`] of the diff running on the current thread.
+	///
+	/// [`CancelToken`]: crate::CancelToken
+	static CANCEL: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
+}
+
+/// Calls the given function with the given [`CancelToken`] checked by any
+/// diff it runs on the current thread, including diffs run through routed
+/// and custom `DiffOp`s.
+///
+/// [`CancelToken`]: crate::CancelToken
+pub(crate) fn with_cancel<T, F>(cancel: Option<&CancelToken>, f: F) -> T
+	where F: FnOnce() -> T
+{
+	let Some(cancel) = cancel else { return f() };
+	let prev = CANCEL.with(|c| c.replace(Some(cancel.clone())));
+	let result = f();
+	CANCEL.with(|c| *c.borrow_mut() = prev);
+	result
+}
+
+thread_local! {
+	/// The [`FileSystem`] read by the diff running on the current thread.
+	///
+	/// [`FileSystem`]: crate::FileSystem
+	static FILE_SYSTEM: RefCell<Option<Arc<dyn FileSystem>>>
+		= const { RefCell::new(None) };
+}
+
+/// Calls the given function with the given [`FileSystem`] read instead of the
+/// local file system by any diff it runs on the current thread.
+///
+/// [`FileSystem`]: crate::FileSystem
+pub(crate) fn with_file_system<T, F>(fs: Option<&Arc<dyn FileSystem>>, f: F)
+	-> T
+	where F: FnOnce() -> T
+{
+	let Some(fs) = fs else { return f() };
+	let prev = FILE_SYSTEM.with(|c| c.replace(Some(Arc::clone(fs))));
+	let result = f();
+	FILE_SYSTEM.with(|c| *c.borrow_mut() = prev);
+	result
+}
+
+/// Returns the [`FileSystem`] read by the diff running on the current thread,
+/// if it is not the local file system.
+///
+/// [`FileSystem`]: crate::FileSystem
+fn file_system() -> Option<Arc<dyn FileSystem>> {
+	FILE_SYSTEM.with(|c| c.borrow().clone())
+}
+
+/// Returns an error if the [`CancelToken`] of the diff running on the current
+/// thread has been cancelled.
+///
+/// [`CancelToken`]: crate::CancelToken
+fn check_cancelled() -> Result<(), std::io::Error> {
+	CANCEL.with(|c| c.borrow().as_ref().map_or(Ok(()), CancelToken::check))
+}
+
+/// Returns `true` if a [`CancelToken`] is checked by the diff running on the
+/// current thread.
+///
+/// [`CancelToken`]: crate::CancelToken
+fn is_cancellable() -> bool {
+	CANCEL.with(|c| c.borrow().is_some())
+}
+
+/// The longest delay between checks for the cancellation of a diff
+/// subprocess.
+const SUBPROCESS_POLL_MAX: Duration = Duration::from_millis(10);
+
+
+/// Options for the internal diff performed by [`DiffOp::InternalWith`].
+///
+/// [`DiffOp::InternalWith`]: DiffOp::InternalWith
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct InternalOptions {
+	/// The size of the read buffer used for each file, in bytes. Larger
+	/// buffers reduce the number of reads, which may be much faster on
+	/// network filesystems.
+	pub buffer_size: usize,
+	/// The number of threads used to compare each pair of files. Files are
+	/// split into one chunk per thread, which may be much faster for very
+	/// large files on fast storage. Files are compared on a single thread if
+	/// this is `1`, or on platforms other than unix and Windows.
+	pub threads: usize,
+	/// The largest number of bytes read from each file, if the reads are
+	/// limited. Files which are the same up to the limit are handled by the
+	/// [`ReadLimitBehavior`].
+	///
+	/// [`ReadLimitBehavior`]: ReadLimitBehavior
+	pub max_read_bytes: Option<u64>,
+	/// The [`ReadLimitBehavior`] indicating how to handle files which are the
+	/// same up to the read limit.
+	///
+	/// [`ReadLimitBehavior`]: ReadLimitBehavior
+	pub read_limit: ReadLimitBehavior,
+}
+
+impl Default for InternalOptions {
+	fn default() -> Self {
+		Self {
+			buffer_size: DEFAULT_BUFFER_SIZE,
+			threads: 1,
+			max_read_bytes: None,
+			read_limit: ReadLimitBehavior::default(),
+		}
+	}
+}
 
 
 /// A diff operation.
-#[derive(Debug, Clone)]
+///
+/// A `DiffOp` is `Send` and `Sync`, so it may be shared between threads.
+#[derive(Clone, Default)]
 #[cfg_attr(feature = "serde", derive(Serialize))]
 #[cfg_attr(feature = "serde", derive(Deserialize))]
 pub enum DiffOp {
 	/// No diff will be performed.
+	#[default]
 	None,
 	
 	/// An internal diff will be used.
 	Internal,
 
+	/// An internal diff will be used, with the given [`InternalOptions`]
+	/// setting its buffer size, threads, and read limit.
+	///
+	/// [`InternalOptions`]: InternalOptions
+	InternalWith(InternalOptions),
+
+	/// An internal diff of text content will be used, ignoring the
+	/// differences given by the [`TextOptions`].
+	///
+	/// [`TextOptions`]: crate::TextOptions
+	Text(TextOptions),
+
+	/// An internal diff of the decompressed content of the files will be
+	/// used, decompressing the files given by the [`Decompression`].
+	///
+	/// [`Decompression`]: crate::Decompression
+	Decompressed(Decompression),
+
+	/// Files of the same size will be compared by their first and last blocks
+	/// and a number of randomly placed interior blocks, as given by the
+	/// [`SampleOptions`]. This avoids reading the whole of very large files,
+	/// such as media files.
+	///
+	/// Sampling is probabilistic: files which differ only outside of the
+	/// sampled blocks are considered the same. Files no larger than the
+	/// sampled blocks are compared in full.
+	///
+	/// [`SampleOptions`]: crate::SampleOptions
+	Sampled(SampleOptions),
+
+	/// Both files will be parsed as JSON and their values compared, ignoring
+	/// formatting and the order of object keys.
+	#[cfg(feature = "serde")]
+	Json,
+
+	/// Both files will be parsed as YAML and their values compared, ignoring
+	/// formatting and the order of mapping keys.
+	#[cfg(feature = "yaml")]
+	Yaml,
+
+	/// Both files will be read as zip or tar archives, and will be considered
+	/// the same if they contain the same files with the same content,
+	/// regardless of the order of the files and any archive-level metadata.
+	/// Files which are not archives are compared with an internal diff.
+	#[cfg(feature = "archive")]
+	Archive,
+
 	/// A diff command will be run as a subprocess.
 	Subprocess {
 		/// The command to execute.
@@ -43,10 +278,138 @@ pub enum DiffOp {
 		/// The arguments to pass to it.
 		args: Vec<&'static str>,
 	},
+
+	/// A user-provided function will be called to perform the diff.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	Custom(Arc<DiffFn>),
+}
+
+impl std::fmt::Debug for DiffOp {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::None     => write!(f, "None"),
+			Self::Internal => write!(f, "Internal"),
+			Self::InternalWith(options) => f
+				.debug_tuple("InternalWith")
+				.field(options)
+				.finish(),
+			Self::Text(options) => f
+				.debug_tuple("Text")
+				.field(options)
+				.finish(),
+			Self::Decompressed(decompression) => f
+				.debug_tuple("Decompressed")
+				.field(decompression)
+				.finish(),
+			Self::Sampled(options) => f
+				.debug_tuple("Sampled")
+				.field(options)
+				.finish(),
+			#[cfg(feature = "serde")]
+			Self::Json     => write!(f, "Json"),
+			#[cfg(feature = "yaml")]
+			Self::Yaml     => write!(f, "Yaml"),
+			#[cfg(feature = "archive")]
+			Self::Archive  => write!(f, "Archive"),
+			Self::Subprocess { command, args } => f
+				.debug_struct("Subprocess")
+				.field("command", command)
+				.field("args", args)
+				.finish(),
+			Self::Custom(_) => f
+				.debug_tuple("Custom")
+				.field(&format_args!("<fn>"))
+				.finish(),
+		}
+	}
+}
+
+impl FromStr for DiffOp {
+	type Err = DiffOpParseError;
+
+	/// Parses a `DiffOp` from one of `none`, `internal`, `sampled`,
+	/// `decompress`, `gzip`, `zstd`, `bzip2`, `cmp`, `diff`, `json`, `yaml`,
+	/// or `archive`. `decompress` decompresses files in any supported format.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		#[cfg(feature = "archive")]
+		if s.eq_ignore_ascii_case("archive") { return Ok(Self::Archive); }
+		#[cfg(feature = "serde")]
+		if s.eq_ignore_ascii_case("json") { return Ok(Self::Json); }
+		#[cfg(feature = "yaml")]
+		if s.eq_ignore_ascii_case("yaml") { return Ok(Self::Yaml); }
+
+		if s.eq_ignore_ascii_case("none") {
+			Ok(Self::None)
+		} else if s.eq_ignore_ascii_case("internal") {
+			Ok(Self::internal())
+		} else if s.eq_ignore_ascii_case("sampled") {
+			Ok(Self::Sampled(SampleOptions::default()))
+		} else if s.eq_ignore_ascii_case("decompress") {
+			Ok(Self::Decompressed(Decompression::Auto))
+		} else if s.eq_ignore_ascii_case("gzip") {
+			Ok(Self::Decompressed(Decompression::Gzip))
+		} else if s.eq_ignore_ascii_case("zstd") {
+			Ok(Self::Decompressed(Decompression::Zstd))
+		} else if s.eq_ignore_ascii_case("bzip2") {
+			Ok(Self::Decompressed(Decompression::Bzip2))
+		} else if s.eq_ignore_ascii_case("cmp") {
+			Ok(Self::posix_cmp())
+		} else if s.eq_ignore_ascii_case("diff") {
+			Ok(Self::posix_diff())
+		} else {
+			Err(DiffOpParseError)
+		}
+	}
+}
+
+/// An error indicating a failure to parse a [`DiffOp`].
+///
+/// [`DiffOp`]: DiffOp
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffOpParseError;
+
+impl std::error::Error for DiffOpParseError {}
+
+impl std::fmt::Display for DiffOpParseError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "failure to parse DiffOp")
+	}
 }
 
 
 impl DiffOp {
+	/// Returns a `DiffOp` that will perform an internal diff.
+	#[must_use]
+	pub fn internal() -> Self {
+		Self::Internal
+	}
+
+	/// Returns a `DiffOp` that will perform an internal diff with the given
+	/// [`InternalOptions`], or a plain internal diff if they are the
+	/// defaults.
+	///
+	/// [`InternalOptions`]: InternalOptions
+	#[must_use]
+	pub fn internal_with(options: InternalOptions) -> Self {
+		if options == InternalOptions::default() {
+			Self::Internal
+		} else {
+			Self::InternalWith(options)
+		}
+	}
+
+	/// Returns the [`InternalOptions`] of an internal diff, or `None` if the
+	/// diff is not internal.
+	///
+	/// [`InternalOptions`]: InternalOptions
+	fn internal_options(&self) -> Option<InternalOptions> {
+		match self {
+			Self::Internal              => Some(InternalOptions::default()),
+			Self::InternalWith(options) => Some(*options),
+			_                           => None,
+		}
+	}
+
 	/// Returns a `DiffOp` that will execute a POSIX diff subprocess.
 	#[must_use]
 	pub fn posix_diff() -> Self {
@@ -64,98 +427,858 @@ impl DiffOp {
 			args: vec!["-s"],
 		}
 	}
+
+	/// Returns a `DiffOp` that will compare file contents internally, saving
+	/// its progress to the given state file so that an interrupted comparison
+	/// can be resumed.
+	#[cfg(feature = "serde")]
+	#[must_use]
+	pub fn resumable(state_file: PathBuf) -> Self {
+		Self::custom(move |a, b| crate::diff_resumable(a, b, &state_file))
+	}
+
+	/// Returns a `DiffOp` that will use the first of the given
+	/// [`DiffOverride`]s whose pattern matches both paths, or the default
+	/// `DiffOp` if none match.
+	///
+	/// [`DiffOverride`]: DiffOverride
+	#[must_use]
+	pub fn routed(overrides: Vec<DiffOverride>, default: Self) -> Self {
+		if overrides.is_empty() { return default; }
+
+		Self::custom(move |a, b| overrides
+			.iter()
+			.find(|o| o.matches(a, b))
+			.map_or(&default, |o| &o.diff_op)
+			.diff(a, b))
+	}
+
+	/// Returns a `DiffOp` that will call the given function to determine
+	/// whether two files are different.
+	///
+	/// The function should return `Ok(true)` if the files at the given paths
+	/// are different.
+	#[must_use]
+	pub fn custom<F>(diff_fn: F) -> Self
+		where F: Fn(&Path, &Path) -> Result<bool, std::io::Error>
+			+ Send
+			+ Sync
+			+ 'static
+	{
+		Self::Custom(Arc::new(diff_fn))
+	}
 	
 
 	/// Returns true if the files at the given paths are different.
 	pub fn diff(&self, a: &Path, b: &Path) -> Result<bool, std::io::Error> {
+		self.diff_counted(a, b).map(|(different, _)| different)
+	}
+
+	/// Returns true if the files at the given paths are different, aborting
+	/// the diff if the given [`CancelToken`] is cancelled.
+	///
+	/// Internal diffs check the token between each block read, and diff
+	/// subprocesses are killed once it is cancelled. Other diffs check the
+	/// token before they begin.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`std::io::Error`] wrapping a [`Cancelled`] error if the
+	/// token is cancelled before the diff completes.
+	///
+	/// [`CancelToken`]: crate::CancelToken
+	/// [`Cancelled`]: crate::Cancelled
+	/// [`std::io::Error`]: std::io::Error
+	pub fn diff_cancellable(&self, a: &Path, b: &Path, cancel: &CancelToken)
+		-> Result<bool, std::io::Error>
+	{
+		with_cancel(Some(cancel), || self.diff(a, b))
+	}
+
+	/// Returns true if the given open files, which were opened from the given
+	/// paths, are different.
+	///
+	/// Internal and sampled diffs read the open files from their start and
+	/// check their metadata through the open handles, so the paths are not
+	/// reopened. All other diffs use the paths. The files' cursors are moved,
+	/// so the same file should not be diffed on multiple threads at once.
+	pub fn diff_open(&self, a: (&Path, &File), b: (&Path, &File))
+		-> Result<bool, std::io::Error>
+	{
+		self.diff_open_counted(a, b).map(|(different, _)| different)
+	}
+
+	/// Returns true if the given open files, which were opened from the given
+	/// paths, are different, along with the number of bytes read from each
+	/// file.
+	pub(crate) fn diff_open_counted(
+		&self,
+		(path_a, a): (&Path, &File),
+		(path_b, b): (&Path, &File))
+		-> Result<(bool, u64), std::io::Error>
+	{
+		if let Some(options) = self.internal_options() {
+			return Self::internal_files_diff(a, b, options);
+		}
 		match self {
-			Self::None => Ok(a != b),
-
-			Self::Internal => {
-				let file_a = match File::options().read(true).open(a) {
-					Ok(f)  => Some(f),
-					Err(e) if matches!(e.kind(), ErrorKind::NotFound) => None,
-					Err(e) => return Err(e),
-				};
-
-				let file_b = match File::options().read(true).open(b) {
-					Ok(f)  => Some(f),
-					Err(e) if matches!(e.kind(), ErrorKind::NotFound) => None,
-					Err(e) => return Err(e),
-				};
-
-				match (file_a, file_b) {
+			Self::Sampled(options) => Self::sampled_files_diff(a, b, *options),
+			_ => self.diff_counted(path_a, path_b),
+		}
+	}
+
+	/// Returns `true` if the diff reads the open files given to
+	/// [`diff_open`] instead of reopening their paths.
+	///
+	/// [`diff_open`]: DiffOp::diff_open
+	pub(crate) fn reads_open_files(&self) -> bool {
+		matches!(self,
+			Self::Internal | Self::InternalWith(_) | Self::Sampled(_))
+	}
+
+	/// Returns true if the files at the given paths are different, along with
+	/// the number of bytes read from each file. Only internal diffs count the
+	/// bytes they read.
+	pub(crate) fn diff_counted(&self, a: &Path, b: &Path)
+		-> Result<(bool, u64), std::io::Error>
+	{
+		check_cancelled()?;
+		if !matches!(self, Self::None) {
+			// Paths to the same underlying file, such as hardlinks, have the
+			// same content.
+			if is_same_file(a, b) { return Ok((false, 0)); }
+			// Special files are never opened or passed to a subprocess, as
+			// reading a FIFO may block.
+			if is_special_path(a) || is_special_path(b) {
+				return Ok((true, 0));
+			}
+		}
+		match self {
+			Self::None => Ok((a != b, 0)),
+
+			Self::Internal | Self::InternalWith(_) => {
+				let options = self.internal_options().unwrap_or_default();
+				if is_virtual(a) || is_virtual(b) {
+					return content_diff(a, b, |content_a, content_b| {
+						Self::limited_eq(content_a, content_b, options)
+					});
+				}
+				match (open_existing(a)?, open_existing(b)?) {
 					(Some(a), Some(b)) => {
-						let meta_a = a.metadata().expect("get file metadata");
-						let meta_b = b.metadata().expect("get file metadata");
-
-						if meta_a.len() != meta_b.len()
-							|| meta_a.is_symlink()
-							|| meta_b.is_symlink()
-							|| meta_a.file_type() != meta_b.file_type()
-						{
-							 Ok(false)
-						} else {
-							Self::internal_eq(&a, &b)
-								.map(bool::not)
-						}
+						Self::internal_files_diff(&a, &b, options)
 					},
+					(None, None) => Ok((false, 0)),
+					_            => Ok((true, 0)),
+				}
+			},
+
+			Self::Text(options) => content_diff(a, b, |content_a, content_b| {
+				text_eq(
+					options.reader(content_a),
+					options.reader(content_b),
+					*options)
+			}),
+
+			Self::Decompressed(decompression) => content_diff(a, b,
+				|content_a, content_b| content_eq_counted(
+					decompression.reader(a, content_a)?,
+					decompression.reader(b, content_b)?,
+					DEFAULT_BUFFER_SIZE)),
+
+			// Archive members, remote files, and git blobs can't be seeked,
+			// so they are compared in full.
+			Self::Sampled(_) if is_virtual(a) || is_virtual(b) => {
+				Self::internal().diff_counted(a, b)
+			},
+
+			Self::Sampled(options) => Self::sampled_diff(a, b, *options),
 
-					(None, None) => Ok(true),
-					_            => Ok(false),
+			#[cfg(feature = "serde")]
+			Self::Json => structured_diff(a, b, |content| {
+				let reader = BufReader::new(content);
+				serde_json::from_reader::<_, serde_json::Value>(reader)
+					.map_err(std::io::Error::from)
+			}),
+
+			#[cfg(feature = "yaml")]
+			Self::Yaml => structured_diff(a, b, |content| {
+				// Each document in a multi-document stream is compared.
+				serde_yaml::Deserializer::from_reader(BufReader::new(content))
+					.map(serde_yaml::Value::deserialize)
+					.collect::<Result<Vec<_>, _>>()
+					.map_err(|e| std::io::Error::new(
+						ErrorKind::InvalidData,
+						e))
+			}),
+
+			#[cfg(feature = "archive")]
+			Self::Archive => {
+				let archive = |p: &Path| ArchiveFormat::of(p)
+					.filter(|_| !is_virtual(p) && p.is_file());
+				match (archive(a), archive(b)) {
+					(Some(format_a), Some(format_b)) => Ok((
+						member_digests(a, format_a)?
+							!= member_digests(b, format_b)?,
+						0)),
+					_ => Self::internal().diff_counted(a, b),
 				}
 			},
 
+			// Archive members, remote files, and git blobs can't be passed
+			// to a subprocess, so they are compared internally, as are all
+			// files on targets without subprocesses.
+			Self::Subprocess { .. } if is_virtual(a)
+				|| is_virtual(b)
+				|| !SUBPROCESSES_SUPPORTED =>
+			{
+				Self::internal().diff_counted(a, b)
+			},
+
 			Self::Subprocess { command, args } => {
-				let status = Command::new(command)
+				#[cfg(feature = "tracing")]
+				tracing::debug!(
+					command = %command,
+					?args,
+					a = %a.display(),
+					b = %b.display(),
+					"launching diff subprocess");
+				SUBPROCESSES.with(|n| n.set(n.get() + 1));
+				let child = spawn(Command::new(command)
 					.args(args)
 					.arg(a)
-					.arg(b)
-					.status()?;
+					.arg(b))?;
+				let status = wait_cancellable(child)?;
+				#[cfg(feature = "tracing")]
+				tracing::debug!(%status, "diff subprocess exited");
 
 				match status.code() {
-					Some(0) => Ok(false),
-					Some(1) => Ok(true),
+					Some(0) => Ok((false, 0)),
+					Some(1) => Ok((true, 0)),
 					Some(_) => Err(std::io::Error::from(ErrorKind::Other)),
 					None => Err(std::io::Error::from(ErrorKind::Interrupted)),
 				}
 			},
-			
+
+			Self::Custom(diff_fn) => diff_fn(a, b).map(|d| (d, 0)),
 		}
 	}
 
-	/// Returns `true` if the given files have the same content.
+	/// Returns `true` if the given files of the given length have the same
+	/// content, along with the number of bytes read from each file, comparing
+	/// them on as many threads as the given [`InternalOptions`] allow. Files
+	/// compared on a single thread are read using `io_uring` if the
+	/// `io-uring` feature is enabled and it is available.
 	///
-	/// ### Errors
-	///
-	/// Returns a [`std::io::Error`] if the file's contents fail to read
-	/// correctly.
+	/// [`InternalOptions`]: InternalOptions
+	#[cfg_attr(not(any(unix, windows)), allow(unused_variables))]
+	fn file_eq(a: &File, b: &File, len: u64, options: InternalOptions)
+		-> Result<(bool, u64), std::io::Error>
+	{
+		#[cfg(any(unix, windows))]
+		if options.threads > 1 {
+			return chunked_eq(a, b, len, options.threads, options.buffer_size);
+		}
+		#[cfg(all(feature = "io-uring", target_os = "linux"))]
+		if let Some(result) = uring_eq(a, b, len, options.buffer_size)? {
+			return Ok(result);
+		}
+		content_eq_counted(a.take(len), b.take(len), options.buffer_size)
+	}
+
+	/// Returns `true` if the given readers have the same content, along with
+	/// the number of bytes read from each reader, reading no more than the
+	/// read limit of the given [`InternalOptions`].
 	///
-	/// [`std::io::Error`]: std::io::Error
-	fn internal_eq(a: &File, b: &File) -> Result<bool, std::io::Error> {
-		let mut buf_reader_a = BufReader::new(a);
-		let mut buf_reader_b = BufReader::new(b);
+	/// [`InternalOptions`]: InternalOptions
+	fn limited_eq<A, B>(mut a: A, mut b: B, options: InternalOptions)
+		-> Result<(bool, u64), std::io::Error>
+		where
+			A: Read,
+			B: Read,
+	{
+		let Some(max) = options.max_read_bytes else {
+			return content_eq_counted(a, b, options.buffer_size);
+		};
+		let has_more = |r: &mut dyn Read| r.read(&mut [0]).map(|n| n > 0);
 
-		loop {
-			let buf_a = buf_reader_a.fill_buf()?;
-			let buf_b = buf_reader_b.fill_buf()?;
+		match content_eq_counted(
+			a.by_ref().take(max),
+			b.by_ref().take(max),
+			options.buffer_size)?
+		{
+			(true, read) if has_more(&mut a)? || has_more(&mut b)? => {
+				options.read_limit.exceeded(max, read)
+			},
+			result => Ok(result),
+		}
+	}
 
-			if buf_a.is_empty() && buf_b.is_empty() {
-				return Ok(true);
+	/// Returns true if the sampled contents of the files at the given paths
+	/// are different, along with the number of bytes read from each file.
+	fn sampled_diff(a: &Path, b: &Path, options: SampleOptions)
+		-> Result<(bool, u64), std::io::Error>
+	{
+		match (open_existing(a)?, open_existing(b)?) {
+			(Some(a), Some(b)) => Self::sampled_files_diff(&a, &b, options),
+			(None, None) => Ok((false, 0)),
+			_            => Ok((true, 0)),
+		}
+	}
+
+	/// Returns true if the given open files are different, along with the
+	/// number of bytes read from each file, using an internal diff with the
+	/// given [`InternalOptions`].
+	///
+	/// [`InternalOptions`]: InternalOptions
+	fn internal_files_diff(a: &File, b: &File, options: InternalOptions)
+		-> Result<(bool, u64), std::io::Error>
+	{
+		let meta_a = a.metadata()?;
+		let meta_b = b.metadata()?;
+
+		if meta_a.len() != meta_b.len()
+			|| meta_a.file_type() != meta_b.file_type()
+		{
+			Ok((true, 0))
+		} else if is_same_open_file(a, b) {
+			Ok((false, 0))
+		} else if shares_extents(a, b) {
+			// Reflinked copies have the same content.
+			Ok((false, 0))
+		} else {
+			let len = meta_a.len();
+			let limit = options.max_read_bytes.map_or(len, |max| max.min(len));
+			rewind(a)?;
+			rewind(b)?;
+			match Self::file_eq(a, b, limit, options)? {
+				(true, read) if limit < len => options.read_limit
+					.exceeded(limit, read)
+					.map(|(eq, read)| (!eq, read)),
+				(eq, read) => Ok((!eq, read)),
 			}
+		}
+	}
+
+	/// Returns true if the sampled contents of the given open files are
+	/// different, along with the number of bytes read from each file.
+	fn sampled_files_diff(a: &File, b: &File, options: SampleOptions)
+		-> Result<(bool, u64), std::io::Error>
+	{
+		let len_a = a.metadata()?.len();
+		let len_b = b.metadata()?.len();
+		if len_a == len_b && is_same_open_file(a, b) {
+			Ok((false, 0))
+		} else if len_a == len_b {
+			rewind(a)?;
+			rewind(b)?;
+			sampled_eq(a, b, len_a, options).map(|(eq, read)| (!eq, read))
+		} else {
+			Ok((true, 0))
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// content_eq
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the given readers have the same content.
+///
+/// Any readers may be compared, such as sockets, in-memory buffers, or
+/// decompressors. Each reader is read through a buffer of
+/// [`DEFAULT_BUFFER_SIZE`] bytes until they differ or both end.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if either reader fails to read.
+///
+/// [`DEFAULT_BUFFER_SIZE`]: DEFAULT_BUFFER_SIZE
+/// [`std::io::Error`]: std::io::Error
+pub fn content_eq<A, B>(a: A, b: B) -> Result<bool, std::io::Error>
+	where
+		A: Read,
+		B: Read,
+{
+	content_eq_counted(a, b, DEFAULT_BUFFER_SIZE).map(|(eq, _)| eq)
+}
+
+/// Returns `true` if the given readers have the same content, along with the
+/// number of bytes read from each reader. Each reader is read through a
+/// buffer of the given size.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if either reader fails to read.
+///
+/// [`std::io::Error`]: std::io::Error
+pub(crate) fn content_eq_counted<A, B>(a: A, b: B, buffer_size: usize)
+	-> Result<(bool, u64), std::io::Error>
+	where
+		A: Read,
+		B: Read,
+{
+	// An empty buffer would be indistinguishable from the end of a stream.
+	let buffer_size = buffer_size.max(1);
+	let mut buf_reader_a = BufReader::with_capacity(buffer_size, a);
+	let mut buf_reader_b = BufReader::with_capacity(buffer_size, b);
+	let mut read = 0;
+
+	loop {
+		check_cancelled()?;
+		let buf_a = buf_reader_a.fill_buf()?;
+		let buf_b = buf_reader_b.fill_buf()?;
+
+		if buf_a.is_empty() && buf_b.is_empty() {
+			return Ok((true, read));
+		}
 
-			let read_len = if buf_a.len() <= buf_b.len() {
-				buf_a.len()
-			} else {
-				buf_b.len()
+		// A stream which has ended differs from one which has not.
+		let read_len = buf_a.len().min(buf_b.len());
+		if read_len == 0 || buf_a[..read_len] != buf_b[..read_len] {
+			return Ok((false, read + read_len as u64));
+		}
+
+		buf_reader_a.consume(read_len);
+		buf_reader_b.consume(read_len);
+		read += read_len as u64;
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// first_difference
+////////////////////////////////////////////////////////////////////////////////
+/// The position of the first difference between two files, as found by
+/// [`first_difference`].
+///
+/// [`first_difference`]: first_difference
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Difference {
+	/// The zero-based byte offset of the first differing byte.
+	pub offset: u64,
+	/// The one-based line number of the first differing byte.
+	pub line: u64,
+	/// The index of the file which ended at the offset, if the other file is
+	/// longer. The index is 0 for the first file and 1 for the second.
+	pub ended: Option<usize>,
+}
+
+/// Returns the position of the first difference between the given readers,
+/// or `None` if they have the same content.
+///
+/// As with [`content_eq`], each reader is read through a buffer of
+/// [`DEFAULT_BUFFER_SIZE`] bytes until they differ or both end.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if either reader fails to read.
+///
+/// [`content_eq`]: content_eq
+/// [`DEFAULT_BUFFER_SIZE`]: DEFAULT_BUFFER_SIZE
+/// [`std::io::Error`]: std::io::Error
+pub fn first_difference<A, B>(a: A, b: B)
+	-> Result<Option<Difference>, std::io::Error>
+	where
+		A: Read,
+		B: Read,
+{
+	let mut buf_reader_a = BufReader::with_capacity(DEFAULT_BUFFER_SIZE, a);
+	let mut buf_reader_b = BufReader::with_capacity(DEFAULT_BUFFER_SIZE, b);
+	let mut offset = 0;
+	let mut line = 1;
+
+	loop {
+		check_cancelled()?;
+		let buf_a = buf_reader_a.fill_buf()?;
+		let buf_b = buf_reader_b.fill_buf()?;
+
+		if buf_a.is_empty() && buf_b.is_empty() { return Ok(None); }
+
+		let read_len = buf_a.len().min(buf_b.len());
+		let same_len = buf_a[..read_len]
+			.iter()
+			.zip(&buf_b[..read_len])
+			.position(|(a, b)| a != b)
+			.unwrap_or(read_len);
+		offset += same_len as u64;
+		line += memchr_iter(b'\n', &buf_a[..same_len]).count() as u64;
+
+		// A stream which has ended differs from one which has not.
+		if same_len < read_len || read_len == 0 {
+			let ended = match (buf_a.is_empty(), buf_b.is_empty()) {
+				(true, _) => Some(0),
+				(_, true) => Some(1),
+				_         => None,
 			};
+			return Ok(Some(Difference { offset, line, ended }));
+		}
 
-			if buf_a[0..read_len] != buf_b[0..read_len] {
-				return Ok(false);
-			}
+		buf_reader_a.consume(read_len);
+		buf_reader_b.consume(read_len);
+	}
+}
 
-			buf_reader_a.consume(read_len);
-			buf_reader_b.consume(read_len);
+/// Returns the position of the first difference between the content of the
+/// files at the given paths, or `None` if they have the same content.
+///
+/// The paths may name archive members, remote files, or git blobs.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if either file is missing or can't be read.
+///
+/// [`std::io::Error`]: std::io::Error
+pub fn first_difference_paths(a: &Path, b: &Path)
+	-> Result<Option<Difference>, std::io::Error>
+{
+	first_difference(open_found(a)?, open_found(b)?)
+}
+
+
+/// Waits for the given child process to exit, killing it if the
+/// [`CancelToken`] of the diff running on the current thread is cancelled.
+///
+/// [`CancelToken`]: crate::CancelToken
+fn wait_cancellable(mut child: Child)
+	-> Result<ExitStatus, std::io::Error>
+{
+	if !is_cancellable() { return child.wait(); }
+
+	let mut delay = Duration::from_micros(100);
+	loop {
+		// Ctrl-C also interrupts the child, so the token is checked after it
+		// exits.
+		if let Some(status) = child.try_wait()? {
+			return check_cancelled().map(|()| status);
+		}
+		if let Err(e) = check_cancelled() {
+			let _ = child.kill();
+			let _ = child.wait();
+			return Err(e);
 		}
+		std::thread::sleep(delay);
+		delay = (delay * 2).min(SUBPROCESS_POLL_MAX);
+	}
+}
+
+/// Returns `true` if the given path is a special file. Special files are not
+/// opened, as opening a FIFO may block.
+fn is_special_path(path: &Path) -> bool {
+	if let Some(fs) = file_system() {
+		return fs.metadata(path)
+			.is_ok_and(|m| m.is_some_and(|m| m.kind == EntryKind::Other));
+	}
+	std::fs::metadata(path).is_ok_and(|m| is_special(m.file_type()))
+}
+
+/// Returns true if the values parsed from the files at the given paths with the
+/// given function are different.
+#[cfg(feature = "serde")]
+fn structured_diff<F, V>(a: &Path, b: &Path, parse: F)
+	-> Result<(bool, u64), std::io::Error>
+	where
+		F: Fn(Box<dyn Read>) -> Result<V, std::io::Error>,
+		V: PartialEq,
+{
+	content_diff(a, b, |content_a, content_b| {
+		Ok((parse(content_a)? == parse(content_b)?, 0))
+	})
+}
+
+/// Returns true if the contents of the files or archive members at the given
+/// paths are different, using the given function to determine whether the
+/// contents are equal and the number of bytes read from each.
+fn content_diff<F>(a: &Path, b: &Path, eq: F)
+	-> Result<(bool, u64), std::io::Error>
+	where F: FnOnce(Box<dyn Read>, Box<dyn Read>)
+		-> Result<(bool, u64), std::io::Error>
+{
+	match (open_content(a)?, open_content(b)?) {
+		(Some(a), Some(b)) => eq(a, b).map(|(eq, read)| (!eq, read)),
+		(None, None) => Ok((false, 0)),
+		_            => Ok((true, 0)),
+	}
+}
+
+/// Opens the content of the file, archive member, remote file, or git blob at
+/// the given path for reading, returning `None` if it does not exist.
+pub(crate) fn open_content(path: &Path)
+	-> Result<Option<Box<dyn Read>>, std::io::Error>
+{
+	if let Some(fs) = file_system() {
+		return Ok(fs
+			.open(path)?
+			.map(|content| -> Box<dyn Read> { Box::new(content) }));
+	}
+	#[cfg(feature = "archive")]
+	if let Some(archive_path) = ArchivePath::parse(path) {
+		return archive_path.open();
+	}
+	#[cfg(feature = "http")]
+	if let Some(url) = RemoteUrl::parse(path) {
+		return Ok(url
+			.read()?
+			.map(|content| -> Box<dyn Read> { Box::new(content) }));
+	}
+	#[cfg(feature = "git")]
+	if let Some(git_blob) = GitBlob::parse(path) {
+		return Ok(git_blob
+			.read()?
+			.map(|content| -> Box<dyn Read> {
+				Box::new(Cursor::new(content))
+			}));
+	}
+	Ok(open_existing(path)?.map(|file| -> Box<dyn Read> { Box::new(file) }))
+}
+
+/// Opens the content of the file, archive member, remote file, or git blob at
+/// the given path for reading, returning an error if it does not exist.
+pub(crate) fn open_found(path: &Path) -> Result<Box<dyn Read>, std::io::Error> {
+	open_content(path)?.ok_or_else(|| std::io::Error::new(
+		ErrorKind::NotFound,
+		format!("{}: file not found", path.display())))
+}
+
+/// Returns `true` if the given paths refer to the same underlying file, such
+/// as through a hardlink. Virtual paths are never the same file.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+	if is_virtual(a) || is_virtual(b) { return false; }
+	match (FileId::from_path(a), FileId::from_path(b)) {
+		(Ok(a), Ok(b)) => a == b,
+		_              => false,
+	}
+}
+
+/// Returns `true` if the given open files are the same underlying file.
+fn is_same_open_file(a: &File, b: &File) -> bool {
+	match (FileId::from_file(a), FileId::from_file(b)) {
+		(Ok(a), Ok(b)) => a == b,
+		_              => false,
+	}
+}
+
+/// Returns `true` if the given path is an [`ArchivePath`], a [`RemoteUrl`], or
+/// a [`GitBlob`], or is read from a [`FileSystem`], so its content is not in a
+/// local file.
+///
+/// [`ArchivePath`]: crate::ArchivePath
+/// [`FileSystem`]: crate::FileSystem
+/// [`GitBlob`]: crate::GitBlob
+/// [`RemoteUrl`]: crate::RemoteUrl
+pub(crate) fn is_virtual(path: &Path) -> bool {
+	FILE_SYSTEM.with(|c| c.borrow().is_some())
+		|| is_member(path)
+		|| is_remote(path)
+		|| is_blob(path)
+}
+
+/// Returns `true` if the given path is an [`ArchivePath`].
+///
+/// [`ArchivePath`]: crate::ArchivePath
+#[cfg(feature = "archive")]
+fn is_member(path: &Path) -> bool {
+	ArchivePath::parse(path).is_some()
+}
+
+/// Returns `true` if the given path is an `ArchivePath`.
+#[cfg(not(feature = "archive"))]
+fn is_member(_path: &Path) -> bool {
+	false
+}
+
+/// Returns `true` if the given path is a [`RemoteUrl`].
+///
+/// [`RemoteUrl`]: crate::RemoteUrl
+#[cfg(feature = "http")]
+fn is_remote(path: &Path) -> bool {
+	RemoteUrl::parse(path).is_some()
+}
+
+/// Returns `true` if the given path is a `RemoteUrl`.
+#[cfg(not(feature = "http"))]
+fn is_remote(_path: &Path) -> bool {
+	false
+}
+
+/// Returns `true` if the given path is a [`GitBlob`].
+///
+/// [`GitBlob`]: crate::GitBlob
+#[cfg(feature = "git")]
+fn is_blob(path: &Path) -> bool {
+	GitBlob::parse(path).is_some()
+}
+
+/// Returns `true` if the given path is a `GitBlob`.
+#[cfg(not(feature = "git"))]
+fn is_blob(_path: &Path) -> bool {
+	false
+}
+
+/// Moves the cursor of the given file to its start.
+fn rewind(mut file: &File) -> Result<(), std::io::Error> {
+	file.rewind()
+}
+
+/// Opens the file at the given path for reading, returning `None` if it does
+/// not exist.
+fn open_existing(path: &Path) -> Result<Option<File>, std::io::Error> {
+	match File::options().read(true).open(path) {
+		Ok(f)  => Ok(Some(f)),
+		Err(e) if matches!(e.kind(), ErrorKind::NotFound) => Ok(None),
+		Err(e) => Err(e),
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ReadLimitBehavior
+////////////////////////////////////////////////////////////////////////////////
+/// Options for handling files which are the same up to the read limit of an
+/// internal diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(clap::ArgEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ReadLimitBehavior {
+	/// Assume the files are different.
+	#[default]
+	Different,
+	/// Return a [`ReadLimitExceeded`] error.
+	///
+	/// [`ReadLimitExceeded`]: ReadLimitExceeded
+	Error,
+}
+
+impl ReadLimitBehavior {
+	/// Returns whether files which are the same up to the given read limit
+	/// are equal, along with the given number of bytes read from each file.
+	pub(crate) fn exceeded(self, max_bytes: u64, read: u64)
+		-> Result<(bool, u64), std::io::Error>
+	{
+		match self {
+			Self::Different => Ok((false, read)),
+			Self::Error => Err(std::io::Error::other(
+				ReadLimitExceeded { max_bytes })),
+		}
+	}
+}
+
+impl FromStr for ReadLimitBehavior {
+	type Err = ReadLimitBehaviorParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if s.eq_ignore_ascii_case("different") {
+			Ok(Self::Different)
+		} else if s.eq_ignore_ascii_case("error") {
+			Ok(Self::Error)
+		} else {
+			Err(ReadLimitBehaviorParseError)
+		}
+	}
+}
+
+/// An error indicating a failure to parse a [`ReadLimitBehavior`].
+///
+/// [`ReadLimitBehavior`]: ReadLimitBehavior
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadLimitBehaviorParseError;
+
+impl std::error::Error for ReadLimitBehaviorParseError {}
+
+impl std::fmt::Display for ReadLimitBehaviorParseError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "failure to parse ReadLimitBehavior")
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ReadLimitExceeded
+////////////////////////////////////////////////////////////////////////////////
+/// An error indicating that files were the same up to the read limit of an
+/// internal diff using [`ReadLimitBehavior::Error`]. It is returned as the
+/// inner error of a [`std::io::Error`].
+///
+/// [`ReadLimitBehavior::Error`]: ReadLimitBehavior::Error
+/// [`std::io::Error`]: std::io::Error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadLimitExceeded {
+	/// The largest number of bytes which could be read from each file.
+	pub max_bytes: u64,
+}
+
+impl ReadLimitExceeded {
+	/// Returns `true` if the given [`std::io::Error`] is a
+	/// `ReadLimitExceeded` error.
+	///
+	/// [`std::io::Error`]: std::io::Error
+	#[must_use]
+	pub fn is(error: &std::io::Error) -> bool {
+		error.get_ref().and_then(|e| e.downcast_ref::<Self>()).is_some()
+	}
+}
+
+impl std::error::Error for ReadLimitExceeded {}
+
+impl std::fmt::Display for ReadLimitExceeded {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "content is the same up to the read limit of {} bytes",
+			self.max_bytes)
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// DiffOverride
+////////////////////////////////////////////////////////////////////////////////
+/// A [`DiffOp`] which applies to paths matching a glob pattern.
+///
+/// [`DiffOp`]: DiffOp
+#[derive(Debug, Clone)]
+pub struct DiffOverride {
+	/// The pattern of paths to apply the `DiffOp` to.
+	pub pattern: Pattern,
+	/// The `DiffOp` to apply.
+	pub diff_op: DiffOp,
+}
+
+impl DiffOverride {
+	/// Returns `true` if the override applies to the given pair of paths,
+	/// which it does if both match its pattern.
+	#[must_use]
+	pub fn matches(&self, a: &Path, b: &Path) -> bool {
+		self.pattern.matches_path(a) && self.pattern.matches_path(b)
+	}
+}
+
+impl FromStr for DiffOverride {
+	type Err = DiffOverrideParseError;
+
+	/// Parses a `DiffOverride` of the form `<glob>=<diff>`.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (pattern, diff_op) = s
+			.rsplit_once('=')
+			.ok_or(DiffOverrideParseError)?;
+
+		Ok(Self {
+			pattern: Pattern::new(pattern)
+				.map_err(|_e| DiffOverrideParseError)?,
+			diff_op: diff_op.parse()
+				.map_err(|_e| DiffOverrideParseError)?,
+		})
+	}
+}
+
+/// An error indicating a failure to parse a [`DiffOverride`].
+///
+/// [`DiffOverride`]: DiffOverride
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffOverrideParseError;
+
+impl std::error::Error for DiffOverrideParseError {}
+
+impl std::fmt::Display for DiffOverrideParseError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "failure to parse DiffOverride")
 	}
 }
@@ -0,0 +1,374 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Path matching functions.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+use unicode_normalization::UnicodeNormalization as _;
+
+// Standard library imports.
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::io::BufRead;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PathCase
+////////////////////////////////////////////////////////////////////////////////
+/// Options for handling letter case when matching paths.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(clap::ArgEnum)]
+pub enum PathCase {
+    /// Paths which differ in case are different paths.
+    Sensitive,
+    /// Paths which differ only in case are the same path. Unicode case folding
+    /// is used to determine whether paths differ in case.
+    Insensitive,
+}
+
+impl PathCase {
+    /// Returns the `PathCase` matching the default semantics of the native
+    /// filesystem. This is `Insensitive` on Windows and macOS, and `Sensitive`
+    /// elsewhere.
+    #[must_use]
+    pub const fn native() -> Self {
+        if cfg!(any(windows, target_os = "macos")) {
+            Self::Insensitive
+        } else {
+            Self::Sensitive
+        }
+    }
+}
+
+impl Default for PathCase {
+    fn default() -> Self {
+        Self::native()
+    }
+}
+
+impl FromStr for PathCase {
+    type Err = PathCaseParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("sensitive") {
+            Ok(Self::Sensitive)
+        } else if s.eq_ignore_ascii_case("insensitive") {
+            Ok(Self::Insensitive)
+        } else {
+            Err(PathCaseParseError)
+        }
+    }
+}
+
+/// An error indicating a failure to parse a [`PathCase`].
+///
+/// [`PathCase`]: PathCase 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathCaseParseError;
+
+impl std::error::Error for PathCaseParseError {}
+
+impl std::fmt::Display for PathCaseParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse PathCase")
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PathMatch
+////////////////////////////////////////////////////////////////////////////////
+/// Options determining when two paths match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PathMatch {
+    /// The [`PathCase`] indicating how to handle letter case.
+    pub case: PathCase,
+    /// Whether to normalize paths to Unicode NFC form before matching. This
+    /// allows paths from filesystems which store decomposed names (such as
+    /// macOS) to match paths from filesystems which store composed names.
+    pub normalize: bool,
+}
+
+impl PathMatch {
+    /// Returns a `PathMatch` which only matches identical paths.
+    #[must_use]
+    pub const fn exact() -> Self {
+        Self {
+            case: PathCase::Sensitive,
+            normalize: false,
+        }
+    }
+
+    /// Returns the key for a single path component.
+    fn component_key(self, s: &str) -> String {
+        let s: String = if self.normalize {
+            s.nfc().collect()
+        } else {
+            s.into()
+        };
+        match self.case {
+            PathCase::Sensitive => s,
+            PathCase::Insensitive if self.normalize => {
+                caseless::default_case_fold_str(&s).nfc().collect()
+            },
+            PathCase::Insensitive => caseless::default_case_fold_str(&s),
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// path_key
+////////////////////////////////////////////////////////////////////////////////
+/// Returns a key for the given path such that matching paths have equal keys.
+///
+/// Path components which are not valid unicode are never case folded or
+/// normalized.
+///
+/// ### Parameters
+/// 
+/// + `matching`: The [`PathMatch`] options determining when paths match.
+///
+/// [`PathMatch`]: PathMatch
+#[must_use]
+pub fn path_key(path: &Path, matching: PathMatch) -> PathBuf {
+    if matching == PathMatch::exact() { return path.to_path_buf(); }
+
+    path.components()
+        .map(|c| {
+            let c = c.as_os_str();
+            c.to_str().map_or_else(
+                || c.to_os_string(),
+                |s| OsString::from(matching.component_key(s)))
+        })
+        .collect()
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// paths_match
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the given paths match.
+///
+/// ### Parameters
+/// 
+/// + `matching`: The [`PathMatch`] options determining when paths match.
+///
+/// [`PathMatch`]: PathMatch
+#[must_use]
+pub fn paths_match(a: &Path, b: &Path, matching: PathMatch) -> bool {
+    a == b || path_key(a, matching) == path_key(b, matching)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// canonical_path
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the given path made absolute, with its `.` and `..` components and
+/// symbolic links resolved, so that aliases of a path such as `./foo` and
+/// `foo` have the same canonical path.
+///
+/// If the path does not exist or `follow_symlinks` is not set, only its
+/// parent directory is resolved, so a symbolic link is not resolved to its
+/// target. Returns `None` if the path can't be resolved.
+#[must_use]
+pub fn canonical_path(path: &Path, follow_symlinks: bool) -> Option<PathBuf> {
+    if follow_symlinks {
+        if let Ok(canonical) = std::fs::canonicalize(path) {
+            return Some(canonical);
+        }
+    }
+    // Paths ending in `..` have no file name to keep unresolved.
+    let Some(name) = path.file_name() else {
+        return std::fs::canonicalize(path).ok();
+    };
+    let parent = path.parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    std::fs::canonicalize(parent).ok().map(|parent| parent.join(name))
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// dedup_paths
+////////////////////////////////////////////////////////////////////////////////
+/// Takes an iterator of [`Path`]s and returns the indices of the first
+/// occurrence of each distinct path, in order.
+///
+/// ### Parameters
+/// 
+/// + `matching`: The [`PathMatch`] options determining when paths match.
+///
+/// [`Path`]: std::path::Path
+/// [`PathMatch`]: PathMatch
+pub fn dedup_paths<'p, P>(paths: P, matching: PathMatch) -> Vec<usize>
+    where P: IntoIterator<Item=&'p Path>
+{
+    let mut seen = HashSet::new();
+
+    paths.into_iter()
+        .enumerate()
+        .filter(|(_, p)| seen.insert(path_key(p, matching)))
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// pair_paths
+////////////////////////////////////////////////////////////////////////////////
+/// The result of pairing a path from one set with a path from another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathPair {
+    /// The path matches a path in both sets. Contains the index of the path in
+    /// each set.
+    Both(usize, usize),
+    /// The path only occurs in the first set. Contains its index.
+    OnlyA(usize),
+    /// The path only occurs in the second set. Contains its index.
+    OnlyB(usize),
+}
+
+/// Pairs the matching [`Path`]s of two sets, such as the relative paths of
+/// two directory trees.
+///
+/// The returned pairs are ordered by the paths of the first set, followed by
+/// the unpaired paths of the second set. If a set contains multiple matching
+/// paths, only the first is paired.
+///
+/// ### Parameters
+/// 
+/// + `matching`: The [`PathMatch`] options determining when paths match.
+///
+/// [`Path`]: std::path::Path
+/// [`PathMatch`]: PathMatch
+pub fn pair_paths<'a, 'b, A, B>(a: A, b: B, matching: PathMatch)
+    -> Vec<PathPair>
+    where
+        A: IntoIterator<Item=&'a Path>,
+        B: IntoIterator<Item=&'b Path>,
+{
+    let mut b_keys: HashMap<PathBuf, usize> = HashMap::new();
+    let mut b_order = Vec::new();
+    for (idx, p) in b.into_iter().enumerate() {
+        let key = path_key(p, matching);
+        if !b_keys.contains_key(&key) {
+            let _ = b_keys.insert(key.clone(), idx);
+            b_order.push(key);
+        }
+    }
+
+    let mut pairs = Vec::new();
+    for (idx, p) in a.into_iter().enumerate() {
+        match b_keys.remove(&path_key(p, matching)) {
+            Some(b_idx) => pairs.push(PathPair::Both(idx, b_idx)),
+            None        => pairs.push(PathPair::OnlyA(idx)),
+        }
+    }
+
+    pairs.extend(b_order
+        .iter()
+        .filter_map(|key| b_keys.get(key))
+        .map(|b_idx| PathPair::OnlyB(*b_idx)));
+
+    pairs
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// read_path_list
+////////////////////////////////////////////////////////////////////////////////
+/// Reads a list of paths from the given reader.
+///
+/// Paths are separated by newlines, or by NUL bytes if `nul_separated` is
+/// `true`. Empty entries are skipped. When separated by newlines, a trailing
+/// carriage return is removed from each path.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if the reader fails. On platforms other than
+/// unix, returns an error if a path is not valid UTF-8.
+///
+/// [`std::io::Error`]: std::io::Error
+pub fn read_path_list<R>(reader: R, nul_separated: bool)
+    -> Result<Vec<PathBuf>, std::io::Error>
+    where R: BufRead
+{
+    let separator = if nul_separated { b'\0' } else { b'\n' };
+
+    let mut paths = Vec::new();
+    for entry in reader.split(separator) {
+        let mut entry = entry?;
+        if !nul_separated && entry.last() == Some(&b'\r') {
+            let _ = entry.pop();
+        }
+        if entry.is_empty() { continue; }
+        paths.push(path_from_bytes(entry)?);
+    }
+    Ok(paths)
+}
+
+/// Converts raw bytes read from a path list into a path.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps)]
+fn path_from_bytes(bytes: Vec<u8>) -> Result<PathBuf, std::io::Error> {
+    use std::os::unix::ffi::OsStringExt as _;
+    Ok(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+/// Converts raw bytes read from a path list into a path.
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> Result<PathBuf, std::io::Error> {
+    String::from_utf8(bytes)
+        .map(PathBuf::from)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// expand_glob
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the paths matching the given glob pattern, in alphabetical order.
+///
+/// The expansion is performed independently of the shell, so patterns behave
+/// the same on all platforms.
+///
+/// ### Errors
+///
+/// Returns a [`GlobError`] if the pattern is invalid or a matched path can't
+/// be read.
+///
+/// [`GlobError`]: GlobError
+pub fn expand_glob(pattern: &str) -> Result<Vec<PathBuf>, GlobError> {
+    let mut paths = Vec::new();
+    for path in glob::glob(pattern)? {
+        paths.push(path?);
+    }
+    Ok(paths)
+}
+
+/// An error which occurs while expanding a glob pattern with
+/// [`expand_glob`].
+///
+/// [`expand_glob`]: expand_glob
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum GlobError {
+    /// The pattern is invalid.
+    #[error(transparent)]
+    Pattern(#[from] glob::PatternError),
+
+    /// A path matching the pattern can't be read.
+    #[error(transparent)]
+    Read(#[from] glob::GlobError),
+}
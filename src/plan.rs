@@ -0,0 +1,193 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Planning of comparison IO.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::compare::Comparator;
+use crate::ops::DiffOp;
+
+// Standard library imports.
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PlannedOp
+////////////////////////////////////////////////////////////////////////////////
+/// An IO operation which a comparison is expected to perform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlannedOp {
+    /// Wait for the given duration.
+    Sleep(Duration),
+    /// Read the metadata of a file.
+    Stat(PathBuf),
+    /// Open a file.
+    Open(PathBuf),
+    /// Read the content of a file.
+    Read {
+        /// The path of the file.
+        path: PathBuf,
+        /// The largest number of bytes expected to be read, if known.
+        bytes: Option<u64>,
+    },
+    /// Run a subprocess.
+    Spawn {
+        /// The command to run.
+        command: String,
+        /// The arguments to pass to the command.
+        args: Vec<String>,
+    },
+    /// Call a user-provided diff function, whose IO is unknown.
+    Custom(PathBuf, PathBuf),
+}
+
+impl std::fmt::Display for PlannedOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sleep(duration) => write!(f, "sleep\t{}",
+                humantime::format_duration(*duration)),
+            Self::Stat(path) => write!(f, "stat\t{}", path.display()),
+            Self::Open(path) => write!(f, "open\t{}", path.display()),
+            Self::Read { path, bytes: Some(bytes) } => write!(f,
+                "read\t{}\tup to {bytes} bytes", path.display()),
+            Self::Read { path, bytes: None } => write!(f,
+                "read\t{}\tunknown bytes", path.display()),
+            Self::Spawn { command, args } => write!(f, "spawn\t{command} {}",
+                args.join(" ")),
+            Self::Custom(a, b) => write!(f, "diff\t{}\t{}",
+                a.display(),
+                b.display()),
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// plan_compare
+////////////////////////////////////////////////////////////////////////////////
+/// Takes an iterator of [`Path`]s and returns the IO operations expected to be
+/// performed when selecting one of them with the given [`Comparator`].
+///
+/// Which pairs of files have their content compared depends on the files
+/// being compared, so each file after the first is planned to be compared
+/// against the first. Sorting compares more pairs than this. File sizes are
+/// read to estimate the number of bytes read, but no file is opened or read
+/// and no subprocess is run.
+///
+/// [`Path`]: std::path::Path
+/// [`Comparator`]: crate::Comparator
+pub fn plan_compare<'p, P>(paths: P, comparator: &Comparator)
+    -> Vec<PlannedOp>
+    where P: IntoIterator<Item=&'p Path>
+{
+    let paths: Vec<&Path> = paths.into_iter().collect();
+    let mut ops = Vec::new();
+
+    if let Some(delay) = comparator.stability_delay {
+        ops.extend(paths.iter().map(|p| PlannedOp::Stat(p.to_path_buf())));
+        ops.push(PlannedOp::Sleep(delay));
+        ops.extend(paths.iter().map(|p| PlannedOp::Stat(p.to_path_buf())));
+    }
+    for path in &paths {
+        ops.push(PlannedOp::Open(path.to_path_buf()));
+        ops.push(PlannedOp::Stat(path.to_path_buf()));
+    }
+    if let Some((first, rest)) = paths.split_first() {
+        for path in rest {
+            ops.extend(plan_diff(first, path, &comparator.diff_op));
+        }
+    }
+    ops
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// plan_diff
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the IO operations expected to be performed when comparing the
+/// content of the files at the given paths with the given [`DiffOp`].
+///
+/// File sizes are read to estimate the number of bytes read, but no file is
+/// opened or read and no subprocess is run.
+///
+/// [`DiffOp`]: crate::DiffOp
+#[must_use]
+pub fn plan_diff(a: &Path, b: &Path, diff_op: &DiffOp) -> Vec<PlannedOp> {
+    match diff_op {
+        DiffOp::None => Vec::new(),
+
+        DiffOp::Internal => plan_read_same_size(a, b, |len| len),
+        DiffOp::InternalWith(options) => plan_read_same_size(a, b, |len| {
+            options.max_read_bytes.map_or(len, |max| max.min(len))
+        }),
+        DiffOp::Sampled(options) => {
+            plan_read_same_size(a, b, |len| options.sampled_len(len))
+        },
+
+        #[cfg(feature = "serde")]
+        DiffOp::Json => plan_read_all(a, b),
+        #[cfg(feature = "yaml")]
+        DiffOp::Yaml => plan_read_all(a, b),
+        #[cfg(feature = "archive")]
+        DiffOp::Archive => plan_read_all(a, b),
+
+        DiffOp::Text(_) | DiffOp::Decompressed(_) => plan_read_all(a, b),
+        DiffOp::Subprocess { command, args } => vec![PlannedOp::Spawn {
+            command: (*command).to_string(),
+            args: args
+                .iter()
+                .map(|arg| (*arg).to_string())
+                .chain([a, b].map(|p| p.display().to_string()))
+                .collect(),
+        }],
+
+        DiffOp::Custom(_) => vec![
+            PlannedOp::Custom(a.to_path_buf(), b.to_path_buf())
+        ],
+    }
+}
+
+/// Returns the IO operations expected to be performed when reading both files
+/// at the given paths only if they have the same size, using the given
+/// function to estimate the number of bytes read from files of a given size.
+fn plan_read_same_size<F>(a: &Path, b: &Path, bytes: F) -> Vec<PlannedOp>
+    where F: Fn(u64) -> u64
+{
+    let size = |p: &Path| std::fs::metadata(p).ok().map(|m| m.len());
+    // Files of different sizes are not read.
+    let bytes = match (size(a), size(b)) {
+        (Some(len_a), Some(len_b)) if len_a != len_b => Some(0),
+        (Some(len), Some(_)) => Some(bytes(len)),
+        _ => None,
+    };
+    let mut ops = vec![
+        PlannedOp::Open(a.to_path_buf()),
+        PlannedOp::Open(b.to_path_buf()),
+        PlannedOp::Stat(a.to_path_buf()),
+        PlannedOp::Stat(b.to_path_buf()),
+    ];
+    if bytes != Some(0) {
+        ops.push(PlannedOp::Read { path: a.to_path_buf(), bytes });
+        ops.push(PlannedOp::Read { path: b.to_path_buf(), bytes });
+    }
+    ops
+}
+
+/// Returns the IO operations expected to be performed when reading the whole
+/// of both files at the given paths.
+fn plan_read_all(a: &Path, b: &Path) -> Vec<PlannedOp> {
+    let size = |p: &Path| std::fs::metadata(p).ok().map(|m| m.len());
+    vec![
+        PlannedOp::Open(a.to_path_buf()),
+        PlannedOp::Open(b.to_path_buf()),
+        PlannedOp::Read { path: a.to_path_buf(), bytes: size(a) },
+        PlannedOp::Read { path: b.to_path_buf(), bytes: size(b) },
+    ]
+}
@@ -0,0 +1,94 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Progress reporting.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+use indicatif::HumanBytes;
+use indicatif::ProgressBar;
+use indicatif::ProgressStyle;
+
+// Standard library imports.
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Progress
+////////////////////////////////////////////////////////////////////////////////
+/// A receiver of progress updates from a comparison.
+///
+/// A `Progress` is called from the threads performing the comparison, so its
+/// methods should return quickly.
+pub trait Progress: std::fmt::Debug + Send + Sync {
+    /// Called when the given number of files are about to be processed.
+    fn start(&self, _files: u64) {}
+
+    /// Called after each file's metadata has been read.
+    fn file_processed(&self) {}
+
+    /// Called after each content comparison with the number of bytes read
+    /// from each file.
+    fn bytes_compared(&self, _bytes: u64) {}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ProgressBarReporter
+////////////////////////////////////////////////////////////////////////////////
+/// A [`Progress`] which draws a progress bar of the files processed and bytes
+/// compared to stderr. Nothing is drawn if stderr is not a terminal.
+///
+/// [`Progress`]: Progress
+#[derive(Debug)]
+pub struct ProgressBarReporter {
+    /// The progress bar.
+    bar: ProgressBar,
+    /// The total number of bytes compared.
+    bytes: AtomicU64,
+}
+
+impl ProgressBarReporter {
+    /// Returns a `ProgressBarReporter` drawing to stderr.
+    #[must_use]
+    pub fn new() -> Self {
+        let bar = ProgressBar::new(0);
+        bar.set_style(ProgressStyle::with_template(
+                "[{elapsed_precise}] {wide_bar} {pos}/{len} files {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar()));
+        Self { bar, bytes: AtomicU64::new(0) }
+    }
+
+    /// Clears the progress bar from the terminal.
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+impl Default for ProgressBarReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Progress for ProgressBarReporter {
+    fn start(&self, files: u64) {
+        self.bar.inc_length(files);
+    }
+
+    fn file_processed(&self) {
+        self.bar.inc(1);
+    }
+
+    fn bytes_compared(&self, bytes: u64) {
+        let total = self.bytes
+            .fetch_add(bytes, Ordering::Relaxed)
+            .saturating_add(bytes);
+        self.bar.set_message(format!("({} compared)", HumanBytes(total)));
+    }
+}
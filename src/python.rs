@@ -0,0 +1,340 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Python bindings.
+//!
+//! The `fcmp` Python module is built from the library with the `python`
+//! feature, e.g., using `maturin build --features python`.
+////////////////////////////////////////////////////////////////////////////////
+// Doc comments of exported items are Python docstrings, and Python arguments
+// are extracted as owned values.
+#![allow(clippy::doc_markdown)]
+#![allow(clippy::needless_pass_by_value)]
+
+// Internal library imports.
+use crate::compare::compare_all;
+use crate::compare::Comparator;
+use crate::compare::CompareOutcome;
+use crate::error::FcmpError;
+use crate::ops::DiffOp;
+use crate::report::compare_all_report;
+use crate::report::CompareReport;
+use crate::report::DecisionBasis;
+use crate::report::EntryStatus;
+use crate::report::ReportEntry;
+
+// External library imports.
+use pyo3::exceptions::PyFileNotFoundError;
+use pyo3::exceptions::PyOSError;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::PyTypeError;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+// Standard library imports.
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Module
+////////////////////////////////////////////////////////////////////////////////
+/// Compare files by modification time, size, and content.
+#[pymodule]
+fn fcmp(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(newest, module)?)?;
+    module.add_function(wrap_pyfunction!(are_equal, module)?)?;
+    module.add_function(wrap_pyfunction!(report, module)?)?;
+    module.add_class::<PyCompareReport>()?;
+    module.add_class::<PyReportEntry>()?;
+    Ok(())
+}
+
+impl From<FcmpError> for PyErr {
+    fn from(error: FcmpError) -> Self {
+        match error {
+            FcmpError::Missing { .. }
+                | FcmpError::BrokenSymlink { .. }
+                => PyFileNotFoundError::new_err(error.to_string()),
+            FcmpError::Cancelled => PyRuntimeError::new_err(error.to_string()),
+            _ => PyOSError::new_err(error.to_string()),
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// newest
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the path of the most recently modified file. If several files
+/// are the most recently modified, the first of them is returned.
+///
+/// Options:
+///     key: The property used to order files, e.g., 'modified' or 'size'.
+///     fallback_key: The property used when `key` is unavailable.
+///     diff: The content comparison, e.g., 'none', 'internal', or 'cmp'.
+///     missing: How missing files are handled, e.g., 'error' or 'oldest'.
+///     mtime_tolerance: Seconds within which modification times are equal.
+///     reverse: If true, returns the least recently modified file instead.
+///
+/// Raises FileNotFoundError if a file is missing and `missing` is 'error',
+/// and OSError if a file can't be read.
+#[pyfunction]
+#[pyo3(signature = (paths, **options))]
+fn newest(
+    py: Python<'_>,
+    paths: Vec<PathBuf>,
+    options: Option<&Bound<'_, PyDict>>)
+    -> PyResult<PathBuf>
+{
+    let (comparator, reverse) = comparator(options)?;
+    let idx = py.allow_threads(|| compare_all(
+        paths.iter().map(PathBuf::as_path),
+        reverse,
+        &comparator))?;
+    paths.get(idx)
+        .cloned()
+        .ok_or_else(|| PyValueError::new_err("no paths given"))
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// are_equal
+////////////////////////////////////////////////////////////////////////////////
+/// Returns True if the files at the given paths have the same content,
+/// according to the given content comparison, which is 'internal' by
+/// default. A missing file is different from an existing file, and two
+/// missing files are the same.
+///
+/// Raises OSError if either file can't be read.
+#[pyfunction]
+#[pyo3(signature = (a, b, diff = None))]
+fn are_equal(
+    py: Python<'_>,
+    a: PathBuf,
+    b: PathBuf,
+    diff: Option<&Bound<'_, PyAny>>)
+    -> PyResult<bool>
+{
+    let diff_op: DiffOp = diff
+        .map(|diff| parse("diff", diff))
+        .transpose()?
+        .unwrap_or_else(DiffOp::internal);
+    Ok(!py.allow_threads(|| diff_op.diff(&a, &b))?)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// report
+////////////////////////////////////////////////////////////////////////////////
+/// Returns a CompareReport recording how the most recently modified file was
+/// selected. Takes the same options as `newest`.
+#[pyfunction]
+#[pyo3(signature = (paths, **options))]
+fn report(
+    py: Python<'_>,
+    paths: Vec<PathBuf>,
+    options: Option<&Bound<'_, PyDict>>)
+    -> PyResult<PyCompareReport>
+{
+    let (comparator, reverse) = comparator(options)?;
+    let report = py.allow_threads(|| compare_all_report(
+        paths.iter().map(PathBuf::as_path),
+        reverse,
+        &comparator))?;
+    Ok(PyCompareReport::from(report))
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PyCompareReport
+////////////////////////////////////////////////////////////////////////////////
+/// A record of how a set of files was compared to select the newest file.
+///
+/// Attributes:
+///     entries: A ReportEntry for each of the given paths, in order.
+///     ties: The indices of every file which ties for the newest file.
+///     selected: The index of the selected file, or None.
+#[pyclass(name = "CompareReport", module = "fcmp", frozen)]
+#[derive(Debug, Clone)]
+struct PyCompareReport {
+    /// The entries of the report.
+    #[pyo3(get)]
+    entries: Vec<PyReportEntry>,
+    /// The indices of the tied files.
+    #[pyo3(get)]
+    ties: Vec<usize>,
+    /// The human-readable explanation of the report.
+    text: String,
+}
+
+#[pymethods]
+impl PyCompareReport {
+    /// The index of the selected file, or None if no file was compared.
+    #[getter]
+    fn selected(&self) -> Option<usize> {
+        self.ties.first().copied()
+    }
+
+    /// Returns the report as a human-readable explanation.
+    fn __str__(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns a summary of the report.
+    fn __repr__(&self) -> String {
+        format!("CompareReport(entries={}, ties={:?})",
+            self.entries.len(),
+            self.ties)
+    }
+}
+
+impl From<CompareReport> for PyCompareReport {
+    fn from(report: CompareReport) -> Self {
+        let text = report.to_string();
+        Self {
+            entries: report.entries
+                .into_iter()
+                .map(PyReportEntry::from)
+                .collect(),
+            ties: report.ties,
+            text,
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PyReportEntry
+////////////////////////////////////////////////////////////////////////////////
+/// The record of a single file in a CompareReport.
+///
+/// Attributes:
+///     path: The path of the file.
+///     status: 'found', 'missing', or 'excluded'.
+///     size: The size of the file in bytes, or None.
+///     modified: The modification time of the file as a datetime, or None.
+///     compared_to: The index of the file this file was compared against.
+///     outcome: 'greater', 'less', 'equal', or 'incomparable', or None.
+///     basis: What determined the outcome: 'same_file', 'content',
+///         'missing', 'key', or 'tie_break', or None.
+///     key: The property which determined the outcome, or None.
+#[pyclass(name = "ReportEntry", module = "fcmp", frozen, get_all)]
+#[derive(Debug, Clone)]
+struct PyReportEntry {
+    /// The path of the file.
+    path: PathBuf,
+    /// The status of the file.
+    status: &'static str,
+    /// The size of the file.
+    size: Option<u64>,
+    /// The modification time of the file.
+    modified: Option<SystemTime>,
+    /// The index of the file this file was compared against.
+    compared_to: Option<usize>,
+    /// The outcome of the comparison.
+    outcome: Option<&'static str>,
+    /// What determined the outcome.
+    basis: Option<&'static str>,
+    /// The key which determined the outcome.
+    key: Option<String>,
+}
+
+#[pymethods]
+impl PyReportEntry {
+    /// Returns a summary of the entry.
+    fn __repr__(&self) -> String {
+        format!("ReportEntry(path='{}', status='{}')",
+            self.path.display(),
+            self.status)
+    }
+}
+
+impl From<ReportEntry> for PyReportEntry {
+    fn from(entry: ReportEntry) -> Self {
+        let (basis, key) = match entry.decision.map(|d| d.basis) {
+            None => (None, None),
+            Some(DecisionBasis::SameFile) => (Some("same_file"), None),
+            Some(DecisionBasis::Content)  => (Some("content"), None),
+            Some(DecisionBasis::Missing)  => (Some("missing"), None),
+            Some(DecisionBasis::Key(key)) => (Some("key"), Some(key)),
+            Some(DecisionBasis::TieBreak(key)) => {
+                (Some("tie_break"), Some(key))
+            },
+        };
+        Self {
+            path: entry.path,
+            status: match entry.status {
+                EntryStatus::Found    => "found",
+                EntryStatus::Missing  => "missing",
+                EntryStatus::Excluded => "excluded",
+            },
+            size: entry.metadata.and_then(|m| m.size),
+            modified: entry.metadata.and_then(|m| m.modified),
+            compared_to: entry.compared_to,
+            outcome: entry.decision.map(|d| match d.outcome {
+                CompareOutcome::Ordered(Ordering::Greater) => "greater",
+                CompareOutcome::Ordered(Ordering::Less)    => "less",
+                CompareOutcome::Ordered(Ordering::Equal)   => "equal",
+                CompareOutcome::Incomparable(_)            => "incomparable",
+            }),
+            basis,
+            key: key.map(|key| format!("{key:?}").to_lowercase()),
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helpers
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the [`Comparator`] given by the keyword options of a Python call,
+/// along with whether to reverse the comparison order.
+///
+/// [`Comparator`]: crate::Comparator
+fn comparator(options: Option<&Bound<'_, PyDict>>)
+    -> PyResult<(Comparator, bool)>
+{
+    let mut comparator = Comparator::default();
+    let mut reverse = false;
+    let Some(options) = options else { return Ok((comparator, reverse)) };
+
+    for (name, value) in options {
+        let name: String = name.extract()?;
+        match name.as_str() {
+            "key"          => comparator.key = parse(&name, &value)?,
+            "fallback_key" => {
+                comparator.fallback_key = Some(parse(&name, &value)?);
+            },
+            "diff"         => comparator.diff_op = parse(&name, &value)?,
+            "missing"      => comparator.missing = parse(&name, &value)?,
+            "mtime_tolerance" => {
+                let secs: f64 = value.extract()?;
+                comparator.mtime_tolerance = Duration::try_from_secs_f64(secs)
+                    .map_err(|e| PyValueError::new_err(format!(
+                        "invalid mtime_tolerance {secs}: {e}")))?;
+            },
+            "reverse"      => reverse = value.extract()?,
+            _ => return Err(PyTypeError::new_err(format!(
+                "unexpected keyword argument '{name}'"))),
+        }
+    }
+    Ok((comparator, reverse))
+}
+
+/// Parses the string value of the named option.
+fn parse<T>(name: &str, value: &Bound<'_, PyAny>) -> PyResult<T>
+    where T: FromStr
+{
+    let value: String = value.extract()?;
+    value.parse().ok().ok_or_else(|| PyValueError::new_err(format!(
+        "invalid {name} '{value}'")))
+}
@@ -0,0 +1,160 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Remote files accessed over HTTP(S).
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+use ureq::Agent;
+use ureq::Response;
+
+// Standard library imports.
+use std::io::Read;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The time to wait for a connection to a remote server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+
+////////////////////////////////////////////////////////////////////////////////
+// RemoteMetadata
+////////////////////////////////////////////////////////////////////////////////
+/// The metadata of a remote file, taken from the headers of a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteMetadata {
+    /// The URL of the file after following any redirects.
+    pub url: String,
+    /// The size of the file in bytes, from the `Content-Length` header.
+    pub len: Option<u64>,
+    /// The modification time of the file, from the `Last-Modified` header.
+    pub modified: Option<SystemTime>,
+    /// The entity tag of the file, from the `ETag` header.
+    pub etag: Option<String>,
+}
+
+impl RemoteMetadata {
+    /// Returns the `RemoteMetadata` given by the headers of the response.
+    fn from_response(response: &Response) -> Self {
+        Self {
+            url: response.get_url().to_string(),
+            len: response.header("Content-Length")
+                .and_then(|len| len.trim().parse().ok()),
+            modified: response.header("Last-Modified")
+                .and_then(|date| httpdate::parse_http_date(date).ok()),
+            etag: response.header("ETag").map(str::to_string),
+        }
+    }
+
+    /// Returns `true` if both files are the same resource and their entity
+    /// tags are strong and equal, which means the files have identical
+    /// content.
+    ///
+    /// Entity tags are only comparable for the same resource, as a server may
+    /// assign the same tag to different resources, so files at different URLs
+    /// never have the same entity tag.
+    #[must_use]
+    pub fn same_etag(&self, other: &Self) -> bool {
+        if self.url != other.url { return false; }
+        match (&self.etag, &other.etag) {
+            (Some(a), Some(b)) => !a.starts_with("W/") && a == b,
+            _                  => false,
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// RemoteUrl
+////////////////////////////////////////////////////////////////////////////////
+/// The URL of a remote file, given in place of a path as in
+/// `https://example.com/release.tar.gz`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteUrl {
+    /// The URL of the file.
+    pub url: String,
+}
+
+impl RemoteUrl {
+    /// Parses a `RemoteUrl` from the given path. Returns `None` if the path
+    /// does not start with `http://` or `https://`.
+    #[must_use]
+    pub fn parse(path: &Path) -> Option<Self> {
+        let url = path.to_str()?;
+        let scheme = url.split_once("://")?.0;
+        if !scheme.eq_ignore_ascii_case("http")
+            && !scheme.eq_ignore_ascii_case("https")
+        {
+            return None;
+        }
+        Some(Self { url: url.to_string() })
+    }
+
+    /// Returns the metadata of the file using a `HEAD` request, or `None` if
+    /// the server has no such file. Servers which don't allow `HEAD` requests
+    /// are sent a `GET` request whose body is not read.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the request fails.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn metadata(&self) -> Result<Option<RemoteMetadata>, std::io::Error> {
+        Ok(self.request("HEAD")?
+            .as_ref()
+            .map(RemoteMetadata::from_response))
+    }
+
+    /// Returns a reader of the file's content using a `GET` request, or
+    /// `None` if the server has no such file. The content is downloaded as
+    /// it is read.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the request fails.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn read(&self)
+        -> Result<Option<Box<dyn Read + Send + Sync>>, std::io::Error>
+    {
+        Ok(self.request("GET")?.map(Response::into_reader))
+    }
+
+    /// Sends a request for the file with the given method, returning the
+    /// response, or `None` if the server has no such file.
+    fn request(&self, method: &str)
+        -> Result<Option<Response>, std::io::Error>
+    {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(method, url = %self.url, "sending HTTP request");
+        match agent().request(method, &self.url).call() {
+            Ok(response) => Ok(Some(response)),
+            Err(ureq::Error::Status(404 | 410, _)) => Ok(None),
+            Err(ureq::Error::Status(405 | 501, _)) if method == "HEAD" => {
+                self.request("GET")
+            },
+            Err(ureq::Error::Status(status, _)) => Err(std::io::Error::other(
+                format!("{}: HTTP status {status}", self.url))),
+            Err(ureq::Error::Transport(e)) => Err(std::io::Error::other(
+                format!("{}: {e}", self.url))),
+        }
+    }
+}
+
+/// Returns the agent shared by all requests.
+fn agent() -> &'static Agent {
+    static AGENT: OnceLock<Agent> = OnceLock::new();
+    AGENT.get_or_init(|| ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .build())
+}
@@ -0,0 +1,498 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Detailed comparison reports.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::compare::Comparator;
+use crate::compare::CompareKey;
+use crate::compare::CompareOutcome;
+use crate::compare::FileCmp;
+use crate::error::FcmpError;
+use crate::format::TimeStyle;
+
+// External library imports.
+use console::Style;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+// Standard library imports.
+use std::cmp::Ordering;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// CompareReport
+////////////////////////////////////////////////////////////////////////////////
+/// A record of how a set of files was compared to select the greatest file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct CompareReport {
+    /// A [`ReportEntry`] for each of the given paths, in order.
+    ///
+    /// [`ReportEntry`]: ReportEntry
+    pub entries: Vec<ReportEntry>,
+    /// The indices of every file which ties for the greatest file, in order.
+    /// Empty if no files were compared.
+    pub ties: Vec<usize>,
+}
+
+impl CompareReport {
+    /// Returns the index of the selected file, if any file was compared.
+    #[must_use]
+    pub fn selected(&self) -> Option<usize> {
+        self.ties.first().copied()
+    }
+
+    /// Returns the report as a human-readable explanation, writing times in
+    /// the given [`TimeStyle`] relative to the given current time.
+    ///
+    /// [`TimeStyle`]: crate::TimeStyle
+    #[must_use]
+    pub fn explain(&self, style: TimeStyle, now: SystemTime)
+        -> Explanation<'_>
+    {
+        Explanation { report: self, style, now, styled: false }
+    }
+}
+
+/// Formats the report as a human-readable explanation, with a line for each
+/// file giving its modification time, size, and how it compared against the
+/// greatest file found before it.
+impl std::fmt::Display for CompareReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.explain(TimeStyle::default(), SystemTime::now()).fmt(f)
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Explanation
+////////////////////////////////////////////////////////////////////////////////
+/// A human-readable explanation of a [`CompareReport`], with a line for each
+/// file giving its modification time, size, and how it compared against the
+/// greatest file found before it.
+///
+/// [`CompareReport`]: CompareReport
+#[derive(Debug, Clone, Copy)]
+pub struct Explanation<'r> {
+    /// The report to explain.
+    report: &'r CompareReport,
+    /// The style used to write times.
+    style: TimeStyle,
+    /// The current time, which relative times are relative to.
+    now: SystemTime,
+    /// Whether the selected file is highlighted and missing files are dimmed
+    /// using terminal styles.
+    styled: bool,
+}
+
+impl Explanation<'_> {
+    /// Returns the explanation with the selected file highlighted and missing
+    /// files dimmed using terminal styles, if `styled` is `true`.
+    #[must_use]
+    pub fn styled(mut self, styled: bool) -> Self {
+        self.styled = styled;
+        self
+    }
+}
+
+impl std::fmt::Display for Explanation<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let report = self.report;
+        // The paths are aligned in a column.
+        let width = report.entries
+            .iter()
+            .map(|e| e.path.display().to_string().chars().count() + 1)
+            .max()
+            .unwrap_or(0);
+        for (idx, entry) in report.entries.iter().enumerate() {
+            let label = format!("{}:", entry.path.display());
+            let label = format!("{label:<width$}");
+            let mut line = String::new();
+            match (&entry.metadata, entry.status) {
+                (Some(metadata), _) => {
+                    match metadata.modified {
+                        Some(t) => write!(line, "modified {}", self.style
+                            .format_seconds(t, self.now))?,
+                        None => write!(line, "modified unknown")?,
+                    }
+                    match metadata.size {
+                        Some(size) => write!(line, ", {size} bytes")?,
+                        None => write!(line, ", unknown size")?,
+                    }
+                },
+                (None, EntryStatus::Excluded) => write!(line, "excluded")?,
+                (None, _) => write!(line, "missing")?,
+            }
+
+            let other = entry.compared_to
+                .and_then(|i| report.entries.get(i))
+                .map(|e| e.path.display());
+            if let (Some(decision), Some(other)) = (&entry.decision, other) {
+                use CompareOutcome::*;
+                write!(line, "; ")?;
+                match (decision.outcome, decision.basis) {
+                    (Incomparable(reason), _) => write!(line,
+                        "incomparable with '{other}': {reason}")?,
+                    (_, DecisionBasis::SameFile) => write!(line,
+                        "the same file as '{other}'")?,
+                    (_, DecisionBasis::Content) => write!(line,
+                        "equal to '{other}' by content")?,
+                    (Ordered(o), DecisionBasis::Missing)
+                        if entry.status == EntryStatus::Missing => write!(line,
+                        "{} than '{other}' as a missing file",
+                        relation(CompareKey::Modified, o))?,
+                    (Ordered(o), DecisionBasis::Missing) => write!(line,
+                        "{} than missing file '{other}'",
+                        relation(CompareKey::Modified, o))?,
+                    (Ordered(Ordering::Equal), DecisionBasis::Key(key)) => {
+                        write!(line,
+                            "equal to '{other}' by {}",
+                            key_name(key))?;
+                    },
+                    (Ordered(o), DecisionBasis::Key(key)) => write!(line,
+                        "{} than '{other}' by {}",
+                        relation(key, o),
+                        key_name(key))?,
+                    (Ordered(o), DecisionBasis::TieBreak(key)) => write!(line,
+                        "{} than '{other}' by tie-breaking {}",
+                        relation(key, o),
+                        key_name(key))?,
+                }
+            }
+
+            let selected = report.selected() == Some(idx);
+            if selected {
+                write!(line, "; selected")?;
+            } else if report.ties.contains(&idx) {
+                write!(line, "; tied")?;
+            }
+
+            match (self.styled, selected, &entry.metadata) {
+                (true, true, _) => writeln!(f, "{} {line}",
+                    Style::new().green().bold().force_styling(true)
+                        .apply_to(label))?,
+                (true, false, None) => writeln!(f, "{}",
+                    Style::new().dim().force_styling(true)
+                        .apply_to(format!("{label} {line}")))?,
+                _ => writeln!(f, "{label} {line}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the name of the given [`CompareKey`] used in explanations.
+///
+/// [`CompareKey`]: crate::CompareKey
+fn key_name(key: CompareKey) -> &'static str {
+    match key {
+        CompareKey::Modified => "modification time",
+        CompareKey::Changed  => "status change time",
+        CompareKey::Accessed => "access time",
+        CompareKey::Created  => "creation time",
+        CompareKey::Size     => "size",
+        CompareKey::Name     => "name",
+        CompareKey::Path     => "path",
+        CompareKey::Digest   => "digest",
+    }
+}
+
+/// Returns the word describing a file which has the given ordering relative
+/// to another file by the given [`CompareKey`].
+///
+/// [`CompareKey`]: crate::CompareKey
+fn relation(key: CompareKey, ordering: Ordering) -> &'static str {
+    match (key, ordering) {
+        (_, Ordering::Equal) => "equal",
+        (CompareKey::Size, Ordering::Greater) => "larger",
+        (CompareKey::Size, Ordering::Less) => "smaller",
+        (CompareKey::Name | CompareKey::Path | CompareKey::Digest,
+            Ordering::Greater) => "greater",
+        (CompareKey::Name | CompareKey::Path | CompareKey::Digest,
+            Ordering::Less) => "less",
+        (_, Ordering::Greater) => "newer",
+        (_, Ordering::Less) => "older",
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ReportEntry
+////////////////////////////////////////////////////////////////////////////////
+/// The record of a single file in a [`CompareReport`].
+///
+/// [`CompareReport`]: CompareReport
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ReportEntry {
+    /// The path of the file.
+    pub path: PathBuf,
+    /// The [`EntryStatus`] of the file.
+    ///
+    /// [`EntryStatus`]: EntryStatus
+    pub status: EntryStatus,
+    /// The file's metadata, if it was found.
+    pub metadata: Option<MetadataSnapshot>,
+    /// The index of the file this file was compared against, which was the
+    /// greatest file found before it. `None` for the first file compared and
+    /// for files which were not compared.
+    pub compared_to: Option<usize>,
+    /// The [`Decision`] made when comparing this file against the file given
+    /// by `compared_to`.
+    ///
+    /// [`Decision`]: Decision
+    pub decision: Option<Decision>,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// EntryStatus
+////////////////////////////////////////////////////////////////////////////////
+/// Whether a file in a [`CompareReport`] was compared.
+///
+/// [`CompareReport`]: CompareReport
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum EntryStatus {
+    /// The file was found and compared.
+    Found,
+    /// The file is missing, and was compared according to the
+    /// [`MissingFileBehavior`].
+    ///
+    /// [`MissingFileBehavior`]: crate::MissingFileBehavior
+    Missing,
+    /// The file was excluded by the [`Comparator`]'s policies, e.g., because
+    /// it is missing and ignored, filtered, unsettled, or unstable.
+    ///
+    /// [`Comparator`]: crate::Comparator
+    Excluded,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// MetadataSnapshot
+////////////////////////////////////////////////////////////////////////////////
+/// The metadata of a file at the time it was compared. Each property is
+/// `None` if it is unavailable. Times are serialized as RFC 3339 timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct MetadataSnapshot {
+    /// The size of the file in bytes.
+    pub size: Option<u64>,
+    /// The modification time of the file.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_time"))]
+    pub modified: Option<SystemTime>,
+    /// The status change time of the file.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_time"))]
+    pub changed: Option<SystemTime>,
+    /// The last access time of the file.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_time"))]
+    pub accessed: Option<SystemTime>,
+    /// The creation time of the file.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_time"))]
+    pub created: Option<SystemTime>,
+}
+
+impl MetadataSnapshot {
+    /// Returns the metadata of the given [`FileCmp`].
+    ///
+    /// [`FileCmp`]: crate::FileCmp
+    fn of(file_cmp: &FileCmp) -> Self {
+        Self {
+            size: file_cmp.size(),
+            modified: file_cmp.modified(),
+            changed: file_cmp.changed(),
+            accessed: file_cmp.accessed(),
+            created: file_cmp.created(),
+        }
+    }
+}
+
+/// Serializes an optional time as an RFC 3339 timestamp.
+#[cfg(feature = "serde")]
+#[allow(clippy::ref_option)] // Required by serialize_with.
+fn serialize_time<S>(time: &Option<SystemTime>, serializer: S)
+    -> Result<S::Ok, S::Error>
+    where S: serde::Serializer
+{
+    match time {
+        Some(time) => serializer.collect_str(
+            &humantime::format_rfc3339_nanos(*time)),
+        None => serializer.serialize_none(),
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Decision
+////////////////////////////////////////////////////////////////////////////////
+/// The result of comparing two files, along with what determined it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Decision {
+    /// The [`CompareOutcome`] of the comparison.
+    ///
+    /// [`CompareOutcome`]: crate::CompareOutcome
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_outcome"))]
+    pub outcome: CompareOutcome,
+    /// The [`DecisionBasis`] which determined the outcome.
+    ///
+    /// [`DecisionBasis`]: DecisionBasis
+    pub basis: DecisionBasis,
+    /// Whether the content of the files was compared.
+    pub content_compared: bool,
+}
+
+/// Serializes a [`CompareOutcome`] as 'greater', 'less', 'equal', or
+/// 'incomparable'.
+///
+/// [`CompareOutcome`]: crate::CompareOutcome
+#[cfg(feature = "serde")]
+#[allow(clippy::trivially_copy_pass_by_ref)] // Required by serialize_with.
+fn serialize_outcome<S>(outcome: &CompareOutcome, serializer: S)
+    -> Result<S::Ok, S::Error>
+    where S: serde::Serializer
+{
+    serializer.serialize_str(match outcome {
+        CompareOutcome::Ordered(Ordering::Greater) => "greater",
+        CompareOutcome::Ordered(Ordering::Less)    => "less",
+        CompareOutcome::Ordered(Ordering::Equal)   => "equal",
+        CompareOutcome::Incomparable(_)            => "incomparable",
+    })
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// DecisionBasis
+////////////////////////////////////////////////////////////////////////////////
+/// What determined the outcome of comparing two files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum DecisionBasis {
+    /// The paths refer to the same file.
+    SameFile,
+    /// The files have the same content.
+    Content,
+    /// One or both files are missing.
+    Missing,
+    /// The files were ordered by the given [`CompareKey`]. Files with equal
+    /// keys and no tie-breaking key have this basis.
+    ///
+    /// [`CompareKey`]: crate::CompareKey
+    Key(CompareKey),
+    /// The files had equal keys and were ordered by the given tie-breaking
+    /// [`CompareKey`].
+    ///
+    /// [`CompareKey`]: crate::CompareKey
+    TieBreak(CompareKey),
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// compare_all_report
+////////////////////////////////////////////////////////////////////////////////
+/// Takes an iterator of [`Path`]s and returns a [`CompareReport`] recording
+/// how the greatest file was selected according to the given [`Comparator`].
+///
+/// Each file is compared against the greatest file found before it, as
+/// [`compare_all_ties`] does, and the ties of the report are the same as
+/// those it returns.
+///
+/// ### Parameters
+///
+/// + `reverse`: Whether to reverse to comparison order and select the least
+///   file.
+/// + `comparator`: The [`Comparator`] used to order the files.
+///
+/// ### Errors
+///
+/// Returns an [`FcmpError`] if a provided file is missing, unreadable, or
+/// otherwise rejected by the `Comparator`'s policies, or if reading or
+/// comparing the files results in an unexpected IO error.
+///
+/// [`FcmpError`]: crate::FcmpError
+/// [`Path`]: std::path::Path
+/// [`CompareReport`]: CompareReport
+/// [`Comparator`]: crate::Comparator
+/// [`compare_all_ties`]: crate::compare_all_ties
+pub fn compare_all_report<'p, P>(
+    paths: P,
+    reverse: bool,
+    comparator: &Comparator)
+    -> Result<CompareReport, FcmpError>
+    where P: IntoIterator<Item=&'p Path>
+{
+    let paths: Vec<&Path> = paths.into_iter().collect();
+    let unstable = comparator.find_unstable(&paths);
+    let files = comparator.open_all(&paths, &unstable)?;
+
+    let mut entries = Vec::with_capacity(paths.len());
+    let mut ties = Vec::new();
+    let mut greatest: Option<FileCmp> = None;
+
+    for (idx, (path, curr)) in paths.iter().zip(files).enumerate() {
+        comparator.check_cancelled()?;
+        let mut entry = ReportEntry {
+            path: path.to_path_buf(),
+            status: EntryStatus::Excluded,
+            metadata: None,
+            compared_to: None,
+            decision: None,
+        };
+        let Some(curr) = curr else {
+            entries.push(entry);
+            continue;
+        };
+        if curr.is_found() {
+            entry.status = EntryStatus::Found;
+            entry.metadata = Some(MetadataSnapshot::of(&curr));
+        } else {
+            entry.status = EntryStatus::Missing;
+        }
+
+        let Some(prev) = greatest.as_ref() else {
+            greatest = Some(curr);
+            ties.push(idx);
+            entries.push(entry);
+            continue;
+        };
+
+        let mut decision = prev.try_decide(&curr, comparator)
+            .map_err(|e| FcmpError::diff(
+                prev.path().to_path_buf(),
+                path.to_path_buf(),
+                e))?;
+        // Record the outcome of this file relative to the greatest file.
+        decision.outcome = decision.outcome.reverse();
+        entry.compared_to = ties.first().copied();
+        entry.decision = Some(decision);
+        entries.push(entry);
+
+        let cmp = decision.outcome
+            .ordering()
+            .map(|o| if reverse { o.reverse() } else { o });
+        match cmp {
+            Some(Ordering::Greater) => {
+                greatest = Some(curr);
+                ties.clear();
+                ties.push(idx);
+            },
+            Some(Ordering::Equal) => ties.push(idx),
+            _ => (),
+        }
+    }
+
+    Ok(CompareReport { entries, ties })
+}
@@ -0,0 +1,217 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Resumable content comparison.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+use serde::Deserialize;
+use serde::Serialize;
+
+// Standard library imports.
+use std::fs::File;
+use std::io::ErrorKind;
+use std::io::Read as _;
+use std::io::Seek as _;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+
+/// The number of bytes read from each file at a time.
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// The number of bytes compared between saved checkpoints.
+const CHECKPOINT_INTERVAL: u64 = 256 << 20;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Checkpoint
+////////////////////////////////////////////////////////////////////////////////
+/// The progress of an interrupted content comparison of two files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The path of the first file.
+    pub a: PathBuf,
+    /// The path of the second file.
+    pub b: PathBuf,
+    /// The length of both files.
+    pub len: u64,
+    /// The modification time of the first file.
+    pub modified_a: SystemTime,
+    /// The modification time of the second file.
+    pub modified_b: SystemTime,
+    /// The number of leading bytes known to be equal.
+    pub offset: u64,
+}
+
+impl Checkpoint {
+    /// Returns `true` if the checkpoint applies to the given files in their
+    /// current state.
+    fn matches(&self, other: &Self) -> bool {
+        self.a == other.a
+            && self.b == other.b
+            && self.len == other.len
+            && self.modified_a == other.modified_a
+            && self.modified_b == other.modified_b
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ResumeState
+////////////////////////////////////////////////////////////////////////////////
+/// The checkpoints of interrupted content comparisons, saved to a state file
+/// so that they can be resumed.
+///
+/// A checkpoint is only used if neither file's length or modification time
+/// has changed since it was saved. Checkpoints are removed once their
+/// comparison completes.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct ResumeState {
+    /// The saved checkpoints.
+    #[serde(rename = "checkpoint", default)]
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl ResumeState {
+    /// Loads the `ResumeState` from the JSON file at the given path. Returns an
+    /// empty `ResumeState` if the file does not exist.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the file can't be read or is not a
+    /// valid state file.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn load(path: &Path) -> Result<Self, std::io::Error> {
+        match File::open(path) {
+            Ok(file) => Ok(serde_json::from_reader(
+                std::io::BufReader::new(file))?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Saves the `ResumeState` as JSON to the file at the given path. The file
+    /// is replaced atomically, so an interruption will not corrupt it.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the file can't be written.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn save(&self, path: &Path) -> Result<(), std::io::Error> {
+        let mut tmp = path.as_os_str().to_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        let file = File::create(&tmp)?;
+        serde_json::to_writer(std::io::BufWriter::new(&file), self)?;
+        file.sync_all()?;
+        std::fs::rename(tmp, path)
+    }
+
+    /// Replaces the checkpoint for the given files, or removes it if `offset`
+    /// is `None`.
+    fn update(&mut self, checkpoint: &Checkpoint, offset: Option<u64>) {
+        self.checkpoints.retain(|c| c.a != checkpoint.a || c.b != checkpoint.b);
+        if let Some(offset) = offset {
+            self.checkpoints.push(Checkpoint {
+                offset,
+                ..checkpoint.clone()
+            });
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// diff_resumable
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the files at the given paths have different content.
+///
+/// Progress is periodically saved to the given state file, and a comparison
+/// interrupted earlier is resumed from its last checkpoint instead of
+/// restarting.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if either file or the state file can't be
+/// read, or the state file can't be written.
+///
+/// [`std::io::Error`]: std::io::Error
+pub fn diff_resumable(a: &Path, b: &Path, state_file: &Path)
+    -> Result<bool, std::io::Error>
+{
+    let mut file_a = File::open(a)?;
+    let mut file_b = File::open(b)?;
+    let meta_a = file_a.metadata()?;
+    let meta_b = file_b.metadata()?;
+    if meta_a.len() != meta_b.len() { return Ok(true); }
+
+    let current = Checkpoint {
+        a: a.to_path_buf(),
+        b: b.to_path_buf(),
+        len: meta_a.len(),
+        modified_a: meta_a.modified()?,
+        modified_b: meta_b.modified()?,
+        offset: 0,
+    };
+
+    let mut state = ResumeState::load(state_file)?;
+    let mut offset = state.checkpoints
+        .iter()
+        .find(|c| c.matches(&current))
+        .map_or(0, |c| c.offset);
+    let _ = file_a.seek(SeekFrom::Start(offset))?;
+    let _ = file_b.seek(SeekFrom::Start(offset))?;
+
+    let mut buf_a = vec![0; CHUNK_SIZE];
+    let mut buf_b = vec![0; CHUNK_SIZE];
+    let mut next_checkpoint = offset + CHECKPOINT_INTERVAL;
+    loop {
+        let len_a = read_full(&mut file_a, &mut buf_a)?;
+        let len_b = read_full(&mut file_b, &mut buf_b)?;
+        if buf_a[..len_a] != buf_b[..len_b] {
+            state.update(&current, None);
+            state.save(state_file)?;
+            return Ok(true);
+        }
+        if len_a == 0 { break; }
+
+        offset += len_a as u64;
+        if offset >= next_checkpoint {
+            state.update(&current, Some(offset));
+            state.save(state_file)?;
+            next_checkpoint = offset + CHECKPOINT_INTERVAL;
+        }
+    }
+
+    state.update(&current, None);
+    state.save(state_file)?;
+    Ok(false)
+}
+
+/// Reads from the file until the buffer is full or the end of the file is
+/// reached. Returns the number of bytes read.
+fn read_full(file: &mut File, buf: &mut [u8]) -> Result<usize, std::io::Error>
+{
+    let mut len = 0;
+    while len < buf.len() {
+        match file.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
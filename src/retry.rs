@@ -0,0 +1,120 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Retrying of transient IO errors.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+// Standard library imports.
+use std::io::ErrorKind;
+use std::time::Duration;
+
+
+/// The default delay before the first retry of a [`RetryPolicy`].
+///
+/// [`RetryPolicy`]: RetryPolicy
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+
+////////////////////////////////////////////////////////////////////////////////
+// RetryPolicy
+////////////////////////////////////////////////////////////////////////////////
+/// A policy for retrying file operations which fail with transient IO errors,
+/// such as those common on network filesystems.
+///
+/// Metadata reads, file opens, and content comparisons are retried if they
+/// fail with an error for which [`is_transient`] returns `true`.
+///
+/// [`is_transient`]: is_transient
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct RetryPolicy {
+    /// The largest number of times a failed operation is retried.
+    pub retries: u32,
+    /// The delay before the first retry. Each later retry waits twice as long
+    /// as the one before it.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Returns a `RetryPolicy` which never retries.
+    #[must_use]
+    pub const fn none() -> Self {
+        Self { retries: 0, backoff: DEFAULT_RETRY_BACKOFF }
+    }
+
+    /// Calls the given operation, calling it again after a delay each time it
+    /// fails with a transient IO error, up to the number of retries.
+    ///
+    /// ### Errors
+    ///
+    /// Returns the last error of the operation if it fails with an error
+    /// which is not transient or if every retry fails.
+    pub fn run<T, F>(&self, mut op: F) -> Result<T, std::io::Error>
+        where F: FnMut() -> Result<T, std::io::Error>
+    {
+        let mut delay = self.backoff;
+        let mut retries = 0;
+        loop {
+            match op() {
+                Err(e) if retries < self.retries && is_transient(&e) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(
+                        error = %e,
+                        retry = retries + 1,
+                        ?delay,
+                        "retrying after transient error");
+                    std::thread::sleep(delay);
+                    delay = delay.saturating_mul(2);
+                    retries += 1;
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// is_transient
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the given IO error may not occur if the operation is
+/// retried, such as an IO error or timeout on a network filesystem.
+#[must_use]
+pub fn is_transient(error: &std::io::Error) -> bool {
+    /// The raw OS errors considered transient.
+    #[cfg(unix)]
+    const TRANSIENT: &[i32] = &[
+        5, // EIO
+    ];
+    /// The raw OS errors considered transient.
+    #[cfg(windows)]
+    const TRANSIENT: &[i32] = &[
+        59,  // ERROR_UNEXP_NET_ERR
+        64,  // ERROR_NETNAME_DELETED
+        121, // ERROR_SEM_TIMEOUT
+    ];
+    /// The raw OS errors considered transient.
+    #[cfg(not(any(unix, windows)))]
+    const TRANSIENT: &[i32] = &[];
+
+    matches!(error.kind(), ErrorKind::TimedOut | ErrorKind::Interrupted)
+        || error.raw_os_error().is_some_and(|code| TRANSIENT.contains(&code))
+}
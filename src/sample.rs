@@ -0,0 +1,158 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Sampled content comparison.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+// Standard library imports.
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher as _;
+use std::hash::Hasher as _;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+
+
+/// The default number of interior blocks compared by [`DiffOp::Sampled`].
+///
+/// [`DiffOp::Sampled`]: crate::DiffOp::Sampled
+pub const DEFAULT_SAMPLE_BLOCKS: usize = 16;
+
+/// The default size of each block compared by [`DiffOp::Sampled`], in bytes.
+///
+/// [`DiffOp::Sampled`]: crate::DiffOp::Sampled
+pub const DEFAULT_SAMPLE_BLOCK_SIZE: usize = 64 * 1024;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// SampleOptions
+////////////////////////////////////////////////////////////////////////////////
+/// Options for the sampled diff performed by [`DiffOp::Sampled`].
+///
+/// [`DiffOp::Sampled`]: crate::DiffOp::Sampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SampleOptions {
+    /// The number of randomly placed interior blocks compared, in addition to
+    /// the first and last blocks.
+    pub blocks: usize,
+    /// The size of each block compared, in bytes.
+    pub block_size: usize,
+}
+
+impl SampleOptions {
+    /// Returns the number of bytes sampled from each file of the given
+    /// length.
+    #[must_use]
+    pub fn sampled_len(&self, len: u64) -> u64 {
+        let blocks = self.blocks.saturating_add(2);
+        let sampled = blocks.saturating_mul(self.block_size.max(1));
+        len.min(u64::try_from(sampled).unwrap_or(u64::MAX))
+    }
+}
+
+impl Default for SampleOptions {
+    fn default() -> Self {
+        Self {
+            blocks: DEFAULT_SAMPLE_BLOCKS,
+            block_size: DEFAULT_SAMPLE_BLOCK_SIZE,
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// sampled_eq
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the given files of the given length have the same
+/// content in the first block, the last block, and a number of randomly
+/// placed interior blocks, along with the number of bytes read from each
+/// file. Files no longer than the sampled blocks are compared in full.
+///
+/// The interior blocks are placed differently on each call, and the same
+/// blocks are read from both files.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if either file fails to read, including if it
+/// is shorter than the given length.
+///
+/// [`std::io::Error`]: std::io::Error
+pub(crate) fn sampled_eq<A, B>(
+    mut a: A,
+    mut b: B,
+    len: u64,
+    options: SampleOptions)
+    -> Result<(bool, u64), std::io::Error>
+    where
+        A: Read + Seek,
+        B: Read + Seek,
+{
+    let block_size = options.block_size.max(1);
+    let block_len = block_size as u64;
+    let mut buf_a = vec![0; block_size];
+    let mut buf_b = vec![0; block_size];
+    let mut read = 0;
+
+    if len <= options.sampled_len(len) {
+        // The whole file is sampled, so the blocks are read in order.
+        while read < len {
+            let n = usize::try_from((len - read).min(block_len))
+                .unwrap_or(block_size);
+            a.read_exact(&mut buf_a[..n])?;
+            b.read_exact(&mut buf_b[..n])?;
+            read += n as u64;
+            if buf_a[..n] != buf_b[..n] { return Ok((false, read)); }
+        }
+        return Ok((true, read));
+    }
+
+    // Interior blocks lie entirely between the first and last blocks.
+    let interior = len.saturating_sub(block_len.saturating_mul(3)) + 1;
+    let mut rng = XorShift::seeded();
+    let offsets = [0, len - block_len].into_iter()
+        .chain((0..options.blocks).map(|_| block_len + rng.next() % interior));
+
+    for offset in offsets {
+        let _ = a.seek(SeekFrom::Start(offset))?;
+        let _ = b.seek(SeekFrom::Start(offset))?;
+        a.read_exact(&mut buf_a)?;
+        b.read_exact(&mut buf_b)?;
+        read += block_len;
+        if buf_a != buf_b { return Ok((false, read)); }
+    }
+    Ok((true, read))
+}
+
+
+/// A xorshift pseudo-random number generator, used to place sampled blocks.
+struct XorShift(u64);
+
+impl XorShift {
+    /// Returns a generator with a random seed.
+    fn seeded() -> Self {
+        let seed = RandomState::new().build_hasher().finish();
+        // A xorshift generator must not have a zero state.
+        Self(seed | 1)
+    }
+
+    /// Returns the next number in the sequence.
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
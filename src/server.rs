@@ -0,0 +1,516 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Long-running comparison server.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+#[cfg(unix)]
+use crate::cancel::CancelToken;
+use crate::compare::compare_all_ties;
+use crate::compare::Comparator;
+use crate::compare::CompareKey;
+use crate::compare::MissingFileBehavior;
+use crate::compare::TieBreak;
+use crate::error::FcmpError;
+
+// External library imports.
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest as _;
+use sha2::Sha256;
+
+// Standard library imports.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+#[cfg(unix)]
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The interval at which a [`Server`] checks its [`CancelToken`] while
+/// waiting for clients to connect.
+///
+/// [`Server`]: Server
+/// [`CancelToken`]: crate::CancelToken
+#[cfg(unix)]
+const ACCEPT_POLL: Duration = Duration::from_millis(100);
+
+/// The default number of content digests cached by a [`Server`].
+///
+/// [`Server`]: Server
+pub const DEFAULT_DIGEST_CACHE_CAPACITY: usize = 4096;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Query
+////////////////////////////////////////////////////////////////////////////////
+/// A query answered by a [`Server`], given as a JSON object whose `op` field
+/// names the query.
+///
+/// [`Server`]: Server
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Query {
+    /// Select the most recently modified of the given files, as in
+    /// `{"op": "newest", "paths": ["a", "b"]}`.
+    Newest {
+        /// The file paths to compare.
+        paths: Vec<PathBuf>,
+        /// The [`CompareKey`] used to order the files.
+        ///
+        /// [`CompareKey`]: crate::CompareKey
+        #[serde(default)]
+        key: CompareKey,
+        /// Additional keys used to order files with equal keys.
+        #[serde(default)]
+        tie_break: Vec<CompareKey>,
+        /// The [`MissingFileBehavior`] indicating how to handle missing
+        /// files.
+        ///
+        /// [`MissingFileBehavior`]: crate::MissingFileBehavior
+        #[serde(default)]
+        missing: MissingFileBehavior,
+        /// Whether to select the oldest file instead of the newest.
+        #[serde(default)]
+        reverse: bool,
+    },
+    /// Check whether the given files all have the same content, as in
+    /// `{"op": "equal", "paths": ["a", "b"]}`. A missing file is different
+    /// from an existing file, and missing files are the same as each other.
+    Equal {
+        /// The file paths to compare.
+        paths: Vec<PathBuf>,
+    },
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Request
+////////////////////////////////////////////////////////////////////////////////
+/// A [`Query`] sent to a [`Server`], along with an optional `id` which is
+/// copied to the [`Response`].
+///
+/// [`Query`]: Query
+/// [`Server`]: Server
+/// [`Response`]: Response
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct Request {
+    /// An identifier for the request, copied to the response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<serde_json::Value>,
+    /// The query to answer.
+    #[serde(flatten)]
+    pub query: Query,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Response
+////////////////////////////////////////////////////////////////////////////////
+/// The answer to a [`Request`], written as a JSON object. Only the fields
+/// relevant to the query are written.
+///
+/// [`Request`]: Request
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct Response {
+    /// The identifier of the request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<serde_json::Value>,
+    /// Whether the query was answered. If not, `error` describes why.
+    pub ok: bool,
+    /// The index of the selected file, for `newest` queries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+    /// The path of the selected file, for `newest` queries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Whether the files have the same content, for `equal` queries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub equal: Option<bool>,
+    /// The reason the query failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Server
+////////////////////////////////////////////////////////////////////////////////
+/// A server answering newline-delimited JSON [`Request`]s with one
+/// [`Response`] line each.
+///
+/// The server caches the content digests of the files compared by `equal`
+/// queries, so that files whose size and modification time have not changed
+/// are not read again. When the cache is full, the least recently used digest
+/// is dropped, and digests of files found to be missing are dropped at once.
+///
+/// [`Request`]: Request
+/// [`Response`]: Response
+#[derive(Debug)]
+pub struct Server {
+    /// The cached content digests.
+    digests: Mutex<DigestCache>,
+    /// The maximum number of cached content digests.
+    capacity: usize,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_DIGEST_CACHE_CAPACITY)
+    }
+}
+
+/// The content digests cached by a [`Server`], keyed by path.
+///
+/// [`Server`]: Server
+#[derive(Debug, Default)]
+struct DigestCache {
+    /// The cached digests.
+    entries: HashMap<PathBuf, CachedDigest>,
+    /// The number of cache lookups and insertions, used to order the entries
+    /// by their last use.
+    uses: u64,
+}
+
+/// A content digest cached by a [`Server`].
+///
+/// [`Server`]: Server
+#[derive(Debug, Clone, Copy)]
+struct CachedDigest {
+    /// The size of the file when it was read.
+    len: u64,
+    /// The modification time of the file when it was read.
+    modified: Option<SystemTime>,
+    /// The SHA-256 digest of the file's content.
+    digest: [u8; 32],
+    /// The number of uses of the [`DigestCache`] when the digest was last
+    /// used.
+    ///
+    /// [`DigestCache`]: DigestCache
+    last_used: u64,
+}
+
+impl DigestCache {
+    /// Returns the cached digest of the file at the given path, marking it as
+    /// used.
+    fn get(&mut self, path: &Path) -> Option<CachedDigest> {
+        self.uses += 1;
+        let entry = self.entries.get_mut(path)?;
+        entry.last_used = self.uses;
+        Some(*entry)
+    }
+
+    /// Caches the digest of the file at the given path, dropping the least
+    /// recently used digest if the cache would hold more than `capacity`
+    /// digests.
+    fn insert(&mut self, path: &Path, mut digest: CachedDigest, capacity: usize)
+    {
+        if capacity == 0 { return; }
+        if self.entries.len() >= capacity && !self.entries.contains_key(path) {
+            let least_used = self.entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(path, _)| path.clone());
+            if let Some(least_used) = least_used {
+                let _ = self.entries.remove(&least_used);
+            }
+        }
+        self.uses += 1;
+        digest.last_used = self.uses;
+        let _ = self.entries.insert(path.to_path_buf(), digest);
+    }
+}
+
+impl Server {
+    /// Returns a `Server` with an empty cache holding up to
+    /// [`DEFAULT_DIGEST_CACHE_CAPACITY`] digests.
+    ///
+    /// [`DEFAULT_DIGEST_CACHE_CAPACITY`]: DEFAULT_DIGEST_CACHE_CAPACITY
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a `Server` with an empty cache holding up to the given number
+    /// of digests. A capacity of zero disables the cache.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            digests: Mutex::new(DigestCache::default()),
+            capacity,
+        }
+    }
+
+    /// Returns the number of cached content digests.
+    #[must_use]
+    pub fn cached(&self) -> usize {
+        self.cache().entries.len()
+    }
+
+    /// Answers the given [`Request`].
+    ///
+    /// [`Request`]: Request
+    #[must_use]
+    pub fn respond(&self, request: Request) -> Response {
+        let mut response = match self.answer(&request.query) {
+            Ok(response) => response,
+            Err(e) => Response {
+                error: Some(error_message(&e)),
+                ..Response::default()
+            },
+        };
+        response.id = request.id;
+        response
+    }
+
+    /// Answers the given JSON request line, returning the JSON response
+    /// line without a trailing newline.
+    #[must_use]
+    pub fn respond_line(&self, line: &str) -> String {
+        let response = match serde_json::from_str(line) {
+            Ok(request) => self.respond(request),
+            Err(e) => Response {
+                error: Some(format!("invalid request: {e}")),
+                ..Response::default()
+            },
+        };
+        serde_json::to_string(&response).expect("serialize response")
+    }
+
+    /// Answers each request line read from the given reader, writing the
+    /// responses to the given writer, until the reader is exhausted. Empty
+    /// lines are ignored.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if reading or writing fails.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn serve<R, W>(&self, reader: R, mut writer: W)
+        -> Result<(), std::io::Error>
+        where
+            R: BufRead,
+            W: Write,
+    {
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() { continue; }
+            writeln!(writer, "{}", self.respond_line(&line))?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Answers requests from clients connecting to a Unix socket at the
+    /// given path until the given [`CancelToken`] is cancelled. Each client
+    /// is served on its own thread. The socket file is removed when the
+    /// server stops.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the socket can't be created, such as
+    /// when a file already exists at the path.
+    ///
+    /// [`CancelToken`]: crate::CancelToken
+    /// [`std::io::Error`]: std::io::Error
+    #[cfg(unix)]
+    pub fn serve_socket(
+        self: &std::sync::Arc<Self>,
+        path: &Path,
+        cancel: &CancelToken)
+        -> Result<(), std::io::Error>
+    {
+        use std::io::BufReader;
+        use std::os::unix::net::UnixListener;
+        use std::sync::Arc;
+
+        let listener = UnixListener::bind(path)?;
+        let _remove = RemoveOnDrop(path);
+        listener.set_nonblocking(true)?;
+
+        while !cancel.is_cancelled() {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    let server = Arc::clone(self);
+                    drop(std::thread::spawn(move || {
+                        let reader = BufReader::new(&stream);
+                        // A client disconnecting only ends its own session.
+                        let _ = server.serve(reader, &stream);
+                    }));
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(ACCEPT_POLL);
+                },
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers the given [`Query`].
+    ///
+    /// [`Query`]: Query
+    fn answer(&self, query: &Query) -> Result<Response, QueryError> {
+        match query {
+            Query::Newest { paths, key, tie_break, missing, reverse } => {
+                let comparator = Comparator {
+                    key: *key,
+                    tie_break: TieBreak::new(tie_break.iter().copied()),
+                    missing: *missing,
+                    ..Comparator::default()
+                };
+                let idx = compare_all_ties(
+                        paths.iter().map(PathBuf::as_path),
+                        *reverse,
+                        &comparator)?
+                    .first()
+                    .copied()
+                    .ok_or(QueryError::NoneSelected)?;
+                Ok(Response {
+                    ok: true,
+                    index: Some(idx),
+                    path: Some(paths[idx].clone()),
+                    ..Response::default()
+                })
+            },
+            Query::Equal { paths } => {
+                let mut first = None;
+                let mut equal = true;
+                for path in paths {
+                    let digest = self.digest(path)
+                        .map_err(|source| QueryError::Read {
+                            path: path.clone(),
+                            source,
+                        })?;
+                    match first {
+                        None => first = Some(digest),
+                        Some(first) if first != digest => {
+                            equal = false;
+                            break;
+                        },
+                        Some(_) => (),
+                    }
+                }
+                Ok(Response {
+                    ok: true,
+                    equal: Some(equal),
+                    ..Response::default()
+                })
+            },
+        }
+    }
+
+    /// Returns the size and content digest of the file at the given path, or
+    /// `None` if it is missing, using the cached digest if the file's size
+    /// and modification time are unchanged.
+    fn digest(&self, path: &Path)
+        -> Result<Option<(u64, [u8; 32])>, std::io::Error>
+    {
+        let metadata = match std::fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let _ = self.cache().entries.remove(path);
+                return Ok(None);
+            },
+            Err(e) => return Err(e),
+        };
+        let len = metadata.len();
+        let modified = metadata.modified().ok();
+
+        let cached = self.cache().get(path);
+        if let Some(cached) = cached.filter(|c| {
+            c.len == len && c.modified.is_some() && c.modified == modified
+        }) {
+            return Ok(Some((len, cached.digest)));
+        }
+
+        let mut hasher = Sha256::new();
+        let _ = std::io::copy(&mut File::open(path)?, &mut hasher)?;
+        let digest = hasher.finalize().into();
+        self.cache().insert(
+            path,
+            CachedDigest { len, modified, digest, last_used: 0 },
+            self.capacity);
+        Ok(Some((len, digest)))
+    }
+
+    /// Locks and returns the digest cache.
+    fn cache(&self) -> std::sync::MutexGuard<'_, DigestCache> {
+        self.digests.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// QueryError
+////////////////////////////////////////////////////////////////////////////////
+/// An error which occurs while answering a [`Query`].
+///
+/// [`Query`]: Query
+#[derive(Debug, thiserror::Error)]
+enum QueryError {
+    /// Comparing the files failed.
+    #[error(transparent)]
+    Compare(#[from] FcmpError),
+
+    /// None of the files were selected.
+    #[error("no files selected")]
+    NoneSelected,
+
+    /// A file's content can't be read.
+    #[error("failed to read '{}'", path.display())]
+    Read {
+        /// The path of the file.
+        path: PathBuf,
+        /// The error which occurred when reading the file.
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helpers
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the message of the given error followed by the messages of its
+/// sources, separated by colons.
+fn error_message(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        message.push_str(": ");
+        message.push_str(&e.to_string());
+        source = e.source();
+    }
+    message
+}
+
+/// Removes the file at the wrapped path when dropped.
+#[cfg(unix)]
+struct RemoveOnDrop<'p>(&'p Path);
+
+#[cfg(unix)]
+impl Drop for RemoveOnDrop<'_> {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(self.0);
+    }
+}
@@ -0,0 +1,239 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Directory tree snapshots for change detection.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::walk::Manifest;
+
+// External library imports.
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest as _;
+use sha2::Sha256;
+
+// Standard library imports.
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FileState
+////////////////////////////////////////////////////////////////////////////////
+/// The recorded state of a file in a [`Snapshot`].
+///
+/// [`Snapshot`]: Snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct FileState {
+    /// The size of the file in bytes.
+    pub len: u64,
+    /// The modification time of the file, if the platform supports it.
+    pub modified: Option<SystemTime>,
+    /// The hexadecimal SHA-256 digest of the file's content, if it is a
+    /// regular file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+}
+
+impl FileState {
+    /// Returns the current state of the file at the given path, reading the
+    /// file to take the digest of its content.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the file's metadata or content can't
+    /// be read.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn of(path: &Path) -> Result<Self, std::io::Error> {
+        let metadata = std::fs::symlink_metadata(path)?;
+        let digest = if metadata.is_file() {
+            Some(content_digest(path)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            digest,
+        })
+    }
+
+    /// Returns `true` if the file's content may have changed between this
+    /// state and the given later state.
+    ///
+    /// If both states have a digest, the content has changed if the digests
+    /// differ, regardless of the modification times. Otherwise, the content
+    /// is assumed to have changed if the size or modification time differs.
+    #[must_use]
+    pub fn changed(&self, later: &Self) -> bool {
+        if self.len != later.len { return true; }
+        match (&self.digest, &later.digest) {
+            (Some(a), Some(b)) => a != b,
+            _                  => self.modified != later.modified,
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Snapshot
+////////////////////////////////////////////////////////////////////////////////
+/// The recorded state of every file in a directory tree, used to detect which
+/// files have changed since the snapshot was taken.
+///
+/// Paths are stored relative to the root of the tree, so a snapshot may be
+/// compared against a copy of the tree at a different location. A file is
+/// considered modified if its size or content digest has changed. Snapshots
+/// taken without digests fall back to comparing modification times.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The state of each file, keyed by its path relative to the root.
+    #[serde(rename = "file", default)]
+    pub files: BTreeMap<PathBuf, FileState>,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the directory tree at `root`, using the given
+    /// [`Manifest`] to list its files.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if a directory or file's metadata or a
+    /// file's content can't be read.
+    ///
+    /// [`Manifest`]: crate::Manifest
+    /// [`std::io::Error`]: std::io::Error
+    pub fn take(root: &Path, manifest: &Manifest)
+        -> Result<Self, std::io::Error>
+    {
+        let mut files = BTreeMap::new();
+        for path in manifest.walk(root)? {
+            let state = FileState::of(&path)?;
+            let relative = path.strip_prefix(root)
+                .map_or_else(|_e| path.clone(), Path::to_path_buf);
+            let _ = files.insert(relative, state);
+        }
+        Ok(Self { files })
+    }
+
+    /// Returns the changes from this snapshot to the given later snapshot, in
+    /// path order.
+    #[must_use]
+    pub fn changes(&self, later: &Self) -> Vec<Change> {
+        let removed = self.files
+            .keys()
+            .filter(|path| !later.files.contains_key(*path))
+            .map(|path| Change::Removed(path.clone()));
+
+        let added_or_modified = later.files
+            .iter()
+            .filter_map(|(path, state)| match self.files.get(path) {
+                None => Some(Change::Added(path.clone())),
+                Some(old) if old.changed(state) => {
+                    Some(Change::Modified(path.clone()))
+                },
+                Some(_) => None,
+            });
+
+        let mut changes = removed
+            .chain(added_or_modified)
+            .collect::<Vec<_>>();
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+        changes
+    }
+
+    /// Loads a `Snapshot` from the JSON file at the given path.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the file can't be read or is not a
+    /// valid snapshot.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn load(path: &Path) -> Result<Self, std::io::Error> {
+        let file = std::fs::File::open(path)?;
+        let reader = std::io::BufReader::new(file);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Saves the `Snapshot` as JSON to the file at the given path.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the file can't be written.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn save(&self, path: &Path) -> Result<(), std::io::Error> {
+        let file = std::fs::File::create(path)?;
+        let writer = std::io::BufWriter::new(file);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Change
+////////////////////////////////////////////////////////////////////////////////
+/// A change to a file between two [`Snapshot`]s, giving the path of the file
+/// relative to the root of the tree.
+///
+/// [`Snapshot`]: Snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// The file is new.
+    Added(PathBuf),
+    /// The file's content has changed.
+    Modified(PathBuf),
+    /// The file has been deleted.
+    Removed(PathBuf),
+}
+
+impl Change {
+    /// Returns the path of the changed file, relative to the root of the tree.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Added(path)
+            | Self::Modified(path)
+            | Self::Removed(path) => path,
+        }
+    }
+
+    /// Returns the single-character code used to display the change.
+    #[must_use]
+    pub const fn code(&self) -> char {
+        match self {
+            Self::Added(_)    => 'A',
+            Self::Modified(_) => 'M',
+            Self::Removed(_)  => 'D',
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helpers
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the hexadecimal SHA-256 digest of the content of the file at the
+/// given path.
+fn content_digest(path: &Path) -> Result<String, std::io::Error> {
+    let mut hasher = Sha256::new();
+    let _ = std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    let mut digest = String::with_capacity(64);
+    for byte in hasher.finalize() {
+        let _ = write!(digest, "{byte:02x}");
+    }
+    Ok(digest)
+}
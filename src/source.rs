@@ -0,0 +1,151 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Candidate path sources.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::backend::output;
+use crate::paths::read_path_list;
+use crate::plan::PlannedOp;
+
+// External library imports.
+use anyhow::anyhow;
+
+// Standard library imports.
+use std::path::PathBuf;
+use std::process::Command;
+use std::str::FromStr;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Source
+////////////////////////////////////////////////////////////////////////////////
+/// A generator of candidate paths to compare.
+pub trait Source: std::fmt::Debug + Send + Sync {
+    /// Returns the candidate paths.
+    ///
+    /// ### Errors
+    ///
+    /// Returns an error if the paths can't be generated.
+    fn paths(&self) -> Result<Vec<PathBuf>, anyhow::Error>;
+
+    /// Returns the IO operations expected to be performed when generating
+    /// the paths, without performing them.
+    fn plan(&self) -> Vec<PlannedOp> {
+        Vec::new()
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// CommandSource
+////////////////////////////////////////////////////////////////////////////////
+/// A [`Source`] which generates paths by running an external command.
+///
+/// [`Source`]: Source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandSource {
+    /// The files tracked by git in the current directory, as listed by
+    /// `git ls-files`.
+    GitLsFiles,
+    /// The files found by `find` with the given whitespace-separated
+    /// arguments, e.g., `src -name *.rs`.
+    Find(String),
+    /// The newline-separated paths printed by the given shell command.
+    Shell(String),
+}
+
+impl CommandSource {
+    /// Returns the command to run and whether its output is NUL-separated.
+    fn command(&self) -> (Command, bool) {
+        match self {
+            Self::GitLsFiles => {
+                let mut command = Command::new("git");
+                let _ = command.args(["ls-files", "-z"]);
+                (command, true)
+            },
+            Self::Find(expr) => {
+                let mut command = Command::new("find");
+                let _ = command.args(expr.split_whitespace()).arg("-print0");
+                (command, true)
+            },
+            Self::Shell(shell) => {
+                let mut command = if cfg!(windows) {
+                    let mut command = Command::new("cmd");
+                    let _ = command.arg("/C");
+                    command
+                } else {
+                    let mut command = Command::new("sh");
+                    let _ = command.arg("-c");
+                    command
+                };
+                let _ = command.arg(shell);
+                (command, false)
+            },
+        }
+    }
+}
+
+impl Source for CommandSource {
+    fn paths(&self) -> Result<Vec<PathBuf>, anyhow::Error> {
+        let (mut command, nul_separated) = self.command();
+        #[cfg(feature = "tracing")]
+        tracing::debug!(?command, "launching source command");
+        let output = output(&mut command)?;
+        if !output.status.success() {
+            return Err(anyhow!("source {:?} failed with {}: {}",
+                self,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()));
+        }
+        Ok(read_path_list(output.stdout.as_slice(), nul_separated)?)
+    }
+
+    fn plan(&self) -> Vec<PlannedOp> {
+        let (command, _) = self.command();
+        vec![PlannedOp::Spawn {
+            command: command.get_program().to_string_lossy().into_owned(),
+            args: command
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+        }]
+    }
+}
+
+impl FromStr for CommandSource {
+    type Err = CommandSourceParseError;
+
+    /// Parses a `CommandSource` of the form `git-ls-files`, `find:<args>`, or
+    /// `cmd:<command>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("git-ls-files") {
+            Ok(Self::GitLsFiles)
+        } else if let Some(expr) = s.strip_prefix("find:") {
+            Ok(Self::Find(expr.to_string()))
+        } else if let Some(shell) = s.strip_prefix("cmd:") {
+            Ok(Self::Shell(shell.to_string()))
+        } else {
+            Err(CommandSourceParseError)
+        }
+    }
+}
+
+/// An error indicating a failure to parse a [`CommandSource`].
+///
+/// [`CommandSource`]: CommandSource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandSourceParseError;
+
+impl std::error::Error for CommandSourceParseError {}
+
+impl std::fmt::Display for CommandSourceParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse CommandSource")
+    }
+}
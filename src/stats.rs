@@ -0,0 +1,118 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Runtime statistics.
+////////////////////////////////////////////////////////////////////////////////
+
+// Standard library imports.
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// CompareStats
+////////////////////////////////////////////////////////////////////////////////
+/// The amount of work done by the comparisons sharing a [`StatsRecorder`].
+///
+/// [`StatsRecorder`]: StatsRecorder
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompareStats {
+    /// The total number of bytes of content read from both files of each
+    /// pair by internal diffs.
+    pub bytes_read: u64,
+    /// The number of files whose metadata was read.
+    pub files_stated: u64,
+    /// The number of content diffs performed.
+    pub diffs: u64,
+    /// The number of diff subprocesses spawned.
+    pub subprocesses: u64,
+    /// The time elapsed since the `StatsRecorder` was created.
+    pub wall_time: Duration,
+}
+
+impl std::fmt::Display for CompareStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "files stat'ed: {}", self.files_stated)?;
+        writeln!(f, "content diffs: {}", self.diffs)?;
+        writeln!(f, "bytes read:    {}", self.bytes_read)?;
+        writeln!(f, "subprocesses:  {}", self.subprocesses)?;
+        writeln!(f, "wall time:     {:?}", self.wall_time)
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// StatsRecorder
+////////////////////////////////////////////////////////////////////////////////
+/// Counts the work done by comparisons, producing [`CompareStats`].
+///
+/// A `StatsRecorder` is `Send` and `Sync`, and may be shared by comparisons
+/// on multiple threads.
+///
+/// [`CompareStats`]: CompareStats
+#[derive(Debug)]
+pub struct StatsRecorder {
+    /// The time the recorder was created.
+    start: Instant,
+    /// The number of bytes of content read.
+    bytes_read: AtomicU64,
+    /// The number of files whose metadata was read.
+    files_stated: AtomicU64,
+    /// The number of content diffs performed.
+    diffs: AtomicU64,
+    /// The number of diff subprocesses spawned.
+    subprocesses: AtomicU64,
+}
+
+impl StatsRecorder {
+    /// Returns a `StatsRecorder` with all counters at zero, measuring wall
+    /// time from now.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            bytes_read: AtomicU64::new(0),
+            files_stated: AtomicU64::new(0),
+            diffs: AtomicU64::new(0),
+            subprocesses: AtomicU64::new(0),
+        }
+    }
+
+    /// Records that the metadata of a file was read.
+    pub fn record_stat(&self) {
+        let _ = self.files_stated.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a content diff which read the given number of bytes from each
+    /// file and spawned the given number of subprocesses.
+    pub fn record_diff(&self, bytes_read: u64, subprocesses: u64) {
+        let _ = self.diffs.fetch_add(1, Ordering::Relaxed);
+        let _ = self.bytes_read
+            .fetch_add(bytes_read.saturating_mul(2), Ordering::Relaxed);
+        let _ = self.subprocesses.fetch_add(subprocesses, Ordering::Relaxed);
+    }
+
+    /// Returns the statistics recorded so far.
+    #[must_use]
+    pub fn stats(&self) -> CompareStats {
+        CompareStats {
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            files_stated: self.files_stated.load(Ordering::Relaxed),
+            diffs: self.diffs.load(Ordering::Relaxed),
+            subprocesses: self.subprocesses.load(Ordering::Relaxed),
+            wall_time: self.start.elapsed(),
+        }
+    }
+}
+
+impl Default for StatsRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,165 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Standard input as a compared file.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+// Standard library imports.
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// STDIN_PATH
+////////////////////////////////////////////////////////////////////////////////
+/// The path used to refer to standard input.
+pub const STDIN_PATH: &str = "-";
+
+/// Returns `true` if the given path refers to standard input.
+#[must_use]
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new(STDIN_PATH)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// StdinMtime
+////////////////////////////////////////////////////////////////////////////////
+/// Options for the modification time given to standard input when it is
+/// compared with other files.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(clap::ArgEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum StdinMtime {
+    /// Use the time standard input was read.
+    #[default]
+    Now,
+    /// Treat standard input as older than all other files.
+    Oldest,
+    /// Treat standard input as newer than all other files.
+    Newest,
+}
+
+impl StdinMtime {
+    /// Returns the modification time to give standard input when it is
+    /// compared with the files at the given paths. Paths which can't be
+    /// stat'ed are not considered.
+    #[must_use]
+    pub fn resolve<'p, P>(self, others: P) -> SystemTime
+        where P: IntoIterator<Item=&'p Path>
+    {
+        let now = SystemTime::now();
+        let mtimes = others
+            .into_iter()
+            .filter_map(|p| p.metadata().and_then(|m| m.modified()).ok());
+        let offset = Duration::from_secs(1);
+        match self {
+            Self::Now    => now,
+            Self::Oldest => mtimes
+                .min()
+                .map_or(now, |t| t.checked_sub(offset)
+                    .unwrap_or(SystemTime::UNIX_EPOCH)),
+            Self::Newest => mtimes
+                .max()
+                .map_or(now, |t| t.checked_add(offset).unwrap_or(t)),
+        }
+    }
+}
+
+impl FromStr for StdinMtime {
+    type Err = StdinMtimeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("now") {
+            Ok(Self::Now)
+        } else if s.eq_ignore_ascii_case("oldest") {
+            Ok(Self::Oldest)
+        } else if s.eq_ignore_ascii_case("newest") {
+            Ok(Self::Newest)
+        } else {
+            Err(StdinMtimeParseError)
+        }
+    }
+}
+
+/// An error indicating a failure to parse a [`StdinMtime`].
+///
+/// [`StdinMtime`]: StdinMtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StdinMtimeParseError;
+
+impl std::error::Error for StdinMtimeParseError {}
+
+impl std::fmt::Display for StdinMtimeParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse StdinMtime")
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// SpooledStdin
+////////////////////////////////////////////////////////////////////////////////
+/// The content of standard input, copied to a temporary file so that it can
+/// be compared like any other file.
+///
+/// The temporary file is removed when the `SpooledStdin` is dropped.
+#[derive(Debug)]
+pub struct SpooledStdin {
+    /// The path of the temporary file.
+    path: PathBuf,
+}
+
+impl SpooledStdin {
+    /// Copies standard input to a temporary file with the given modification
+    /// time.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if standard input fails to read or the
+    /// temporary file fails to write.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn new(modified: SystemTime) -> Result<Self, std::io::Error> {
+        let path = std::env::temp_dir()
+            .join(format!("fcmp-stdin-{}", std::process::id()));
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        let spooled = Self { path };
+
+        let _ = std::io::copy(&mut std::io::stdin().lock(), &mut file)?;
+        file.set_modified(modified)?;
+        Ok(spooled)
+    }
+
+    /// Returns the path of the temporary file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SpooledStdin {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,231 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Normalized text comparison.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+use encoding_rs_io::DecodeReaderBytesBuilder;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+// Standard library imports.
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// TextOptions
+////////////////////////////////////////////////////////////////////////////////
+/// Options determining which differences between text files are ignored by
+/// [`DiffOp::Text`].
+///
+/// [`DiffOp::Text`]: crate::DiffOp::Text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[allow(clippy::struct_excessive_bools)]
+pub struct TextOptions {
+    /// Whether CRLF line endings are treated as equivalent to LF line
+    /// endings.
+    pub ignore_line_endings: bool,
+    /// Whether whitespace at the end of each line is ignored, like the
+    /// `--ignore-trailing-space` option of GNU diff.
+    pub ignore_trailing_space: bool,
+    /// Whether all whitespace within each line is ignored, like the
+    /// `--ignore-all-space` option of GNU diff.
+    pub ignore_all_space: bool,
+    /// Whether text is decoded before it is compared, so that the same text
+    /// stored in different encodings is equivalent. Text beginning with a
+    /// UTF-8 or UTF-16 byte order mark is decoded from that encoding, and all
+    /// other text is compared as it is stored.
+    pub ignore_encoding: bool,
+}
+
+impl TextOptions {
+    /// Returns `true` if no differences are ignored, so that text is compared
+    /// exactly.
+    #[must_use]
+    pub fn is_exact(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns a reader of the text content of the given reader, decoding it
+    /// if encodings are ignored.
+    pub(crate) fn reader<'r, R>(self, reader: R) -> Box<dyn BufRead + 'r>
+        where R: Read + 'r
+    {
+        if self.ignore_encoding {
+            let decoder = DecodeReaderBytesBuilder::new()
+                .bom_sniffing(true)
+                .strip_bom(true)
+                .build(reader);
+            Box::new(BufReader::new(decoder))
+        } else {
+            Box::new(BufReader::new(reader))
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Token
+////////////////////////////////////////////////////////////////////////////////
+/// A byte of text read by a [`Tokens`] reader.
+///
+/// [`Tokens`]: Tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    /// A byte of text which is always compared.
+    Byte(u8),
+    /// A whitespace byte which is ignored if only whitespace follows it on
+    /// its line.
+    Space(u8),
+    /// The end of the text.
+    End,
+}
+
+impl Token {
+    /// Returns `true` if the token ends a line, so that any whitespace before
+    /// it is trailing whitespace.
+    fn ends_line(self) -> bool {
+        matches!(self, Self::Byte(b'\n') | Self::End)
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Tokens
+////////////////////////////////////////////////////////////////////////////////
+/// A reader of the [`Token`]s of text, ignoring the bytes which the
+/// [`TextOptions`] ignore.
+///
+/// Bytes are taken from the reader's buffer, so that no more than the buffer
+/// is held in memory however long the lines are.
+///
+/// [`Token`]: Token
+/// [`TextOptions`]: TextOptions
+struct Tokens<R> {
+    /// The reader of the text.
+    reader: R,
+    /// The options determining which bytes are ignored.
+    options: TextOptions,
+    /// The number of bytes read.
+    read: u64,
+}
+
+impl<R> Tokens<R> where R: BufRead {
+    /// Returns the next byte of the text without consuming it, or `None` at
+    /// the end of the text.
+    fn peek(&mut self) -> Result<Option<u8>, std::io::Error> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    /// Consumes the byte returned by [`peek`].
+    ///
+    /// [`peek`]: Tokens::peek
+    fn consume(&mut self) {
+        self.reader.consume(1);
+        self.read += 1;
+    }
+
+    /// Returns the next token of the text.
+    fn next_token(&mut self) -> Result<Token, std::io::Error> {
+        loop {
+            let Some(byte) = self.peek()? else { return Ok(Token::End) };
+            self.consume();
+            if byte == b'\n' { return Ok(Token::Byte(byte)); }
+
+            if self.options.ignore_all_space {
+                if !byte.is_ascii_whitespace() {
+                    return Ok(Token::Byte(byte));
+                }
+            } else if self.options.ignore_trailing_space {
+                return Ok(if byte.is_ascii_whitespace() {
+                    Token::Space(byte)
+                } else {
+                    Token::Byte(byte)
+                });
+            } else if self.options.ignore_line_endings
+                && byte == b'\r'
+                && matches!(self.peek()?, Some(b'\n') | None)
+            {
+                // The line ending is returned by the next call.
+            } else {
+                return Ok(Token::Byte(byte));
+            }
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// text_eq
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the given readers have the same text content after
+/// normalizing each line with the given [`TextOptions`], along with the number
+/// of bytes read from each reader.
+///
+/// The readers are compared a byte at a time from their buffers, so only the
+/// buffers are held in memory, however long the lines are.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if either reader fails to read.
+///
+/// [`TextOptions`]: TextOptions
+/// [`std::io::Error`]: std::io::Error
+pub(crate) fn text_eq<A, B>(a: A, b: B, options: TextOptions)
+    -> Result<(bool, u64), std::io::Error>
+    where
+        A: BufRead,
+        B: BufRead,
+{
+    let mut a = Tokens { reader: a, options, read: 0 };
+    let mut b = Tokens { reader: b, options, read: 0 };
+    // Whether the whitespace read since the last compared bytes differs
+    // between the readers. Differing whitespace is only allowed if it is
+    // trailing whitespace in both.
+    let mut space_differs = false;
+
+    loop {
+        let mut token_a = a.next_token()?;
+        let mut token_b = b.next_token()?;
+
+        match (token_a, token_b) {
+            (Token::Space(x), Token::Space(y)) => {
+                space_differs |= x != y;
+                continue;
+            },
+            (Token::Space(_), _) => {
+                space_differs = true;
+                while let Token::Space(_) = token_a {
+                    token_a = a.next_token()?;
+                }
+            },
+            (_, Token::Space(_)) => {
+                space_differs = true;
+                while let Token::Space(_) = token_b {
+                    token_b = b.next_token()?;
+                }
+            },
+            _ => (),
+        }
+
+        if space_differs && !(token_a.ends_line() && token_b.ends_line()) {
+            return Ok((false, a.read.max(b.read)));
+        }
+        space_differs = false;
+
+        if token_a != token_b { return Ok((false, a.read.max(b.read))); }
+        if token_a == Token::End { return Ok((true, a.read.max(b.read))); }
+    }
+}
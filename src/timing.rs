@@ -0,0 +1,128 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Per-file timing measurements.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+// Standard library imports.
+use std::collections::BTreeMap;
+#[cfg(feature = "serde")]
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FileTiming
+////////////////////////////////////////////////////////////////////////////////
+/// The time spent on a single candidate file during a comparison.
+///
+/// When serialized, durations are given in seconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct FileTiming {
+    /// The time spent opening the file.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "secs"))]
+    pub open: Duration,
+    /// The time spent reading the file's metadata.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "secs"))]
+    pub stat: Duration,
+    /// The number of bytes of the file's content read by internal diffs.
+    pub bytes_read: u64,
+    /// The time spent in content diffs involving the file.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "secs"))]
+    pub diff: Duration,
+}
+
+/// Serializes a `Duration` as a number of seconds.
+#[cfg(feature = "serde")]
+fn secs<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where S: serde::Serializer
+{
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// TimingRecorder
+////////////////////////////////////////////////////////////////////////////////
+/// Collects a [`FileTiming`] for each file involved in a comparison.
+///
+/// A `TimingRecorder` is `Send` and `Sync`, and may be shared by comparisons
+/// on multiple threads.
+///
+/// [`FileTiming`]: FileTiming
+#[derive(Debug, Default)]
+pub struct TimingRecorder {
+    /// The timings recorded so far, keyed by path.
+    files: Mutex<BTreeMap<PathBuf, FileTiming>>,
+}
+
+impl TimingRecorder {
+    /// Returns an empty `TimingRecorder`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the timing of the given path using the given function.
+    pub fn record<F>(&self, path: &Path, update: F)
+        where F: FnOnce(&mut FileTiming)
+    {
+        let mut files = self.files
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        update(files.entry(path.to_path_buf()).or_default());
+    }
+
+    /// Returns the timings recorded so far, ordered by path.
+    #[must_use]
+    pub fn timings(&self) -> BTreeMap<PathBuf, FileTiming> {
+        self.files
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Writes the timings recorded so far to the given writer as a JSON
+    /// array, with one object per path.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the timings can't be written.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    #[cfg(feature = "serde")]
+    pub fn write_json<W>(&self, writer: W) -> Result<(), std::io::Error>
+        where W: Write
+    {
+        /// A serialized path and its timing.
+        #[derive(Serialize)]
+        struct Entry<'a> {
+            /// The path of the file.
+            path: &'a Path,
+            /// The timing of the file.
+            #[serde(flatten)]
+            timing: &'a FileTiming,
+        }
+
+        let timings = self.timings();
+        let entries = timings
+            .iter()
+            .map(|(path, timing)| Entry { path, timing })
+            .collect::<Vec<_>>();
+        serde_json::to_writer_pretty(writer, &entries)?;
+        Ok(())
+    }
+}
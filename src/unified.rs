@@ -0,0 +1,98 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Unified diffs of file content.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::ops::open_found;
+
+// External library imports.
+use similar::TextDiff;
+
+// Standard library imports.
+use std::io::Read;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ContentDiff
+////////////////////////////////////////////////////////////////////////////////
+/// The differences between the content of two files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentDiff {
+    /// The files have identical content.
+    Same,
+    /// The files differ, and at least one of them is not a text file.
+    Binary,
+    /// The files differ, with the given unified diff of their lines.
+    Text(String),
+}
+
+impl ContentDiff {
+    /// Returns the differences between the given contents, labelling the
+    /// unified diff with the given names and showing the given number of
+    /// unchanged lines around each change.
+    ///
+    /// Content containing a NUL byte is not text. Invalid UTF-8 sequences in
+    /// text are replaced with U+FFFD.
+    #[must_use]
+    pub fn new(a: &[u8], b: &[u8], labels: (&str, &str), context: usize)
+        -> Self
+    {
+        if a == b { return Self::Same; }
+        if a.contains(&0) || b.contains(&0) { return Self::Binary; }
+
+        let a = String::from_utf8_lossy(a);
+        let b = String::from_utf8_lossy(b);
+        let text = TextDiff::from_lines(a.as_ref(), b.as_ref())
+            .unified_diff()
+            .context_radius(context)
+            .header(labels.0, labels.1)
+            .to_string();
+        Self::Text(text)
+    }
+
+    /// Returns `true` if the files have identical content.
+    #[must_use]
+    pub fn is_same(&self) -> bool {
+        matches!(self, Self::Same)
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// unified_diff
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the differences between the content of the files at the given
+/// paths, showing the given number of unchanged lines around each change.
+///
+/// The paths may name archive members, remote files, or git blobs.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if either file is missing or can't be read.
+///
+/// [`std::io::Error`]: std::io::Error
+pub fn unified_diff(a: &Path, b: &Path, context: usize)
+    -> Result<ContentDiff, std::io::Error>
+{
+    let a_content = read_content(a)?;
+    let b_content = read_content(b)?;
+    Ok(ContentDiff::new(
+        &a_content,
+        &b_content,
+        (&a.to_string_lossy(), &b.to_string_lossy()),
+        context))
+}
+
+/// Reads the content of the file at the given path.
+fn read_content(path: &Path) -> Result<Vec<u8>, std::io::Error> {
+    let mut content = Vec::new();
+    let _ = open_found(path)?.read_to_end(&mut content)?;
+    Ok(content)
+}
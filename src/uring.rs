@@ -0,0 +1,197 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Content comparison using `io_uring` on Linux.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+use io_uring::opcode;
+use io_uring::types::Fd;
+use io_uring::IoUring;
+use io_uring::Probe;
+
+// Standard library imports.
+use std::fs::File;
+use std::os::unix::fs::FileExt as _;
+use std::os::unix::io::AsRawFd as _;
+
+
+/// The number of blocks of each file which may be read at once. While one
+/// block of each file is compared, the reads of the following blocks are in
+/// flight.
+const SLOTS: usize = 2;
+
+/// The largest buffer size used for a single read.
+const MAX_READ_LEN: usize = 1 << 30;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// uring_eq
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the given files of the given length have the same
+/// content, along with the number of bytes read from each file, or `None` if
+/// `io_uring` is unavailable.
+///
+/// The reads of both files are submitted together in batches, with the reads
+/// of the next blocks submitted before the current blocks are compared.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if either file fails to read, including if it
+/// is shorter than the given length.
+///
+/// [`std::io::Error`]: std::io::Error
+pub(crate) fn uring_eq(a: &File, b: &File, len: u64, buffer_size: usize)
+    -> Result<Option<(bool, u64)>, std::io::Error>
+{
+    let Some(mut ring) = read_ring() else { return Ok(None) };
+
+    let mut reader = UringReader {
+        files: [a, b],
+        len,
+        block_size: buffer_size.clamp(1, MAX_READ_LEN),
+        bufs: Vec::new(),
+        in_flight: 0,
+    };
+    reader.bufs = (0..SLOTS * 2)
+        .map(|_| vec![0; reader.block_size])
+        .collect();
+
+    let result = reader.compare(&mut ring);
+
+    // The kernel may still be writing to the buffers of any reads in flight,
+    // so they must complete before the buffers are freed.
+    if reader.drain(&mut ring).is_err() {
+        let _ = std::mem::take(&mut reader.bufs).leak();
+    }
+    result.map(Some)
+}
+
+/// Returns an `IoUring` for reading files, or `None` if `io_uring` or its
+/// read operation is unavailable.
+fn read_ring() -> Option<IoUring> {
+    let ring = IoUring::new(u32::try_from(SLOTS * 2).ok()?).ok()?;
+    let mut probe = Probe::new();
+    ring.submitter().register_probe(&mut probe).ok()?;
+    probe.is_supported(opcode::Read::CODE).then_some(ring)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// UringReader
+////////////////////////////////////////////////////////////////////////////////
+/// The state of a comparison of two files using `io_uring`.
+struct UringReader<'f> {
+    /// The files being compared.
+    files: [&'f File; 2],
+    /// The length of both files.
+    len: u64,
+    /// The size of each block read.
+    block_size: usize,
+    /// The read buffers, one for each file in each slot.
+    bufs: Vec<Vec<u8>>,
+    /// The number of reads submitted which have not completed.
+    in_flight: usize,
+}
+
+impl UringReader<'_> {
+    /// Returns `true` if the files have the same content, along with the
+    /// number of bytes read from each file.
+    fn compare(&mut self, ring: &mut IoUring)
+        -> Result<(bool, u64), std::io::Error>
+    {
+        let block_len = self.block_size as u64;
+        let blocks = self.len.div_ceil(block_len);
+        let mut results = [None; SLOTS * 2];
+        let mut read = 0;
+
+        for block in 0..blocks.min(SLOTS as u64) {
+            self.submit(ring, block)?;
+        }
+
+        for block in 0..blocks {
+            let slot = usize::try_from(block).unwrap_or(0) % SLOTS;
+            let (idx_a, idx_b) = (slot * 2, slot * 2 + 1);
+            while results[idx_a].is_none() || results[idx_b].is_none() {
+                let _ = ring.submit_and_wait(1)?;
+                for entry in ring.completion() {
+                    self.in_flight -= 1;
+                    let idx = usize::try_from(entry.user_data()).unwrap_or(0);
+                    results[idx] = Some(entry.result());
+                }
+            }
+
+            let offset = block * block_len;
+            let n = usize::try_from((self.len - offset).min(block_len))
+                .unwrap_or(self.block_size);
+            for idx in [idx_a, idx_b] {
+                let res = results[idx].take().unwrap_or(0);
+                let done = usize::try_from(res).map_err(|_e| {
+                    std::io::Error::from_raw_os_error(-res)
+                })?;
+                // Short reads are completed without `io_uring`.
+                if done < n {
+                    self.files[idx % 2].read_exact_at(
+                        &mut self.bufs[idx][done..n],
+                        offset + done as u64)?;
+                }
+            }
+            read += n as u64;
+
+            if self.bufs[idx_a][..n] != self.bufs[idx_b][..n] {
+                return Ok((false, read));
+            }
+            if block + (SLOTS as u64) < blocks {
+                self.submit(ring, block + SLOTS as u64)?;
+            }
+        }
+        Ok((true, read))
+    }
+
+    /// Submits the reads of the given block of both files into the block's
+    /// slot.
+    fn submit(&mut self, ring: &mut IoUring, block: u64)
+        -> Result<(), std::io::Error>
+    {
+        let block_len = self.block_size as u64;
+        let slot = usize::try_from(block).unwrap_or(0) % SLOTS;
+        let offset = block * block_len;
+        let n = u32::try_from((self.len - offset).min(block_len))
+            .unwrap_or(u32::MAX);
+
+        for (file_idx, file) in self.files.iter().enumerate() {
+            let idx = slot * 2 + file_idx;
+            let entry = opcode::Read::new(
+                    Fd(file.as_raw_fd()),
+                    self.bufs[idx].as_mut_ptr(),
+                    n)
+                .offset(offset)
+                .build()
+                .user_data(idx as u64);
+
+            // SAFETY: The buffer is at least `n` bytes long and is not
+            // accessed or freed until the read completes, as every read is
+            // drained before the buffers are dropped.
+            #[allow(unsafe_code)]
+            let pushed = unsafe { ring.submission().push(&entry) };
+            pushed.map_err(|_e| std::io::Error::other(
+                "io_uring submission queue is full"))?;
+            self.in_flight += 1;
+        }
+        let _ = ring.submit()?;
+        Ok(())
+    }
+
+    /// Waits for every read in flight to complete.
+    fn drain(&mut self, ring: &mut IoUring) -> Result<(), std::io::Error> {
+        while self.in_flight > 0 {
+            let _ = ring.submit_and_wait(1)?;
+            self.in_flight -= ring.completion().count();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,503 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Directory tree traversal.
+////////////////////////////////////////////////////////////////////////////////
+
+
+// Internal library imports.
+use crate::cancel::CancelToken;
+#[cfg(feature = "gitignore")]
+use crate::cancel::Cancelled;
+use crate::fs::FileSystem;
+use crate::fs::StdFileSystem;
+
+// External library imports.
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+// Standard library imports.
+#[cfg(feature = "gitignore")]
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs::FileType;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::PoisonError;
+use std::sync::RwLock;
+use std::sync::RwLockReadGuard;
+use std::sync::RwLockWriteGuard;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// EntryKind
+////////////////////////////////////////////////////////////////////////////////
+/// The kind of a directory entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub enum EntryKind {
+    /// A regular file.
+    File,
+    /// A directory.
+    Dir,
+    /// A symbolic link.
+    Symlink,
+    /// Any other kind of file, such as a FIFO, socket, or device node.
+    Other,
+}
+
+impl From<FileType> for EntryKind {
+    fn from(file_type: FileType) -> Self {
+        if file_type.is_dir() {
+            Self::Dir
+        } else if file_type.is_symlink() {
+            Self::Symlink
+        } else if is_special(file_type) {
+            Self::Other
+        } else {
+            Self::File
+        }
+    }
+}
+
+impl FromStr for EntryKind {
+    type Err = EntryKindParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("regular") || s.eq_ignore_ascii_case("file") {
+            Ok(Self::File)
+        } else if s.eq_ignore_ascii_case("dir") {
+            Ok(Self::Dir)
+        } else if s.eq_ignore_ascii_case("symlink") {
+            Ok(Self::Symlink)
+        } else if s.eq_ignore_ascii_case("special") {
+            Ok(Self::Other)
+        } else {
+            Err(EntryKindParseError)
+        }
+    }
+}
+
+/// An error indicating a failure to parse an [`EntryKind`].
+///
+/// [`EntryKind`]: EntryKind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryKindParseError;
+
+impl std::error::Error for EntryKindParseError {}
+
+impl std::fmt::Display for EntryKindParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failure to parse EntryKind")
+    }
+}
+
+/// Returns `true` if the given `FileType` is a FIFO, socket, or device node.
+#[cfg(unix)]
+pub(crate) fn is_special(file_type: FileType) -> bool {
+    use std::os::unix::fs::FileTypeExt as _;
+
+    file_type.is_fifo()
+        || file_type.is_socket()
+        || file_type.is_block_device()
+        || file_type.is_char_device()
+}
+
+/// Returns `true` if the given `FileType` is a FIFO, socket, or device node.
+#[cfg(not(unix))]
+pub(crate) const fn is_special(_file_type: FileType) -> bool {
+    false
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Manifest
+////////////////////////////////////////////////////////////////////////////////
+/// A cache of directory listings used to speed up repeated directory tree
+/// traversals.
+///
+/// Each listing is stored with the modification time of its directory. Since
+/// a directory's modification time changes whenever entries are added,
+/// removed, or renamed, unchanged directories can be traversed using the
+/// cached listing instead of being read again. Symbolic links to directories
+/// are not followed.
+///
+/// A `Manifest` is `Send` and `Sync`, and may be shared between threads which
+/// traverse directory trees concurrently.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct Manifest {
+    /// The cached directory listings, keyed by directory path.
+    dirs: RwLock<BTreeMap<PathBuf, DirListing>>,
+}
+
+impl Clone for Manifest {
+    fn clone(&self) -> Self {
+        Self {
+            dirs: RwLock::new(self.read().clone()),
+        }
+    }
+}
+
+impl PartialEq for Manifest {
+    fn eq(&self, other: &Self) -> bool {
+        *self.read() == *other.read()
+    }
+}
+
+impl Eq for Manifest {}
+
+/// A cached directory listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+struct DirListing {
+    /// The modification time of the directory when it was listed.
+    modified: SystemTime,
+    /// The directory's entries, sorted by name.
+    entries: Vec<(OsString, EntryKind)>,
+}
+
+impl Manifest {
+    /// Returns an empty `Manifest`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of cached directory listings.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Returns `true` if no directory listings are cached.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Locks the cached listings for reading. The listings are only a cache,
+    /// so a lock poisoned by a panicking thread is still used.
+    fn read(&self) -> RwLockReadGuard<'_, BTreeMap<PathBuf, DirListing>> {
+        self.dirs.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the cached listings for writing. The listings are only a cache,
+    /// so a lock poisoned by a panicking thread is still used.
+    fn write(&self) -> RwLockWriteGuard<'_, BTreeMap<PathBuf, DirListing>> {
+        self.dirs.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Traverses the directory tree at `root` and returns the paths of all
+    /// non-directory entries within it, in depth-first order sorted by name.
+    ///
+    /// Directories whose modification time matches the cached listing are not
+    /// read again. Listings for directories under `root` which no longer exist
+    /// are removed.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if a directory can't be read.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    pub fn walk(&self, root: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+        self.walk_cancellable(root, &CancelToken::new())
+    }
+
+    /// Traverses the directory tree at `root` as [`walk`] does, checking the
+    /// given [`CancelToken`] before reading each directory.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if a directory can't be read, or wrapping
+    /// a [`Cancelled`] error if the token is cancelled.
+    ///
+    /// [`walk`]: Manifest::walk
+    /// [`CancelToken`]: crate::CancelToken
+    /// [`Cancelled`]: crate::Cancelled
+    /// [`std::io::Error`]: std::io::Error
+    pub fn walk_cancellable(&self, root: &Path, cancel: &CancelToken)
+        -> Result<Vec<PathBuf>, std::io::Error>
+    {
+        let mut files = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![root.to_path_buf()];
+
+        while let Some(dir) = stack.pop() {
+            cancel.check()?;
+            let entries = self.listing(&dir)?;
+            // Push subdirectories in reverse so they are visited in order.
+            for (name, kind) in entries.iter().rev() {
+                if *kind == EntryKind::Dir { stack.push(dir.join(name)); }
+            }
+            files.extend(entries
+                .iter()
+                .filter(|(_, kind)| *kind != EntryKind::Dir)
+                .map(|(name, _)| dir.join(name)));
+            let _ = visited.insert(dir);
+        }
+
+        self.write().retain(|dir, _| !dir.starts_with(root)
+            || visited.contains(dir));
+
+        Ok(files)
+    }
+
+    /// Returns the entries of the given directory, reading the directory only
+    /// if the cached listing is out of date.
+    fn listing(&self, dir: &Path)
+        -> Result<Vec<(OsString, EntryKind)>, std::io::Error>
+    {
+        let modified = std::fs::metadata(dir)?.modified()?;
+        if let Some(listing) = self.read().get(dir) {
+            if listing.modified == modified {
+                return Ok(listing.entries.clone());
+            }
+        }
+
+        let entries = StdFileSystem.read_dir(dir)?;
+        let _ = self.write().insert(dir.to_path_buf(), DirListing {
+            modified,
+            entries: entries.clone(),
+        });
+        Ok(entries)
+    }
+
+    /// Loads a `Manifest` from the JSON file at the given path.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the file can't be read or is not a
+    /// valid manifest.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    #[cfg(feature = "serde")]
+    pub fn load(path: &Path) -> Result<Self, std::io::Error> {
+        let file = std::fs::File::open(path)?;
+        let reader = std::io::BufReader::new(file);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Saves the `Manifest` as JSON to the file at the given path.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`std::io::Error`] if the file can't be written.
+    ///
+    /// [`std::io::Error`]: std::io::Error
+    #[cfg(feature = "serde")]
+    pub fn save(&self, path: &Path) -> Result<(), std::io::Error> {
+        let file = std::fs::File::create(path)?;
+        let writer = std::io::BufWriter::new(file);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// walk_tree
+////////////////////////////////////////////////////////////////////////////////
+/// Traverses the directory tree at `root` and returns the paths of all
+/// non-directory entries within it, in depth-first order sorted by name.
+///
+/// Symbolic links to directories are not followed.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if a directory can't be read.
+///
+/// [`std::io::Error`]: std::io::Error
+pub fn walk_tree(root: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    Manifest::new().walk(root)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// walk_fs
+////////////////////////////////////////////////////////////////////////////////
+/// Traverses the directory tree at `root` in the given [`FileSystem`].
+///
+/// Returns the paths of all non-directory entries within the tree, in
+/// depth-first order sorted by name, as [`walk_tree`] does for the local file
+/// system.
+///
+/// ### Errors
+///
+/// Returns a [`std::io::Error`] if a directory can't be read.
+///
+/// [`FileSystem`]: crate::FileSystem
+/// [`walk_tree`]: walk_tree
+/// [`std::io::Error`]: std::io::Error
+pub fn walk_fs(fs: &dyn FileSystem, root: &Path)
+    -> Result<Vec<PathBuf>, std::io::Error>
+{
+    let mut files = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let entries = fs.read_dir(&dir)?;
+        // Push subdirectories in reverse so they are visited in order.
+        for (name, kind) in entries.iter().rev() {
+            if *kind == EntryKind::Dir { stack.push(dir.join(name)); }
+        }
+        files.extend(entries
+            .iter()
+            .filter(|(_, kind)| *kind != EntryKind::Dir)
+            .map(|(name, _)| dir.join(name)));
+    }
+    Ok(files)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// walk_tree_ignoring
+////////////////////////////////////////////////////////////////////////////////
+/// Traverses the directory tree at `root` and returns the paths of all
+/// non-directory entries within it which are not excluded by an ignore file,
+/// in depth-first order sorted by name.
+///
+/// The `.gitignore` and `.ignore` files in `root`, its subdirectories, and its
+/// parent directories are respected, along with `.git/info/exclude` and the
+/// global git excludes file. Any [`FCMP_IGNORE_FILE`] within the tree is also
+/// respected. Ignore files are respected whether or not the tree is inside a
+/// git repository. Hidden files are not skipped unless they
+/// are ignored. Symbolic links to directories are not followed.
+///
+/// ### Errors
+///
+/// Returns a [`WalkError`] if a directory or ignore file can't be read.
+///
+/// [`FCMP_IGNORE_FILE`]: FCMP_IGNORE_FILE
+/// [`WalkError`]: WalkError
+#[cfg(feature = "gitignore")]
+pub fn walk_tree_ignoring(root: &Path) -> Result<Vec<PathBuf>, WalkError> {
+    walk_tree_ignoring_cancellable(root, &CancelToken::new())
+}
+
+/// Traverses the directory tree at `root` as [`walk_tree_ignoring`] does,
+/// checking the given [`CancelToken`] before reading each entry.
+///
+/// ### Errors
+///
+/// Returns a [`WalkError`] if a directory or ignore file can't be read, or
+/// [`WalkError::Cancelled`] if the token is cancelled.
+///
+/// [`walk_tree_ignoring`]: walk_tree_ignoring
+/// [`CancelToken`]: crate::CancelToken
+/// [`WalkError`]: WalkError
+/// [`WalkError::Cancelled`]: WalkError::Cancelled
+#[cfg(feature = "gitignore")]
+pub fn walk_tree_ignoring_cancellable(root: &Path, cancel: &CancelToken)
+    -> Result<Vec<PathBuf>, WalkError>
+{
+    let walker = ignore::WalkBuilder::new(root)
+        .standard_filters(false)
+        .ignore(true)
+        .git_ignore(true)
+        .git_global(true)
+        .git_exclude(true)
+        .parents(true)
+        .require_git(false)
+        .add_custom_ignore_filename(FCMP_IGNORE_FILE)
+        .build();
+
+    let mut files = Vec::new();
+    for entry in walker {
+        if cancel.is_cancelled() { return Err(Cancelled.into()); }
+        let entry = entry?;
+        if entry.file_type().is_some_and(|t| !t.is_dir()) {
+            files.push(entry.into_path());
+        }
+    }
+    files.sort_by(|a, b| tree_order(a, b));
+    Ok(files)
+}
+
+/// An error which occurs while traversing a directory tree with
+/// [`walk_tree_ignoring`].
+///
+/// [`walk_tree_ignoring`]: walk_tree_ignoring
+#[cfg(feature = "gitignore")]
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum WalkError {
+    /// A directory or ignore file can't be read, or an ignore file is
+    /// invalid.
+    #[error(transparent)]
+    Ignore(#[from] ignore::Error),
+
+    /// The traversal was aborted by its [`CancelToken`].
+    ///
+    /// [`CancelToken`]: crate::CancelToken
+    #[error("directory traversal cancelled")]
+    Cancelled(#[from] Cancelled),
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// filter_fcmpignore
+////////////////////////////////////////////////////////////////////////////////
+/// The name of the ignore file which excludes paths from directory traversals
+/// by fcmp. The file uses gitignore syntax, and is independent of git.
+pub const FCMP_IGNORE_FILE: &str = ".fcmpignore";
+
+/// Removes the paths excluded by the [`FCMP_IGNORE_FILE`] in `root` from the
+/// given paths, which should be within `root`. Returns the paths unchanged if
+/// `root` has no ignore file.
+///
+/// ### Errors
+///
+/// Returns an [`ignore::Error`] if the ignore file can't be read or parsed.
+///
+/// [`FCMP_IGNORE_FILE`]: FCMP_IGNORE_FILE
+/// [`ignore::Error`]: ignore::Error
+#[cfg(feature = "gitignore")]
+pub fn filter_fcmpignore(root: &Path, paths: Vec<PathBuf>)
+    -> Result<Vec<PathBuf>, ignore::Error>
+{
+    let ignore_file = root.join(FCMP_IGNORE_FILE);
+    if !ignore_file.is_file() { return Ok(paths); }
+
+    let mut builder = ignore::gitignore::GitignoreBuilder::new(root);
+    if let Some(err) = builder.add(ignore_file) { return Err(err); }
+    let ignore = builder.build()?;
+
+    Ok(paths
+        .into_iter()
+        .filter(|path| !path.starts_with(root) || !ignore
+            .matched_path_or_any_parents(path, false)
+            .is_ignore())
+        .collect())
+}
+
+/// Orders paths in the same depth-first order as [`walk_tree`]: the files of
+/// a directory sorted by name, followed by the contents of its subdirectories.
+///
+/// [`walk_tree`]: walk_tree
+#[cfg(feature = "gitignore")]
+fn tree_order(a: &Path, b: &Path) -> Ordering {
+    let a_len = a.components().count();
+    let b_len = b.components().count();
+    for (i, (ca, cb)) in a.components().zip(b.components()).enumerate() {
+        if ca != cb {
+            let a_is_file = i + 1 == a_len;
+            let b_is_file = i + 1 == b_len;
+            return b_is_file.cmp(&a_is_file).then_with(|| ca.cmp(&cb));
+        }
+    }
+    a_len.cmp(&b_len)
+}
@@ -0,0 +1,170 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Re-comparing files when they change.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::cancel::CancelToken;
+use crate::compare::compare_all;
+use crate::compare::Comparator;
+use crate::error::FcmpError;
+
+// External library imports.
+use notify::EventKind;
+use notify::RecursiveMode;
+use notify::Watcher as _;
+
+// Standard library imports.
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The time to wait after a change for further changes before the files are
+/// compared, so that a burst of writes is handled once.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// The interval at which the [`CancelToken`] is checked while waiting for
+/// changes.
+///
+/// [`CancelToken`]: crate::CancelToken
+const CANCEL_POLL: Duration = Duration::from_millis(250);
+
+
+////////////////////////////////////////////////////////////////////////////////
+// watch_paths
+////////////////////////////////////////////////////////////////////////////////
+/// Watches the files at the given paths, comparing them according to the
+/// given [`Comparator`] whenever any of them is created, modified, or
+/// removed.
+///
+/// The given function is called with the paths which changed and the result
+/// of [`compare_all`], once with no changed paths when watching starts and
+/// then after each change. Watching stops when the function returns `false`
+/// or the `Comparator`'s [`CancelToken`] is cancelled.
+///
+/// Each path's parent directory is watched, so that files which are missing
+/// or replaced by renaming are still seen.
+///
+/// ### Errors
+///
+/// Returns a [`WatchError`] if the paths can't be watched.
+///
+/// [`Comparator`]: crate::Comparator
+/// [`compare_all`]: crate::compare_all
+/// [`CancelToken`]: crate::CancelToken
+/// [`WatchError`]: WatchError
+pub fn watch_paths<F>(
+    paths: &[PathBuf],
+    reverse: bool,
+    comparator: &Comparator,
+    mut on_change: F)
+    -> Result<(), WatchError>
+    where F: FnMut(&[PathBuf], Result<usize, FcmpError>) -> bool
+{
+    let absolute = paths
+        .iter()
+        .map(|path| std::path::absolute(path)
+            .map_err(|source| WatchError::Path {
+                path: path.clone(),
+                source,
+            }))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    let dirs: BTreeSet<&Path> = absolute
+        .iter()
+        .map(|path| path.parent().unwrap_or(path))
+        .collect();
+    for dir in dirs {
+        watcher.watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|source| WatchError::Watch {
+                path: dir.to_path_buf(),
+                source,
+            })?;
+    }
+
+    let compare = || compare_all(
+        paths.iter().map(PathBuf::as_path),
+        reverse,
+        comparator);
+    if !on_change(&[], compare()) { return Ok(()); }
+
+    let mut timeout = CANCEL_POLL;
+    let mut changed = BTreeSet::new();
+    loop {
+        if comparator.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return Ok(());
+        }
+        match receiver.recv_timeout(timeout) {
+            Ok(event) => {
+                let event = event?;
+                // Reading the files to compare them generates access events,
+                // so they are ignored.
+                if matches!(event.kind, EventKind::Access(_)) { continue; }
+                for path in &event.paths {
+                    if let Some(idx) = absolute.iter().position(|p| p == path) {
+                        let _ = changed.insert(idx);
+                        timeout = DEBOUNCE;
+                    }
+                }
+            },
+            Err(RecvTimeoutError::Timeout) if !changed.is_empty() => {
+                let changed_paths: Vec<PathBuf> = std::mem::take(&mut changed)
+                    .into_iter()
+                    .map(|idx| paths[idx].clone())
+                    .collect();
+                timeout = CANCEL_POLL;
+                if !on_change(&changed_paths, compare()) { return Ok(()); }
+            },
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// WatchError
+////////////////////////////////////////////////////////////////////////////////
+/// An error which occurs while watching files with [`watch_paths`].
+///
+/// [`watch_paths`]: watch_paths
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum WatchError {
+    /// The absolute path of a watched file can't be determined.
+    #[error("failed to watch '{}'", path.display())]
+    Path {
+        /// The path of the file.
+        path: PathBuf,
+        /// The error which occurred when resolving the path.
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// A directory containing a watched file can't be watched.
+    #[error("failed to watch '{}'", path.display())]
+    Watch {
+        /// The path of the directory.
+        path: PathBuf,
+        /// The error which occurred when watching the directory.
+        #[source]
+        source: notify::Error,
+    },
+
+    /// The file watcher can't be created or reported an error.
+    #[error(transparent)]
+    Notify(#[from] notify::Error),
+}
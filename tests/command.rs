@@ -0,0 +1,217 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Tests for parsing command line options.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+use fcmp::command::split_args;
+use fcmp::command::ExitMap;
+use fcmp::command::ExitMapParseError;
+use fcmp::command::FcmpCli;
+use fcmp::command::FcmpCommand;
+use fcmp::command::FcmpOptions;
+use fcmp::command::Outcome;
+use fcmp::MissingFileBehavior;
+use pretty_assertions::assert_eq;
+
+// Standard library imports.
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ExitMap
+////////////////////////////////////////////////////////////////////////////////
+/// Tests that outcomes not given in an `ExitMap` use their default codes.
+#[test]
+fn exit_map_parse_partial() {
+    let map: ExitMap = "error=3, Other = 7".parse().expect("parse exit map");
+
+    assert_eq!(map, ExitMap {
+        first: 0,
+        other: 7,
+        equal: 0,
+        error: 3,
+    });
+    assert_eq!(map.code(Outcome::Error), 3);
+}
+
+/// Tests that the full range of exit status codes is accepted.
+#[test]
+fn exit_map_parse_bounds() {
+    let map: ExitMap = "first=0,other=255,equal=1,error=254".parse()
+        .expect("parse exit map");
+
+    assert_eq!(map.code(Outcome::First), 0);
+    assert_eq!(map.code(Outcome::Other), 255);
+    assert_eq!(map.code(Outcome::Equal), 1);
+    assert_eq!(map.code(Outcome::Error), 254);
+}
+
+/// Tests that codes which don't fit in an exit status are rejected, rather
+/// than being truncated by the operating system.
+#[test]
+fn exit_map_parse_out_of_range() {
+    assert_eq!("error=256".parse::<ExitMap>(), Err(ExitMapParseError));
+    assert_eq!("other=-1".parse::<ExitMap>(), Err(ExitMapParseError));
+    assert_eq!("first=1000000000000".parse::<ExitMap>(),
+        Err(ExitMapParseError));
+}
+
+/// Tests that malformed entries are rejected.
+#[test]
+fn exit_map_parse_malformed() {
+    assert_eq!("error".parse::<ExitMap>(), Err(ExitMapParseError));
+    assert_eq!("error=".parse::<ExitMap>(), Err(ExitMapParseError));
+    assert_eq!("missing=1".parse::<ExitMap>(), Err(ExitMapParseError));
+    assert_eq!("first=0,".parse::<ExitMap>(), Err(ExitMapParseError));
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FCMP_OPTS
+////////////////////////////////////////////////////////////////////////////////
+/// Parses the given command line with the given `FCMP_OPTS` value.
+fn parse(args: &[&str], env_opts: &str) -> FcmpCommand {
+    FcmpCli::try_parse_from_with_opts(args, Some(OsStr::new(env_opts)))
+        .expect("parse arguments")
+        .into_command()
+}
+
+/// Returns the selection options of the given command.
+fn selection_options(command: FcmpCommand) -> FcmpOptions {
+    match command {
+        FcmpCommand::Newest(options)
+            | FcmpCommand::Oldest(options)
+            | FcmpCommand::Sort(options) => options,
+        other => panic!("expected selection command, got {other:?}"),
+    }
+}
+
+/// Tests that arguments are split on whitespace.
+#[test]
+fn split_args_whitespace() {
+    assert_eq!(
+        split_args("  --missing\terror -r  ").expect("split arguments"),
+        ["--missing", "error", "-r"]);
+    assert_eq!(split_args("").expect("split arguments"), [""; 0]);
+}
+
+/// Tests that quotes group arguments and are removed.
+#[test]
+fn split_args_quotes() {
+    assert_eq!(
+        split_args(r#"--where 'size > 1K' "a b"c '' x"y'z""#)
+            .expect("split arguments"),
+        ["--where", "size > 1K", "a bc", "", "xy'z"]);
+}
+
+/// Tests that an unterminated quote is an error.
+#[test]
+fn split_args_unterminated_quote() {
+    assert!(split_args("--where 'size > 1K").is_err());
+    assert!(split_args("\"").is_err());
+}
+
+/// Tests that environment arguments apply when only paths are given.
+#[test]
+fn env_opts_before_paths() {
+    let options = selection_options(parse(
+        &["fcmp", "a", "b"],
+        "--missing error -r"));
+
+    assert_eq!(options.missing, MissingFileBehavior::Error);
+    assert!(options.reverse);
+    assert_eq!(options.paths, [PathBuf::from("a"), PathBuf::from("b")]);
+}
+
+/// Tests that environment arguments are inserted after a selection
+/// subcommand.
+#[test]
+fn env_opts_after_subcommand() {
+    let command = parse(&["fcmp", "sort", "a"], "--missing error");
+
+    assert!(matches!(command, FcmpCommand::Sort(_)));
+    assert_eq!(
+        selection_options(command).missing,
+        MissingFileBehavior::Error);
+}
+
+/// Tests that environment arguments are not given to other subcommands.
+#[test]
+fn env_opts_skip_other_subcommands() {
+    let command = parse(&["fcmp", "check", "a", "b"], "--missing error");
+
+    assert!(matches!(command, FcmpCommand::Check { .. }));
+}
+
+/// Tests that command line arguments override environment arguments.
+#[test]
+fn env_opts_overridden() {
+    let options = selection_options(parse(
+        &["fcmp", "--missing", "ignore", "-r", "a"],
+        "--missing error -r"));
+
+    assert_eq!(options.missing, MissingFileBehavior::Ignore);
+    assert!(options.reverse);
+
+    let options = selection_options(parse(
+        &["fcmp", "sort", "--missing", "ignore", "a"],
+        "--missing error"));
+
+    assert_eq!(options.missing, MissingFileBehavior::Ignore);
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// rotate
+////////////////////////////////////////////////////////////////////////////////
+/// Tests that `rotate` refuses options which would change its output,
+/// instead of ignoring them.
+#[test]
+fn rotate_refuses_unsupported_options() {
+    let refused: [&[&str]; 8] = [
+        &["-q"],
+        &["--json"],
+        &["--output", "csv"],
+        &["--sort"],
+        &["--top", "2"],
+        &["--index"],
+        &["--exit-map", "error=3"],
+        &["--on-equal", "print-all"],
+    ];
+    for options in refused {
+        let args = ["fcmp", "rotate", "--keep", "2", "a", "b"]
+            .iter()
+            .chain(options);
+        assert!(FcmpCli::try_parse_from_with_opts(args, None).is_err(),
+            "{options:?} accepted");
+    }
+}
+
+/// Tests that `rotate` accepts the options used to select and print the
+/// files to remove.
+#[test]
+fn rotate_accepts_selection_options() {
+    let command = FcmpCli::try_parse_from_with_opts(
+            ["fcmp", "rotate", "--keep", "2", "--print0", "--where",
+                "size > 1K", "-r", "--format", "{path}", "a", "b"],
+            None)
+        .expect("parse arguments")
+        .into_command();
+
+    match command {
+        FcmpCommand::Rotate { keep, options } => {
+            assert_eq!(keep, 2);
+            assert!(options.print0);
+            assert!(options.reverse);
+            assert!(options.filter.is_some());
+        },
+        other => panic!("expected rotate command, got {other:?}"),
+    }
+}
@@ -0,0 +1,88 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Tests for comparing more files than can be held open at once.
+////////////////////////////////////////////////////////////////////////////////
+#![cfg(unix)]
+
+// External library imports.
+use pretty_assertions::assert_eq;
+
+// Standard library imports.
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helpers
+////////////////////////////////////////////////////////////////////////////////
+/// A temporary directory which is removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates an empty temporary directory unique to the given test.
+    fn new(test: &str) -> Self {
+        let path = std::env::temp_dir()
+            .join(format!("fcmp-{}-{test}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("create temporary directory");
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Tests
+////////////////////////////////////////////////////////////////////////////////
+/// Tests that sorting files with identical content under a low open file
+/// limit gives the same order as without a limit.
+///
+/// Each file is newer than the one before it, so files which can't be opened
+/// for comparison would be reported as differing and sorted newest first.
+#[test]
+fn sort_equal_content_under_low_fd_limit() {
+    const FILES: u64 = 100;
+    let dir = TempDir::new("descriptors");
+    let start = SystemTime::now() - Duration::from_secs(FILES);
+    let paths: Vec<PathBuf> = (0..FILES)
+        .map(|i| {
+            let path = dir.0.join(format!("f{i}"));
+            std::fs::write(&path, "content").expect("write file");
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(
+                    start + Duration::from_secs(i)))
+                .expect("set modification time");
+            path
+        })
+        .collect();
+
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg("ulimit -n 20 && exec \"$0\" -d --buffer-size 4K --sort \"$@\"")
+        .arg(env!("CARGO_BIN_EXE_fcmp"))
+        .args(&paths)
+        .output()
+        .expect("run fcmp");
+
+    assert!(output.status.success(), "fcmp failed: {output:?}");
+    let sorted: Vec<PathBuf> = String::from_utf8(output.stdout)
+        .expect("UTF-8 output")
+        .lines()
+        .map(PathBuf::from)
+        .collect();
+    assert_eq!(sorted, paths);
+}
@@ -0,0 +1,140 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Tests for ordering files whose key is unavailable.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+use fcmp::compare_all;
+use fcmp::partial_cmp_paths;
+use fcmp::Comparator;
+use fcmp::CompareKey;
+use fcmp::CompareOutcome;
+use fcmp::EntryKind;
+use fcmp::FcmpError;
+use fcmp::FileSystem;
+use fcmp::FsMetadata;
+use fcmp::MemoryFileSystem;
+use pretty_assertions::assert_eq;
+
+// Standard library imports.
+use std::cmp::Ordering;
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helpers
+////////////////////////////////////////////////////////////////////////////////
+/// A [`MemoryFileSystem`] which doesn't record the modification time of the
+/// file named `untimed`.
+#[derive(Debug)]
+struct UntimedFileSystem(MemoryFileSystem);
+
+impl FileSystem for UntimedFileSystem {
+    fn metadata(&self, path: &Path)
+        -> Result<Option<FsMetadata>, std::io::Error>
+    {
+        let untimed = path.file_name().is_some_and(|name| name == "untimed");
+        Ok(self.0.metadata(path)?.map(|metadata| FsMetadata {
+            modified: if untimed { None } else { metadata.modified },
+            ..metadata
+        }))
+    }
+
+    fn open(&self, path: &Path)
+        -> Result<Option<Box<dyn Read + Send>>, std::io::Error>
+    {
+        self.0.open(path)
+    }
+
+    fn read_dir(&self, path: &Path)
+        -> Result<Vec<(OsString, EntryKind)>, std::io::Error>
+    {
+        self.0.read_dir(path)
+    }
+}
+
+/// Returns a `Comparator` reading a file system holding a large `untimed`
+/// file, a newer, smaller `timed` file, and an `older` file.
+fn comparator(fallback_key: Option<CompareKey>) -> Comparator {
+    let fs = MemoryFileSystem::new();
+    let now = SystemTime::now();
+    fs.insert("untimed", "large content", now - Duration::from_secs(60));
+    fs.insert("timed", "small", now);
+    fs.insert("older", "small", now - Duration::from_secs(120));
+
+    Comparator {
+        fs: Some(Arc::new(UntimedFileSystem(fs))),
+        fallback_key,
+        ..Comparator::default()
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Tests
+////////////////////////////////////////////////////////////////////////////////
+/// Tests that an unavailable modification time is an error without a
+/// fallback key.
+#[test]
+fn unavailable_key_without_fallback_is_error() {
+    let comparator = comparator(None);
+
+    let result = partial_cmp_paths(
+        Path::new("untimed"),
+        Path::new("timed"),
+        &comparator);
+
+    match result {
+        Err(FcmpError::KeyUnavailable { path, key, .. }) => {
+            assert_eq!(path, Path::new("untimed"));
+            assert_eq!(key, CompareKey::Modified);
+        },
+        other => panic!("expected KeyUnavailable error, got {other:?}"),
+    }
+}
+
+/// Tests that an available modification time is not an error without a
+/// fallback key.
+#[test]
+fn available_key_without_fallback_is_ordered() {
+    let comparator = comparator(None);
+
+    let outcome = partial_cmp_paths(
+            Path::new("timed"),
+            Path::new("older"),
+            &comparator)
+        .expect("compare timed files");
+
+    assert_eq!(outcome, CompareOutcome::Ordered(Ordering::Greater));
+}
+
+/// Tests that files are ordered by the fallback key when the modification
+/// time of either is unavailable.
+#[test]
+fn unavailable_key_uses_fallback() {
+    let comparator = comparator(Some(CompareKey::Size));
+
+    let outcome = partial_cmp_paths(
+            Path::new("untimed"),
+            Path::new("timed"),
+            &comparator)
+        .expect("compare with fallback key");
+    assert_eq!(outcome, CompareOutcome::Ordered(Ordering::Greater));
+
+    let newest = compare_all(
+            [Path::new("timed"), Path::new("untimed")],
+            false,
+            &comparator)
+        .expect("select with fallback key");
+    assert_eq!(newest, 1);
+}
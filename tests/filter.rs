@@ -0,0 +1,229 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Tests for parsing and evaluating filter expressions.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+use fcmp::parse_size;
+use fcmp::FileResult;
+use fcmp::FilterExpr;
+use fcmp::FilterExprErrorKind;
+use fcmp::FilterExprParseError;
+use fcmp::SizeParseError;
+use pretty_assertions::assert_eq;
+
+// Standard library imports.
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helpers
+////////////////////////////////////////////////////////////////////////////////
+/// The time used as the current time when evaluating expressions.
+fn now() -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000)
+}
+
+/// Returns a found file with the given path and size, modified the given
+/// number of seconds before `now`.
+fn file(path: &str, size: u64, age_secs: u64) -> FileResult {
+    FileResult {
+        index: 0,
+        path: PathBuf::from(path),
+        found: true,
+        modified: Some(now() - Duration::from_secs(age_secs)),
+        accessed: None,
+        changed: None,
+        size: Some(size),
+    }
+}
+
+/// Returns `true` if the given file satisfies the given expression.
+fn matches(expr: &str, file: &FileResult) -> bool {
+    expr.parse::<FilterExpr>()
+        .unwrap_or_else(|e| panic!("parse {expr:?}: {e}"))
+        .matches(file, now())
+}
+
+/// Returns the error from parsing the given expression.
+fn parse_error(expr: &str) -> FilterExprParseError {
+    match expr.parse::<FilterExpr>() {
+        Ok(parsed) => panic!("parsed {expr:?} as {parsed:?}"),
+        Err(e) => e,
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Grammar
+////////////////////////////////////////////////////////////////////////////////
+/// Tests each kind of condition.
+#[test]
+fn conditions() {
+    let f = file("dir/log.txt", 2048, 3600);
+
+    assert!(matches("size == 2K", &f));
+    assert!(matches("size >= 2048", &f));
+    assert!(!matches("size < 2k", &f));
+    assert!(matches("age > 30m", &f));
+    assert!(!matches("age > 2h", &f));
+    assert!(matches("mtime < 2001-09-09T01:46:40Z", &f));
+    assert!(matches("name == '*.txt'", &f));
+    assert!(matches("name != *.log", &f));
+    assert!(matches("path == \"dir/*\"", &f));
+    assert!(matches("SIZE = 2K", &f));
+}
+
+/// Tests that conditions on the size or time of a missing file are never
+/// satisfied.
+#[test]
+fn missing_file_conditions() {
+    let missing = FileResult {
+        found: false,
+        modified: None,
+        size: None,
+        ..file("missing", 0, 0)
+    };
+
+    assert!(!matches("size < 1", &missing));
+    assert!(!matches("size != 1", &missing));
+    assert!(!matches("age >= 0s", &missing));
+    assert!(matches("!size < 1", &missing));
+    assert!(matches("name == missing", &missing));
+}
+
+/// Tests that `&&` binds more tightly than `||`, and `!` more tightly than
+/// both.
+#[test]
+fn precedence() {
+    let f = file("a.txt", 100, 0);
+
+    // Parsed as `true || (false && false)`.
+    assert!(matches("size == 100 || size == 1 && size == 2", &f));
+    assert!(!matches("(size == 100 || size == 1) && size == 2", &f));
+    // Parsed as `(false && false) || true`.
+    assert!(matches("size == 1 && size == 2 || size == 100", &f));
+    // Parsed as `(!false) && true`.
+    assert!(matches("!size == 1 && size == 100", &f));
+    assert!(!matches("!(size == 1 || size == 100)", &f));
+    assert!(matches("!!size == 100", &f));
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Errors
+////////////////////////////////////////////////////////////////////////////////
+/// Tests that an expression ending early is reported at its end.
+#[test]
+fn unexpected_end() {
+    for expr in ["", "size > 1M ||", "(size > 1", "size >", "size", "!"] {
+        assert_eq!(parse_error(expr), FilterExprParseError {
+            offset: expr.len(),
+            kind: FilterExprErrorKind::UnexpectedEnd,
+        }, "{expr:?}");
+    }
+}
+
+/// Tests that misplaced tokens are reported at their offset.
+#[test]
+fn unexpected_token() {
+    let unexpected = |offset, token: &str| FilterExprParseError {
+        offset,
+        kind: FilterExprErrorKind::UnexpectedToken(token.to_string()),
+    };
+
+    assert_eq!(parse_error("size > 1 )"), unexpected(9, ")"));
+    assert_eq!(parse_error("size 1"), unexpected(5, "1"));
+    assert_eq!(parse_error("size > 1 size > 2"), unexpected(9, "size"));
+    assert_eq!(parse_error("size > 1 & size > 2"), unexpected(9, "&"));
+    assert_eq!(parse_error("|| size > 1"), unexpected(0, "||"));
+    assert_eq!(parse_error("(size > 1 size"), unexpected(10, "size"));
+}
+
+/// Tests errors in the parts of a condition.
+#[test]
+fn invalid_condition() {
+    assert_eq!(parse_error("name == 'x"), FilterExprParseError {
+        offset: 8,
+        kind: FilterExprErrorKind::UnterminatedQuote,
+    });
+    assert_eq!(parse_error("size > 1 && color == red"), FilterExprParseError {
+        offset: 12,
+        kind: FilterExprErrorKind::UnknownField("color".to_string()),
+    });
+    assert_eq!(parse_error("name < x"), FilterExprParseError {
+        offset: 5,
+        kind: FilterExprErrorKind::UnsupportedOperator {
+            field: "name".to_string(),
+            op: "<".to_string(),
+        },
+    });
+
+    let invalid_at = |expr| match parse_error(expr) {
+        FilterExprParseError {
+            offset,
+            kind: FilterExprErrorKind::InvalidValue { field, value, .. },
+        } => (offset, field, value),
+        other => panic!("expected InvalidValue, got {other:?}"),
+    };
+    assert_eq!(invalid_at("age < -1d"),
+        (6, "age".to_string(), "-1d".to_string()));
+    assert_eq!(invalid_at("size > 17179869184T"),
+        (7, "size".to_string(), "17179869184T".to_string()));
+    assert_eq!(invalid_at("mtime < yesterday"),
+        (8, "mtime".to_string(), "yesterday".to_string()));
+}
+
+/// Tests that errors are displayed with their offset.
+#[test]
+fn error_display() {
+    assert_eq!(
+        parse_error("size > 1M ||").to_string(),
+        "at offset 12: unexpected end of expression");
+    assert_eq!(
+        parse_error("color == red").to_string(),
+        "at offset 0: unknown field 'color'");
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// parse_size
+////////////////////////////////////////////////////////////////////////////////
+/// Tests sizes with and without suffixes.
+#[test]
+fn parse_size_suffixes() {
+    assert_eq!(parse_size("0"), Ok(0));
+    assert_eq!(parse_size("512"), Ok(512));
+    assert_eq!(parse_size("512B"), Ok(512));
+    assert_eq!(parse_size("4k"), Ok(4 << 10));
+    assert_eq!(parse_size("4K"), Ok(4 << 10));
+    assert_eq!(parse_size("3M"), Ok(3 << 20));
+    assert_eq!(parse_size("2G"), Ok(2 << 30));
+    assert_eq!(parse_size("1T"), Ok(1 << 40));
+}
+
+/// Tests that sizes which aren't a number with a known suffix are errors.
+#[test]
+fn parse_size_malformed() {
+    for size in ["", "K", "-1", "1.5K", "1KB", "1X", " 1"] {
+        assert_eq!(parse_size(size), Err(SizeParseError), "{size:?}");
+    }
+}
+
+/// Tests that sizes which don't fit in 64 bits are errors, rather than
+/// wrapping.
+#[test]
+fn parse_size_overflow() {
+    assert_eq!(parse_size("18446744073709551615"), Ok(u64::MAX));
+    assert_eq!(parse_size("18446744073709551616"), Err(SizeParseError));
+    assert_eq!(parse_size("16777215T"), Ok(16_777_215 << 40));
+    assert_eq!(parse_size("16777216T"), Err(SizeParseError));
+    assert_eq!(parse_size("17179869184G"), Err(SizeParseError));
+}
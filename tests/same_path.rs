@@ -0,0 +1,184 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Tests for recognizing aliases of the same path.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+#[cfg(unix)]
+use fcmp::canonical_path;
+use fcmp::partial_cmp_paths;
+use fcmp::Comparator;
+use fcmp::CompareOutcome;
+use fcmp::FcmpError;
+#[cfg(unix)]
+use fcmp::Incomparable;
+use fcmp::MissingFileBehavior;
+#[cfg(unix)]
+use fcmp::SymlinkPolicy;
+use pretty_assertions::assert_eq;
+
+// Standard library imports.
+use std::cmp::Ordering;
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::time::Duration;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helpers
+////////////////////////////////////////////////////////////////////////////////
+/// A temporary directory which is removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates an empty temporary directory unique to the given test.
+    fn new(test: &str) -> Self {
+        let path = std::env::temp_dir()
+            .join(format!("fcmp-{}-{test}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("create temporary directory");
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Returns a `Comparator` which fails if a missing file is opened, so that
+/// comparing a missing file succeeds only if it is recognized as the same
+/// path without being opened.
+fn missing_is_error(canonicalize_paths: bool) -> Comparator {
+    Comparator {
+        missing: MissingFileBehavior::Error,
+        canonicalize_paths,
+        ..Comparator::default()
+    }
+}
+
+/// Asserts that the given comparison failed because the file at the given
+/// path is missing.
+fn assert_missing(result: Result<CompareOutcome, FcmpError>, path: &Path) {
+    match result {
+        Err(FcmpError::Missing { path: missing }) => {
+            assert_eq!(missing, path);
+        },
+        other => panic!("expected Missing error, got {other:?}"),
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Tests
+////////////////////////////////////////////////////////////////////////////////
+/// Tests that `d/./foo` and `d/foo` are the same path, and that `d/e/../foo`
+/// is the same path as `d/foo`.
+#[test]
+fn relative_aliases_are_same_path() {
+    let dir = TempDir::new("relative");
+    std::fs::create_dir(dir.0.join("e")).expect("create directory");
+    let foo = dir.0.join("foo");
+    let dot = dir.0.join("./foo");
+    let parent = dir.0.join("e/../foo");
+
+    let same = CompareOutcome::Ordered(Ordering::Equal);
+    let comparator = missing_is_error(true);
+    assert_eq!(
+        partial_cmp_paths(&dot, &foo, &comparator).expect("compare ./foo"),
+        same);
+    assert_eq!(
+        partial_cmp_paths(&parent, &foo, &comparator)
+            .expect("compare e/../foo"),
+        same);
+
+    // A `.` component is ignored when comparing paths, but resolving `..`
+    // requires canonicalization.
+    let comparator = missing_is_error(false);
+    assert_eq!(
+        partial_cmp_paths(&dot, &foo, &comparator).expect("compare ./foo"),
+        same);
+    assert_missing(partial_cmp_paths(&parent, &foo, &comparator), &parent);
+}
+
+/// Tests that a path through a symbolic link to a directory is the same path
+/// as the path through the directory, with either symbolic link policy.
+#[cfg(unix)]
+#[test]
+fn symlinked_directory_alias_is_same_path() {
+    let dir = TempDir::new("symlink-dir");
+    std::fs::create_dir(dir.0.join("d")).expect("create directory");
+    std::os::unix::fs::symlink(dir.0.join("d"), dir.0.join("link"))
+        .expect("create link");
+    let direct = dir.0.join("d/foo");
+    let linked = dir.0.join("link/foo");
+
+    // Only the last component of a path is left unresolved by `-P`.
+    for symlinks in [SymlinkPolicy::Follow, SymlinkPolicy::NoFollow] {
+        let comparator = Comparator {
+            symlinks,
+            ..missing_is_error(true)
+        };
+        assert_eq!(
+            partial_cmp_paths(&linked, &direct, &comparator)
+                .expect("compare linked path"),
+            CompareOutcome::Ordered(Ordering::Equal),
+            "{symlinks:?}");
+    }
+
+    assert_missing(
+        partial_cmp_paths(&linked, &direct, &missing_is_error(false)),
+        &linked);
+}
+
+/// Tests that a symbolic link is the same path as its target when links are
+/// followed with `-L`, and is its own path with `-P`.
+///
+/// Both files were just modified, so they are excluded by a settle time
+/// unless they are recognized as the same path before being opened. A
+/// followed link can't be told apart from its target once opened.
+#[cfg(unix)]
+#[test]
+fn symlink_alias_depends_on_policy() {
+    let dir = TempDir::new("symlink");
+    let target = dir.0.join("foo");
+    let link = dir.0.join("link");
+    std::fs::write(&target, "content").expect("write target");
+    std::os::unix::fs::symlink(&target, &link).expect("create link");
+
+    assert_eq!(canonical_path(&link, true), canonical_path(&target, true));
+    assert_ne!(canonical_path(&link, false), canonical_path(&target, false));
+
+    let unsettled = |symlinks, canonicalize_paths| Comparator {
+        symlinks,
+        canonicalize_paths,
+        settle_time: Duration::from_secs(3600),
+        ..Comparator::default()
+    };
+    let excluded = CompareOutcome::Incomparable(Incomparable::Excluded);
+
+    let follow = unsettled(SymlinkPolicy::Follow, true);
+    assert_eq!(
+        partial_cmp_paths(&link, &target, &follow)
+            .expect("compare followed link"),
+        CompareOutcome::Ordered(Ordering::Equal));
+
+    let no_follow = unsettled(SymlinkPolicy::NoFollow, true);
+    assert_eq!(
+        partial_cmp_paths(&link, &target, &no_follow)
+            .expect("compare unfollowed link"),
+        excluded);
+
+    let not_canonical = unsettled(SymlinkPolicy::Follow, false);
+    assert_eq!(
+        partial_cmp_paths(&link, &target, &not_canonical)
+            .expect("compare followed link without canonicalization"),
+        excluded);
+}
@@ -0,0 +1,118 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Tests for diffing special files.
+////////////////////////////////////////////////////////////////////////////////
+#![cfg(unix)]
+
+// External library imports.
+use fcmp::content_equal;
+use fcmp::DiffOp;
+use fcmp::SampleOptions;
+use fcmp::TextOptions;
+use pretty_assertions::assert_eq;
+
+// Standard library imports.
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::mpsc;
+use std::time::Duration;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helpers
+////////////////////////////////////////////////////////////////////////////////
+/// A temporary directory which is removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates an empty temporary directory unique to the given test.
+    fn new(test: &str) -> Self {
+        let path = std::env::temp_dir()
+            .join(format!("fcmp-{}-{test}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("create temporary directory");
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Creates a FIFO at the given path.
+fn mkfifo(path: &Path) {
+    let status = Command::new("mkfifo")
+        .arg(path)
+        .status()
+        .expect("run mkfifo");
+    assert!(status.success(), "mkfifo failed: {status}");
+}
+
+/// Runs the given function on another thread, panicking if it doesn't
+/// finish in time. A diff which opens a FIFO blocks until it is written.
+fn with_timeout<F, T>(f: F) -> T
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    let _ = std::thread::spawn(move || tx.send(f()));
+    rx.recv_timeout(Duration::from_secs(10))
+        .expect("diff blocked on a special file")
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Tests
+////////////////////////////////////////////////////////////////////////////////
+/// Tests that every kind of content diff reports a FIFO as different from a
+/// regular file without opening it.
+#[test]
+fn fifo_differs_without_blocking() {
+    let dir = TempDir::new("fifo");
+    let file = dir.0.join("file");
+    let fifo = dir.0.join("fifo");
+    std::fs::write(&file, "content").expect("write file");
+    mkfifo(&fifo);
+
+    let diff_ops = [
+        DiffOp::internal(),
+        DiffOp::posix_cmp(),
+        DiffOp::posix_diff(),
+        DiffOp::Text(TextOptions::default()),
+        DiffOp::Sampled(SampleOptions::default()),
+        DiffOp::custom(|_, _| panic!("special file passed to custom diff")),
+    ];
+    for diff_op in diff_ops {
+        let (file, fifo) = (file.clone(), fifo.clone());
+        let name = format!("{diff_op:?}");
+        let different = with_timeout(move || diff_op.diff(&file, &fifo));
+        assert_eq!(different.ok(), Some(true), "{name}");
+    }
+}
+
+/// Tests that `content_equal` doesn't block on a FIFO when diffing with a
+/// subprocess.
+#[test]
+fn content_equal_skips_fifo() {
+    let dir = TempDir::new("content-equal");
+    let file = dir.0.join("file");
+    let fifo = dir.0.join("fifo");
+    std::fs::write(&file, "content").expect("write file");
+    mkfifo(&fifo);
+
+    let equal = with_timeout(move || content_equal(
+        [file.as_path(), fifo.as_path()],
+        0,
+        &DiffOp::posix_cmp()));
+
+    assert_eq!(equal, vec![0]);
+}
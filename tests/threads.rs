@@ -0,0 +1,189 @@
+////////////////////////////////////////////////////////////////////////////////
+// Fcmp file compare utility
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Tests for sharing comparisons between threads.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+use fcmp::compare_all;
+use fcmp::sort_paths;
+use fcmp::Comparator;
+use fcmp::CompareStats;
+use fcmp::DiffOp;
+use fcmp::FileTiming;
+use fcmp::Manifest;
+use fcmp::StatsRecorder;
+use fcmp::TimingRecorder;
+use pretty_assertions::assert_eq;
+
+// Standard library imports.
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helpers
+////////////////////////////////////////////////////////////////////////////////
+/// The number of threads sharing a comparison.
+const THREADS: u64 = 8;
+
+/// The number of times each thread runs the comparisons.
+const ROUNDS: u64 = 4;
+
+/// A temporary directory which is removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates an empty temporary directory unique to the given test.
+    fn new(test: &str) -> Self {
+        let path = std::env::temp_dir()
+            .join(format!("fcmp-{}-{test}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("create temporary directory");
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Creates files in nested directories under the given root. Every other
+/// file has the same content, so internal diffs are run between them.
+fn populate(root: &Path) {
+    let start = SystemTime::now() - Duration::from_secs(3600);
+    for i in 0..12_u64 {
+        let dir = root.join(format!("d{}", i % 3));
+        std::fs::create_dir_all(&dir).expect("create directory");
+        let path = dir.join(format!("f{i}"));
+        let content = if i % 2 == 0 { "same".to_string() } else {
+            format!("content {i}")
+        };
+        std::fs::write(&path, content).expect("write file");
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(
+                start + Duration::from_secs(i * 60)))
+            .expect("set modification time");
+    }
+}
+
+/// Returns a `Comparator` diffing content internally and recording its stats
+/// and timings.
+fn comparator() -> Comparator {
+    Comparator {
+        diff_op: DiffOp::internal(),
+        stats: Some(Arc::new(StatsRecorder::new())),
+        timings: Some(Arc::new(TimingRecorder::new())),
+        ..Comparator::default()
+    }
+}
+
+/// The results of one round of comparisons.
+#[derive(Debug, PartialEq, Eq)]
+struct Round {
+    /// The paths found by walking the manifest.
+    paths: Vec<PathBuf>,
+    /// The index of the newest file.
+    newest: usize,
+    /// The indices of the files from newest to oldest.
+    sorted: Vec<usize>,
+}
+
+/// Walks the given root with the given `Manifest`, then selects and sorts the
+/// files found with the given `Comparator`.
+fn round(manifest: &Manifest, root: &Path, comparator: &Comparator) -> Round {
+    let paths = manifest.walk(root).expect("walk directory");
+    let newest = compare_all(
+            paths.iter().map(PathBuf::as_path),
+            false,
+            comparator)
+        .expect("select newest file");
+    let sorted = sort_paths(
+            paths.iter().map(PathBuf::as_path),
+            false,
+            comparator)
+        .expect("sort files");
+    Round { paths, newest, sorted }
+}
+
+/// Returns the number of directories cached by a `Manifest` after walking
+/// the given root once.
+fn single_manifest_len(root: &Path) -> usize {
+    let manifest = Manifest::new();
+    let _ = manifest.walk(root).expect("walk directory");
+    manifest.len()
+}
+
+/// Returns the counts recorded in the given stats, without the wall time.
+fn counts(stats: CompareStats) -> (u64, u64, u64) {
+    (stats.files_stated, stats.diffs, stats.bytes_read)
+}
+
+/// Returns the bytes read from each file in the given timings.
+fn bytes_read(timings: &BTreeMap<PathBuf, FileTiming>)
+    -> BTreeMap<PathBuf, u64>
+{
+    timings.iter()
+        .map(|(path, timing)| (path.clone(), timing.bytes_read))
+        .collect()
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Tests
+////////////////////////////////////////////////////////////////////////////////
+/// Tests that a `Comparator` and `Manifest` shared by several threads give the
+/// same results as on one thread, and that no recorded work is lost.
+#[test]
+fn shared_comparator_matches_single_thread() {
+    let dir = TempDir::new("threads");
+    populate(&dir.0);
+
+    // Run one round on a single thread for the expected results.
+    let single = comparator();
+    let expected = round(&Manifest::new(), &dir.0, &single);
+    let single_stats = single.stats.as_ref().expect("stats").stats();
+    let single_timings = single.timings.as_ref().expect("timings").timings();
+    assert_eq!(expected.paths.len(), 12);
+    assert!(single_stats.diffs > 0, "no content diffs: {single_stats:?}");
+
+    let shared = comparator();
+    let manifest = Manifest::new();
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..THREADS)
+            .map(|_| scope.spawn(|| (0..ROUNDS)
+                .map(|_| round(&manifest, &dir.0, &shared))
+                .collect::<Vec<_>>()))
+            .collect();
+        for worker in workers {
+            for result in worker.join().expect("join worker") {
+                assert_eq!(result, expected);
+            }
+        }
+    });
+
+    let runs = THREADS * ROUNDS;
+    let stats = shared.stats.as_ref().expect("stats").stats();
+    let (stated, diffs, read) = counts(single_stats);
+    assert_eq!(counts(stats), (stated * runs, diffs * runs, read * runs));
+
+    let timings = shared.timings.as_ref().expect("timings").timings();
+    let expected_read: BTreeMap<PathBuf, u64> = bytes_read(&single_timings)
+        .into_iter()
+        .map(|(path, read)| (path, read * runs))
+        .collect();
+    assert_eq!(bytes_read(&timings), expected_read);
+    assert_eq!(manifest.len(), single_manifest_len(&dir.0));
+}